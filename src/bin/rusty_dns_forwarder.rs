@@ -0,0 +1,48 @@
+use rusty_dns::{forwarder::*, resolver::*, DNS_PORT};
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+const USAGE: &str =
+    "Usage: rusty_dns_forwarder [--listen ADDRESS:PORT] [--max-stale SECONDS] [UPSTREAM[:PORT]...]";
+
+fn main() -> Result<(), String> {
+    let mut address = format!("0.0.0.0:{}", DNS_PORT);
+    let mut max_stale = DEFAULT_MAX_STALE;
+    let mut upstreams = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--listen" {
+            address = args.next().ok_or(USAGE)?;
+        } else if arg == "--max-stale" {
+            max_stale = args
+                .next()
+                .and_then(|seconds| seconds.parse().ok())
+                .ok_or(USAGE)?;
+        } else if let Ok(upstream) = arg.parse::<SocketAddr>() {
+            upstreams.push(upstream);
+        } else if let Ok(upstream) = arg.parse::<IpAddr>() {
+            upstreams.push(SocketAddr::new(upstream, DNS_PORT as u16));
+        } else {
+            return Err(USAGE.into());
+        }
+    }
+
+    // Without upstreams given, forward to the name servers of the system resolver.
+    if upstreams.is_empty() {
+        upstreams = ResolverConfig::system()?.nameservers;
+    }
+
+    let mut config = ForwarderConfig::new(upstreams);
+    config.cache.max_stale = max_stale;
+    let handle = Forwarder::new(config)?.spawn(&address)?;
+    println!(
+        "Forwarding on {}, enter \"quit\" to shut down",
+        handle.local_address()
+    );
+
+    // Run detached, with standard input at its end, the server keeps serving until it is killed.
+    handle.serve_until_quit(std::io::stdin().lock());
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use rusty_dns::{server::*, zone::*, DNS_PORT};
+use std::env;
+use std::path::Path;
+
+const USAGE: &str = "Usage: rusty_dns_server [--listen ADDRESS:PORT] ORIGIN=ZONE_FILE...";
+
+fn main() -> Result<(), String> {
+    let mut address = format!("0.0.0.0:{}", DNS_PORT);
+    let mut zones = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--listen" {
+            address = args.next().ok_or(USAGE)?;
+        } else if let Some((origin, path)) = arg.split_once('=') {
+            let origin = rusty_dns::domain_name::normalize_domain_name(&String::from(origin));
+            zones.push(Zone::from_master_file(Path::new(path), &origin)?);
+        } else {
+            return Err(USAGE.into());
+        }
+    }
+
+    if zones.is_empty() {
+        return Err(USAGE.into());
+    }
+
+    let handle = AuthoritativeServer::new(zones)?.spawn(&address)?;
+    println!(
+        "Serving on {}, enter \"quit\" to shut down",
+        handle.local_address()
+    );
+
+    // Run detached, with standard input at its end, the server keeps serving until it is killed.
+    handle.serve_until_quit(std::io::stdin().lock());
+
+    Ok(())
+}
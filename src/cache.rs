@@ -0,0 +1,677 @@
+use crate::{
+    dns_packet::{dns_resource_record::*, *},
+    domain_name::*,
+    qtypes::*,
+    rcodes::*,
+    types::*,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The default upper bound on the TTL of cached records, one day.
+pub const DEFAULT_MAX_CACHE_TTL: u32 = 86400;
+
+/// The default upper bound on the size of a cache, in octets of the wire format of the cached records.
+pub const DEFAULT_MAX_CACHE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Limits applied by a [Cache].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    /// TTLs below this are raised to it before caching.
+    pub min_ttl: u32,
+    /// TTLs above this are lowered to it before caching.
+    pub max_ttl: u32,
+    /// The size the cache is kept under by evicting the least recently used entries, measured as the
+    /// wire format size of the cached records.
+    pub max_size: usize,
+    /// How many seconds entries are kept after they expire, to be served by [Cache::get_stale] when
+    /// fresh answers cannot be had (RFC 8767).
+    pub max_stale: u32,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            min_ttl: 0,
+            max_ttl: DEFAULT_MAX_CACHE_TTL,
+            max_size: DEFAULT_MAX_CACHE_SIZE,
+            max_stale: 0,
+        }
+    }
+}
+
+/// A cached answer, with TTLs counting down from when it was cached.
+#[derive(Debug, Clone)]
+pub enum CachedAnswer {
+    /// The RRset of the requested name, type and class.
+    Records(Vec<DnsResourceRecord>),
+    /// The name does not exist, with the SOA record of the zone saying so.
+    NameError(DnsResourceRecord),
+    /// The name exists but has no records of the requested type, with the SOA record of its zone.
+    NoData(DnsResourceRecord),
+}
+
+/// Counters describing the use of a [Cache].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStatistics {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups that found nothing, or only expired entries.
+    pub misses: u64,
+    /// Entries removed to stay under the size limit.
+    pub evictions: u64,
+    /// The number of entries held.
+    pub entries: usize,
+    /// The size of the entries held, as counted against [CacheConfig::max_size].
+    pub size: usize,
+}
+
+/// Entries are found by lowercased labels, so names differing only in case share them, and names are
+/// taken as absolute whether or not they end with a '.'. A name error
+/// applies to every type of the name, and is kept without one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    labels: Vec<Vec<u8>>,
+    rrtype: Option<u16>,
+    class: u16,
+}
+
+impl CacheKey {
+    fn new(name: &str, rrtype: Option<u16>, class: u16) -> Result<CacheKey, String> {
+        let labels = split_domain_name(name)?
+            .0
+            .into_iter()
+            .map(|label| label.to_ascii_lowercase())
+            .collect();
+
+        Ok(CacheKey {
+            labels,
+            rrtype,
+            class,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    answer: CachedAnswer,
+    cached_at: Instant,
+    expires: Instant,
+    size: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Keys of the entries by when they were last used, least recent first.
+    recency: BTreeMap<u64, CacheKey>,
+    clock: u64,
+    size: usize,
+    statistics: CacheStatistics,
+}
+
+impl CacheState {
+    fn remove(&mut self, key: &CacheKey) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        self.size -= entry.size;
+
+        Some(entry)
+    }
+
+    fn touch(&mut self, key: &CacheKey) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = self.clock;
+            self.recency.insert(self.clock, key.clone());
+        }
+    }
+}
+
+/// A thread safe cache of RRsets and negative answers (RFC 2308), keyed by name, type and class.
+/// Entries expire with the TTL they were cached with, and are handed out with the TTL remaining.
+#[derive(Debug, Default)]
+pub struct Cache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl Cache {
+    /// Create an empty cache with the limits of config.
+    pub fn new(config: CacheConfig) -> Cache {
+        Cache {
+            config,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// The limits of the cache.
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// The current statistics of the cache.
+    pub fn statistics(&self) -> CacheStatistics {
+        let state = self.lock();
+        CacheStatistics {
+            entries: state.entries.len(),
+            size: state.size,
+            ..state.statistics
+        }
+    }
+
+    /// Remove every entry, keeping the statistics.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.recency.clear();
+        state.size = 0;
+    }
+
+    /// Look up the cached answer for name, rrtype and class. A cached name error answers for any type.
+    pub fn get(&self, name: &str, rrtype: u16, class: u16) -> Option<CachedAnswer> {
+        self.get_at(name, rrtype, class, Instant::now())
+    }
+
+    /// Look up the cached answer for name, rrtype and class like [Cache::get], also returning answers
+    /// that expired less than [CacheConfig::max_stale] seconds ago. Their TTLs are 0.
+    pub fn get_stale(&self, name: &str, rrtype: u16, class: u16) -> Option<CachedAnswer> {
+        self.find(name, rrtype, class, Instant::now(), true)
+    }
+
+    /// Cache the RRset records, which must share a name, type and class. The RRset is cached with the
+    /// lowest TTL of its records, replacing anything cached for it before.
+    pub fn insert_rrset(&self, records: &[DnsResourceRecord]) -> Result<(), String> {
+        self.insert_rrset_at(records, Instant::now())
+    }
+
+    /// Cache the answer section of a response by RRset, and a name error or no data answer for the end
+    /// of the CNAME chain starting at the question. Negative answers are cached for the lower of the TTL
+    /// and MINIMUM of the SOA record in the authority section, and not at all without one. Truncated
+    /// responses and those with other RCODEs are not cached.
+    pub fn insert_response(&self, response: &DnsPacket) -> Result<(), String> {
+        self.insert_response_at(response, Instant::now())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn clamp_ttl(&self, ttl: u32) -> u32 {
+        ttl.min(self.config.max_ttl).max(self.config.min_ttl)
+    }
+
+    fn get_at(&self, name: &str, rrtype: u16, class: u16, now: Instant) -> Option<CachedAnswer> {
+        self.find(name, rrtype, class, now, false)
+    }
+
+    fn find(
+        &self,
+        name: &str,
+        rrtype: u16,
+        class: u16,
+        now: Instant,
+        stale: bool,
+    ) -> Option<CachedAnswer> {
+        let keys = [
+            CacheKey::new(name, Some(rrtype), class),
+            CacheKey::new(name, None, class),
+        ];
+
+        let mut state = self.lock();
+        for key in keys.iter().flatten() {
+            let (cached_at, expires) = match state.entries.get(key) {
+                Some(entry) => (entry.cached_at, entry.expires),
+                None => continue,
+            };
+            if expires <= now {
+                let stale_until = expires + Duration::from_secs(self.config.max_stale as u64);
+                if stale_until <= now {
+                    state.remove(key);
+                    continue;
+                } else if !stale {
+                    continue;
+                }
+            }
+
+            state.touch(key);
+            state.statistics.hits += 1;
+
+            let elapsed = now.saturating_duration_since(cached_at).as_secs();
+            let decrement = |record: &DnsResourceRecord| DnsResourceRecord {
+                ttl: record
+                    .ttl
+                    .saturating_sub(elapsed.min(u32::MAX as u64) as u32),
+                ..record.clone()
+            };
+            return Some(match &state.entries[key].answer {
+                CachedAnswer::Records(records) => {
+                    CachedAnswer::Records(records.iter().map(decrement).collect())
+                }
+                CachedAnswer::NameError(soa) => CachedAnswer::NameError(decrement(soa)),
+                CachedAnswer::NoData(soa) => CachedAnswer::NoData(decrement(soa)),
+            });
+        }
+
+        state.statistics.misses += 1;
+        None
+    }
+
+    fn insert_rrset_at(&self, records: &[DnsResourceRecord], now: Instant) -> Result<(), String> {
+        let first = records.first().ok_or("cannot cache an empty RRset")?;
+        if records.iter().any(|record| {
+            record.rrtype != first.rrtype
+                || record.class != first.class
+                || !domain_names_equal(&record.name, &first.name)
+        }) {
+            return Err(format!(
+                "records cached with {} {} are not one RRset",
+                first.name, first.rrtype
+            ));
+        } else if first.rrtype == DNS_TYPE_OPT {
+            return Err("cannot cache OPT pseudo-records".into());
+        }
+
+        let ttl = self.clamp_ttl(records.iter().map(|record| record.ttl).min().unwrap_or(0));
+        let records = records
+            .iter()
+            .map(|record| DnsResourceRecord {
+                ttl,
+                ..record.clone()
+            })
+            .collect();
+        let key = CacheKey::new(&first.name, Some(first.rrtype), first.class)?;
+        self.insert_entry(key, CachedAnswer::Records(records), ttl, now);
+
+        Ok(())
+    }
+
+    fn insert_response_at(&self, response: &DnsPacket, now: Instant) -> Result<(), String> {
+        let rcode = response.header.rcode;
+        let question = match response.question.first() {
+            Some(question) if !response.header.tc => question,
+            _ => return Ok(()),
+        };
+        if rcode != DNS_RCODE_NO_ERROR && rcode != DNS_RCODE_NAME_ERROR {
+            return Ok(());
+        }
+
+        // Only records on the alias chain from the question are cached, so that a server cannot add
+        // records for unrelated names to the cache.
+        let chain = response.alias_chain(&question.qname)?;
+        let on_chain = |record: &&DnsResourceRecord| {
+            chain.iter().any(|name| {
+                domain_names_equal(&record.name, name)
+                    || (record.rrtype == DNS_TYPE_DNAME && is_subdomain_of(name, &record.name))
+            })
+        };
+
+        let mut rrsets: Vec<Vec<DnsResourceRecord>> = Vec::new();
+        for record in response.answer.iter().filter(on_chain) {
+            let rrset = rrsets.iter_mut().find(|rrset| {
+                rrset[0].rrtype == record.rrtype
+                    && rrset[0].class == record.class
+                    && domain_names_equal(&rrset[0].name, &record.name)
+            });
+            match rrset {
+                Some(rrset) => rrset.push(record.clone()),
+                None => rrsets.push(vec![record.clone()]),
+            }
+        }
+        for rrset in rrsets.iter() {
+            self.insert_rrset_at(rrset, now)?;
+        }
+
+        let name = chain[chain.len() - 1].clone();
+        let answered = response.answer.iter().any(|record| {
+            domain_names_equal(&record.name, &name)
+                && (record.rrtype == question.qtype || question.qtype == DNS_QTYPE_ANY)
+        });
+        if rcode == DNS_RCODE_NO_ERROR && answered {
+            return Ok(());
+        }
+
+        let soa = response
+            .authority
+            .iter()
+            .find(|record| record.rrtype == DNS_TYPE_SOA && is_subdomain_of(&name, &record.name));
+        let mut soa = match soa {
+            Some(soa) => soa.clone(),
+            None => return Ok(()),
+        };
+        if let DnsResourceRecordData::SOA((_, _, _, _, _, _, minimum)) = soa.rdata {
+            soa.ttl = self.clamp_ttl(soa.ttl.min(minimum));
+        }
+
+        let (key, answer) = if rcode == DNS_RCODE_NAME_ERROR {
+            let key = CacheKey::new(&name, None, question.qclass)?;
+            (key, CachedAnswer::NameError(soa.clone()))
+        } else {
+            let key = CacheKey::new(&name, Some(question.qtype), question.qclass)?;
+            (key, CachedAnswer::NoData(soa.clone()))
+        };
+        self.insert_entry(key, answer, soa.ttl, now);
+
+        Ok(())
+    }
+
+    fn insert_entry(&self, key: CacheKey, answer: CachedAnswer, ttl: u32, now: Instant) {
+        let record_size = |record: &DnsResourceRecord| {
+            let rdata_size = record
+                .rdata
+                .to_bytes()
+                .map(|rdata| rdata.len())
+                .unwrap_or(record.rdlength as usize);
+            domain_name_wire_length(&record.name) + 10 + rdata_size
+        };
+        let size = match &answer {
+            CachedAnswer::Records(records) => records.iter().map(record_size).sum(),
+            CachedAnswer::NameError(soa) | CachedAnswer::NoData(soa) => record_size(soa),
+        };
+
+        let mut state = self.lock();
+        state.remove(&key);
+        if ttl == 0 || size > self.config.max_size {
+            return;
+        }
+
+        while state.size + size > self.config.max_size {
+            let oldest = match state.recency.values().next() {
+                Some(oldest) => oldest.clone(),
+                None => break,
+            };
+            state.remove(&oldest);
+            state.statistics.evictions += 1;
+        }
+
+        state.clock += 1;
+        let last_used = state.clock;
+        state.recency.insert(last_used, key.clone());
+        state.size += size;
+        state.entries.insert(
+            key,
+            CacheEntry {
+                answer,
+                cached_at: now,
+                expires: now + Duration::from_secs(ttl as u64),
+                size,
+                last_used,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{classes::*, zone::parser::*};
+
+    fn records(text: &str) -> Result<Vec<DnsResourceRecord>, String> {
+        parse_master_file_str(&format!("$TTL 3600\n{}", text), "example.com.")
+    }
+
+    fn response(
+        qname: &str,
+        qtype: u16,
+        rcode: u8,
+        answer: &str,
+        authority: &str,
+    ) -> Result<DnsPacket, String> {
+        let mut response = DnsPacket::new(&qname.to_string(), qtype)?;
+        response.header.qr = true;
+        response.header.rcode = rcode;
+        response.answer = records(answer)?;
+        response.authority = records(authority)?;
+        response.update_counts();
+
+        Ok(response)
+    }
+
+    fn ttls(answer: Option<CachedAnswer>) -> Vec<u32> {
+        match answer {
+            Some(CachedAnswer::Records(records)) => {
+                records.iter().map(|record| record.ttl).collect()
+            }
+            Some(CachedAnswer::NameError(soa)) | Some(CachedAnswer::NoData(soa)) => vec![soa.ttl],
+            None => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_cache_rrsets() -> Result<(), String> {
+        let cache = Cache::new(CacheConfig {
+            min_ttl: 60,
+            max_ttl: 3600,
+            ..CacheConfig::default()
+        });
+        let now = Instant::now();
+        let later = |secs: u64| now + Duration::from_secs(secs);
+
+        cache.insert_rrset_at(&records("www 300 A 192.0.2.1\nwww 600 A 192.0.2.2\n")?, now)?;
+        assert_eq!(
+            ttls(cache.get_at("www.example.com.", DNS_TYPE_A, DNS_CLASS_IN, now)),
+            [300, 300]
+        );
+        assert_eq!(
+            ttls(cache.get_at("WWW.Example.COM", DNS_TYPE_A, DNS_CLASS_IN, later(100))),
+            [200, 200]
+        );
+        assert!(cache
+            .get_at("www.example.com.", DNS_TYPE_AAAA, DNS_CLASS_IN, now)
+            .is_none());
+        assert!(cache
+            .get_at("www.example.com.", DNS_TYPE_A, DNS_CLASS_CH, now)
+            .is_none());
+        assert!(cache
+            .get_at("www.example.com.", DNS_TYPE_A, DNS_CLASS_IN, later(300))
+            .is_none());
+
+        // TTLs are clamped to the configured bounds.
+        cache.insert_rrset_at(&records("a 10 TXT short\nb 86400 TXT long\n")?[..1], now)?;
+        cache.insert_rrset_at(&records("b 86400 TXT long\n")?, now)?;
+        assert_eq!(
+            ttls(cache.get_at("a.example.com.", DNS_TYPE_TXT, DNS_CLASS_IN, now)),
+            [60]
+        );
+        assert_eq!(
+            ttls(cache.get_at("b.example.com.", DNS_TYPE_TXT, DNS_CLASS_IN, now)),
+            [3600]
+        );
+
+        assert_eq!(
+            cache.statistics(),
+            CacheStatistics {
+                hits: 4,
+                misses: 3,
+                evictions: 0,
+                entries: 2,
+                size: (15 + 10 + 6) + (15 + 10 + 5),
+            }
+        );
+        cache.clear();
+        assert_eq!(cache.statistics().entries, 0);
+        assert_eq!(cache.statistics().size, 0);
+
+        assert!(cache.insert_rrset(&[]).is_err());
+        assert!(cache
+            .insert_rrset(&records("a A 192.0.2.1\nb A 192.0.2.2\n")?)
+            .is_err());
+        assert!(cache
+            .insert_rrset(&records("a A 192.0.2.1\na AAAA ::1\n")?)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_negative_answers() -> Result<(), String> {
+        let cache = Cache::default();
+        let now = Instant::now();
+        let soa = "@ 3600 SOA ns host 1 2 3 4 300\n";
+
+        // The name error applies to the end of the CNAME chain, for every type.
+        let name_error = response(
+            "alias.example.com.",
+            DNS_TYPE_A,
+            DNS_RCODE_NAME_ERROR,
+            "alias 600 CNAME missing\n",
+            soa,
+        )?;
+        cache.insert_response_at(&name_error, now)?;
+        assert_eq!(
+            ttls(cache.get_at("alias.example.com.", DNS_TYPE_CNAME, DNS_CLASS_IN, now)),
+            [600]
+        );
+        match cache.get_at("missing.example.com.", DNS_TYPE_MX, DNS_CLASS_IN, now) {
+            Some(CachedAnswer::NameError(soa)) => assert_eq!(soa.ttl, 300),
+            answer => return Err(format!("expected a name error, got {:?}", answer)),
+        }
+
+        let no_data = response(
+            "www.example.com.",
+            DNS_TYPE_AAAA,
+            DNS_RCODE_NO_ERROR,
+            "",
+            soa,
+        )?;
+        cache.insert_response_at(&no_data, now)?;
+        match cache.get_at(
+            "www.example.com.",
+            DNS_TYPE_AAAA,
+            DNS_CLASS_IN,
+            now + Duration::from_secs(100),
+        ) {
+            Some(CachedAnswer::NoData(soa)) => assert_eq!(soa.ttl, 200),
+            answer => return Err(format!("expected no data, got {:?}", answer)),
+        }
+        assert!(cache
+            .get_at("www.example.com.", DNS_TYPE_A, DNS_CLASS_IN, now)
+            .is_none());
+
+        // Without an SOA, or when truncated, there is nothing to cache.
+        let referral = response(
+            "ftp.example.com.",
+            DNS_TYPE_A,
+            DNS_RCODE_NO_ERROR,
+            "",
+            "@ NS ns\n",
+        )?;
+        cache.insert_response_at(&referral, now)?;
+        let mut truncated = response("ftp.example.com.", DNS_TYPE_A, DNS_RCODE_NO_ERROR, "", soa)?;
+        truncated.header.tc = true;
+        cache.insert_response_at(&truncated, now)?;
+        let failure = response(
+            "ftp.example.com.",
+            DNS_TYPE_A,
+            DNS_RCODE_SERVER_ERROR,
+            "",
+            soa,
+        )?;
+        cache.insert_response_at(&failure, now)?;
+        assert!(cache
+            .get_at("ftp.example.com.", DNS_TYPE_A, DNS_CLASS_IN, now)
+            .is_none());
+        assert_eq!(cache.statistics().entries, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_alias_chain_only() -> Result<(), String> {
+        let cache = Cache::default();
+        let now = Instant::now();
+
+        // Records for names off the alias chain from the question are not cached.
+        let poisoned = response(
+            "www.example.com.",
+            DNS_TYPE_A,
+            DNS_RCODE_NO_ERROR,
+            "www CNAME host.sub\nsub DNAME example.net.\nhost.example.net. A 192.0.2.1\n\
+             ftp A 192.0.2.2\nns.example.org. A 192.0.2.3\n",
+            "",
+        )?;
+        cache.insert_response_at(&poisoned, now)?;
+        for (name, rrtype) in [
+            ("www.example.com.", DNS_TYPE_CNAME),
+            ("sub.example.com.", DNS_TYPE_DNAME),
+            ("host.example.net.", DNS_TYPE_A),
+        ]
+        .iter()
+        {
+            assert!(cache.get_at(name, *rrtype, DNS_CLASS_IN, now).is_some());
+        }
+        assert!(cache
+            .get_at("ftp.example.com.", DNS_TYPE_A, DNS_CLASS_IN, now)
+            .is_none());
+        assert!(cache
+            .get_at("ns.example.org.", DNS_TYPE_A, DNS_CLASS_IN, now)
+            .is_none());
+        assert_eq!(cache.statistics().entries, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_stale() -> Result<(), String> {
+        let cache = Cache::new(CacheConfig {
+            max_stale: 100,
+            ..CacheConfig::default()
+        });
+        let now = Instant::now();
+        let later = |secs: u64| now + Duration::from_secs(secs);
+        cache.insert_rrset_at(&records("www 300 A 192.0.2.1\n")?, now)?;
+
+        let stale =
+            |at: Instant| ttls(cache.find("www.example.com.", DNS_TYPE_A, DNS_CLASS_IN, at, true));
+        assert_eq!(stale(later(100)), [200]);
+        assert!(cache
+            .get_at("www.example.com.", DNS_TYPE_A, DNS_CLASS_IN, later(350))
+            .is_none());
+        assert_eq!(stale(later(350)), [0]);
+        assert!(stale(later(400)).is_empty());
+        assert_eq!(cache.statistics().entries, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_eviction() -> Result<(), String> {
+        // Each of these RRsets is 15 + 10 + 4 octets.
+        let cache = Cache::new(CacheConfig {
+            max_size: 3 * 29,
+            ..CacheConfig::default()
+        });
+        let now = Instant::now();
+
+        for name in ["a", "b", "c"].iter() {
+            cache.insert_rrset_at(&records(&format!("{} A 192.0.2.1\n", name))?, now)?;
+        }
+        assert!(cache
+            .get_at("a.example.com.", DNS_TYPE_A, DNS_CLASS_IN, now)
+            .is_some());
+        cache.insert_rrset_at(&records("d A 192.0.2.1\n")?, now)?;
+
+        let cached: Vec<bool> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| {
+                let name = format!("{}.example.com.", name);
+                cache.get_at(&name, DNS_TYPE_A, DNS_CLASS_IN, now).is_some()
+            })
+            .collect();
+        assert_eq!(cached, [true, false, true, true]);
+        assert_eq!(cache.statistics().evictions, 1);
+        assert_eq!(cache.statistics().size, 3 * 29);
+
+        // An RRset larger than the whole cache is not cached, and evicts nothing.
+        let text = "x".repeat(100);
+        cache.insert_rrset_at(&records(&format!("e TXT {}\n", text))?, now)?;
+        assert_eq!(cache.statistics().entries, 3);
+
+        Ok(())
+    }
+}
@@ -1,8 +1,8 @@
-use crate::{opcodes::*, rcodes::*, *};
+use crate::{opcodes::*, rcodes::*, wire::*, *};
 use rand::prelude::*;
 
 /// DNS Packet Header.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DnsHeader {
     /// The ID for the DNS query and corresponding response.
     pub id: u16,
@@ -54,24 +54,31 @@ impl DnsHeader {
     }
 
     /// Parse a DNS header from the start of a raw DNS packet.
-    pub fn parse(dns_packet_buf: &Vec<u8>) -> Result<DnsHeader, String> {
-        if dns_packet_buf.len() < DNS_HEADER_SIZE {
+    pub fn parse(dns_packet_buf: &[u8]) -> Result<DnsHeader, String> {
+        DnsHeader::read(&mut WireReader::new(dns_packet_buf))
+    }
+
+    /// Read a DNS header from reader.
+    pub fn read(reader: &mut WireReader) -> Result<DnsHeader, String> {
+        if reader.remaining() < DNS_HEADER_SIZE {
             return Err("buf too short".into());
         }
 
-        let id: u16 = (dns_packet_buf[0] as u16) << 8 | dns_packet_buf[1] as u16;
-        let qr: bool = (dns_packet_buf[2] & 0x80) == 0x80;
-        let opcode: u8 = (dns_packet_buf[2] & 0x78) >> 3;
-        let aa: bool = (dns_packet_buf[2] & 0x4) == 0x4;
-        let tc: bool = (dns_packet_buf[2] & 0x2) == 0x2;
-        let rd: bool = (dns_packet_buf[2] & 0x1) == 0x1;
-        let ra: bool = (dns_packet_buf[3] & 0x80) == 0x80;
-        let z: u8 = (dns_packet_buf[3] & 0x70) >> 4;
-        let rcode: u8 = dns_packet_buf[3] & 0xF;
-        let qdcount: u16 = (dns_packet_buf[4] as u16) << 8 | dns_packet_buf[5] as u16;
-        let ancount: u16 = (dns_packet_buf[6] as u16) << 8 | dns_packet_buf[7] as u16;
-        let nscount: u16 = (dns_packet_buf[8] as u16) << 8 | dns_packet_buf[9] as u16;
-        let arcount: u16 = (dns_packet_buf[10] as u16) << 8 | dns_packet_buf[11] as u16;
+        let id = reader.read_u16()?;
+        let flags = reader.read_u8()?;
+        let qr: bool = (flags & 0x80) == 0x80;
+        let opcode: u8 = (flags & 0x78) >> 3;
+        let aa: bool = (flags & 0x4) == 0x4;
+        let tc: bool = (flags & 0x2) == 0x2;
+        let rd: bool = (flags & 0x1) == 0x1;
+        let flags = reader.read_u8()?;
+        let ra: bool = (flags & 0x80) == 0x80;
+        let z: u8 = (flags & 0x70) >> 4;
+        let rcode: u8 = flags & 0xF;
+        let qdcount = reader.read_u16()?;
+        let ancount = reader.read_u16()?;
+        let nscount = reader.read_u16()?;
+        let arcount = reader.read_u16()?;
 
         let dns_header: DnsHeader = DnsHeader {
             id,
@@ -94,34 +101,30 @@ impl DnsHeader {
 
     /// Serialize the DNS header into a DNS protocol conformant, network ready buffer.
     pub fn serialize(&self) -> Vec<u8> {
-        let mut buf = Vec::new();
+        let mut writer = WireWriter::new();
+        self.write(&mut writer);
 
-        buf.push(((self.id >> 8) & 0xFF) as u8);
-        buf.push((self.id & 0xFF) as u8);
+        writer.into_bytes()
+    }
 
-        buf.push(
+    /// Write the DNS header to writer.
+    pub fn write(&self, writer: &mut WireWriter) {
+        writer.write_u16(self.id);
+
+        writer.write_u8(
             (self.qr as u8) << 7
-                | self.opcode << 3
+                | (self.opcode & 0xF) << 3
                 | (self.aa as u8) << 2
                 | (self.tc as u8) << 1
                 | self.rd as u8,
         );
 
-        buf.push((self.ra as u8) << 7 | self.z << 4 | (self.rcode as u8) & 0xF);
-
-        buf.push(((self.qdcount >> 8) & 0xFF) as u8);
-        buf.push((self.qdcount & 0xFF) as u8);
+        writer.write_u8((self.ra as u8) << 7 | (self.z & 0x7) << 4 | self.rcode & 0xF);
 
-        buf.push(((self.ancount >> 8) & 0xFF) as u8);
-        buf.push((self.ancount & 0xFF) as u8);
-
-        buf.push(((self.nscount >> 8) & 0xFF) as u8);
-        buf.push((self.nscount & 0xFF) as u8);
-
-        buf.push(((self.arcount >> 8) & 0xFF) as u8);
-        buf.push((self.arcount & 0xFF) as u8);
-
-        buf
+        writer.write_u16(self.qdcount);
+        writer.write_u16(self.ancount);
+        writer.write_u16(self.nscount);
+        writer.write_u16(self.arcount);
     }
 }
 
@@ -149,6 +152,9 @@ mod tests {
         assert_eq!(dns_header.nscount, 0);
         assert_eq!(dns_header.arcount, 0);
 
+        assert_eq!(dns_header.serialize(), &query[..DNS_HEADER_SIZE]);
+        assert!(DnsHeader::parse(&query[..DNS_HEADER_SIZE - 1]).is_err());
+
         Ok(())
     }
 }
@@ -1,8 +1,10 @@
-use super::{dns_header::*, domain_name::*};
+use super::dns_header::*;
+use crate::{mnemonics::*, wire::*};
 use std::collections::HashMap;
+use std::fmt;
 
 /// DNS Packet Question.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DnsQuestion {
     /// The domain name for the resource record that is being queried for.
     pub qname: String,
@@ -12,22 +14,41 @@ pub struct DnsQuestion {
     pub qclass: u16,
 }
 
+/// Formats the question as "www.example.com. IN A".
+impl fmt::Display for DnsQuestion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.qname,
+            class_to_string(self.qclass),
+            type_to_string(self.qtype)
+        )
+    }
+}
+
 impl DnsQuestion {
     /// Parse an entry for the DNS packet question section from a raw dns packet.
     pub fn parse_dns_question(
-        dns_packet_buf: &Vec<u8>,
+        dns_packet_buf: &[u8],
         start: usize,
     ) -> Result<(DnsQuestion, usize), String> {
-        let (qname, end) = parse_domain_name(dns_packet_buf, start, dns_packet_buf.len())?;
+        let mut reader = WireReader::with_bounds(dns_packet_buf, start, dns_packet_buf.len())?;
+        let dns_question = DnsQuestion::read(&mut reader)?;
+
+        Ok((dns_question, reader.position()))
+    }
 
-        if end + 3 >= dns_packet_buf.len()
-        // after successfull parse, end should always be first byte of qtype
-        {
+    /// Read an entry for the DNS packet question section from reader.
+    pub fn read(reader: &mut WireReader) -> Result<DnsQuestion, String> {
+        let qname = reader.read_name()?;
+
+        if reader.remaining() < 4 {
             return Err("question too short".into());
         }
 
-        let qtype: u16 = (dns_packet_buf[end] as u16) << 8 | dns_packet_buf[end + 1] as u16;
-        let qclass: u16 = (dns_packet_buf[end + 2] as u16) << 8 | dns_packet_buf[end + 3] as u16;
+        let qtype = reader.read_u16()?;
+        let qclass = reader.read_u16()?;
 
         let dns_question: DnsQuestion = DnsQuestion {
             qname,
@@ -35,25 +56,42 @@ impl DnsQuestion {
             qclass,
         };
 
-        Ok((dns_question, end + 4))
+        Ok(dns_question)
     }
 
     /// Parse the DNS question section from a raw dns packet.
     pub fn parse_questions(
-        dns_packet_buf: &Vec<u8>,
+        dns_packet_buf: &[u8],
         header: &DnsHeader,
-        mut start: usize,
+        start: usize,
     ) -> Result<(Vec<DnsQuestion>, usize), String> {
-        let mut questions: Vec<DnsQuestion> = Vec::new();
+        let mut reader = WireReader::with_bounds(dns_packet_buf, start, dns_packet_buf.len())?;
+        let questions = DnsQuestion::read_questions(&mut reader, header.qdcount)?;
 
-        for _ in 0..header.qdcount {
-            let (question, end) = DnsQuestion::parse_dns_question(dns_packet_buf, start)?;
+        Ok((questions, reader.position()))
+    }
 
-            start = end;
-            questions.push(question);
+    /// Read qdcount entries of the DNS question section from reader.
+    pub fn read_questions(
+        reader: &mut WireReader,
+        qdcount: u16,
+    ) -> Result<Vec<DnsQuestion>, String> {
+        let mut questions: Vec<DnsQuestion> = Vec::new();
+
+        for _ in 0..qdcount {
+            questions.push(DnsQuestion::read(reader)?);
         }
 
-        Ok((questions, start))
+        Ok(questions)
+    }
+
+    /// Write the DNS question to writer.
+    pub fn write(&self, writer: &mut WireWriter) -> Result<(), String> {
+        writer.write_name(&self.qname, true)?;
+        writer.write_u16(self.qtype);
+        writer.write_u16(self.qclass);
+
+        Ok(())
     }
 
     /// Serialize the DNS question section into a DNS protocol conformant, network ready buffer.
@@ -63,16 +101,9 @@ impl DnsQuestion {
         buf: &mut Vec<u8>,
         domain_name_offsets: &mut HashMap<String, u16>,
     ) -> Result<usize, String> {
-        serialize_domain_name(&self.qname, buf, domain_name_offsets)?;
-
-        buf.push(((self.qtype >> 8) & 0xFF) as u8);
-        buf.push((self.qtype & 0xFF) as u8);
-
-        buf.push(((self.qclass >> 8) & 0xFF) as u8);
-        buf.push((self.qclass & 0xFF) as u8);
+        write_with_parts(buf, domain_name_offsets, |writer| self.write(writer))?;
 
-        let start = start + buf.len();
-        Ok(start)
+        Ok(start + buf.len())
     }
 }
 
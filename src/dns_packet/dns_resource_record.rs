@@ -1,67 +1,438 @@
 use super::domain_name::*;
-use crate::types::*;
+use crate::{encoding::*, mnemonics::*, rcodes::*, types::*, wire::*};
 use std::collections::HashMap;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The maximum length of a single character-string, not including its length octet.
+pub const MAX_CHARACTER_STRING_LENGTH: usize = 255;
 
 /// Parse character string from buf
 pub fn parse_character_string(
-    buf: &Vec<u8>,
+    buf: &[u8],
     start: usize,
     limit: usize,
-) -> Result<(String, usize), String> {
-    if start >= buf.len() {
-        return Err(format!("start={} is past buf.len()={}", start, buf.len()));
+) -> Result<(Vec<u8>, usize), String> {
+    let mut reader = WireReader::with_bounds(buf, start, limit.min(buf.len()))?;
+    let character_string = reader.read_character_string()?;
+
+    Ok((character_string, reader.position()))
+}
+
+/// Format a character string in presentation format, enclosed in quotes.
+/// Quotes and backslashes are escaped with a backslash, and any octet that is not printable ASCII as `\DDD`.
+pub fn character_string_to_presentation(character_string: &[u8]) -> String {
+    let mut presentation = String::from("\"");
+    for octet in character_string {
+        match octet {
+            b'"' | b'\\' => {
+                presentation.push('\\');
+                presentation.push(*octet as char);
+            }
+            0x20..=0x7E => presentation.push(*octet as char),
+            _ => presentation.push_str(&format!("\\{:03}", octet)),
+        }
     }
+    presentation.push('"');
 
-    let mut curr = start;
+    presentation
+}
 
-    let string_length = buf[curr] as usize;
-    curr += 1;
-    let end = string_length + curr;
-    if end > buf.len() {
+/// Parse a character string from its presentation format, without any enclosing quotes,
+/// resolving `\X` and `\DDD` escapes.
+pub fn character_string_from_presentation(text: &str) -> Result<Vec<u8>, String> {
+    let character_string = unescape_presentation(text)?;
+    if character_string.len() > MAX_CHARACTER_STRING_LENGTH {
         return Err(format!(
-            "character-string length {} is past buf.len()={}",
-            string_length,
-            buf.len()
-        ));
-    } else if end > limit {
-        return Err(format!(
-            "character-string length {} is past buf.len()={}",
-            string_length,
-            buf.len()
+            "character string {} is longer than {} octets",
+            text, MAX_CHARACTER_STRING_LENGTH
         ));
     }
 
-    let mut character_string = String::from("");
-    for ch in &buf[curr..end] {
-        character_string.push(*ch as char);
+    Ok(character_string)
+}
+
+/// Resolve the `\X` and `\DDD` escapes of a quoted field without any enclosing quotes, of any length.
+fn unescape_presentation(text: &str) -> Result<Vec<u8>, String> {
+    let mut character_string = Vec::new();
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut utf8 = [0; 4];
+            character_string.extend_from_slice(ch.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some(digit) if digit.is_ascii_digit() => {
+                let digits: String = std::iter::once(digit)
+                    .chain(chars.by_ref().take(2))
+                    .collect();
+                match digits.parse::<u16>() {
+                    Ok(value) if digits.len() == 3 && value <= 0xFF => {
+                        character_string.push(value as u8)
+                    }
+                    _ => return Err(format!("invalid \\DDD escape in {}", text)),
+                }
+            }
+            Some(escaped) if escaped.is_ascii() => character_string.push(escaped as u8),
+            _ => return Err(format!("invalid escape in {}", text)),
+        }
     }
 
-    Ok((character_string, end))
+    Ok(character_string)
 }
 
+/// The largest TTL value allowed, RFC 2181 section 8.
+pub const MAX_TTL: u32 = 0x7FFF_FFFF;
+
+/// Parse a TTL in presentation format, either a number of seconds or a sequence of numbers with the
+/// units w, d, h, m and s as accepted by BIND, such as "1h30m".
+pub fn parse_ttl(text: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid TTL {}", text);
+
+    if text.is_empty() || !text.starts_with(|ch: char| ch.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let mut ttl: u64 = 0;
+    let mut value: u64 = 0;
+    let mut has_units = false;
+    let mut pending_digits = false;
+
+    for ch in text.chars() {
+        if let Some(digit) = ch.to_digit(10) {
+            value = value * 10 + digit as u64;
+            pending_digits = true;
+        } else {
+            let multiplier = match ch.to_ascii_lowercase() {
+                'w' => 604_800,
+                'd' => 86_400,
+                'h' => 3_600,
+                'm' => 60,
+                's' => 1,
+                _ => return Err(invalid()),
+            };
+            if !pending_digits {
+                return Err(invalid());
+            }
+            ttl += value * multiplier;
+            value = 0;
+            has_units = true;
+            pending_digits = false;
+        }
+
+        if ttl + value > MAX_TTL as u64 {
+            return Err(format!("TTL {} is larger than {}", text, MAX_TTL));
+        }
+    }
+
+    if has_units && pending_digits {
+        return Err(invalid());
+    }
+
+    Ok((ttl + value) as u32)
+}
+
+/// Cursor over the fields of resource record data in presentation format.
+struct PresentationFields<'a> {
+    fields: &'a [&'a str],
+    position: usize,
+    origin: &'a str,
+}
+
+impl<'a> PresentationFields<'a> {
+    fn next(&mut self) -> Result<&'a str, String> {
+        let field = self
+            .fields
+            .get(self.position)
+            .ok_or(format!("missing field {}", self.position + 1))?;
+        self.position += 1;
+
+        Ok(field)
+    }
+
+    fn next_number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let field = self.next()?;
+        field
+            .parse()
+            .map_err(|_| format!("invalid number {}", field))
+    }
+
+    fn next_name(&mut self) -> Result<String, String> {
+        absolute_domain_name(self.next()?, self.origin)
+    }
+
+    fn next_character_string(&mut self) -> Result<Vec<u8>, String> {
+        character_string_from_presentation(self.next()?)
+    }
+
+    /// A quoted field not limited to the length of a character string.
+    fn next_quoted(&mut self) -> Result<Vec<u8>, String> {
+        unescape_presentation(self.next()?)
+    }
+
+    fn next_address<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let field = self.next()?;
+        field
+            .parse()
+            .map_err(|_| format!("invalid address {}", field))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.fields.len()
+    }
+
+    /// The remaining fields joined together, as used for base64 data which may contain spaces.
+    fn rest(&mut self) -> String {
+        let rest = self.fields[self.position.min(self.fields.len())..].concat();
+        self.position = self.fields.len();
+
+        rest
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.fields.get(self.position) {
+            Some(field) => Err(format!("unexpected extra field {}", field)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse an EUI-48 or EUI-64 identifier from hexadecimal pairs separated by '-', per RFC 7043.
+fn eui_from_presentation(text: &str, octets: &mut [u8]) -> Result<(), String> {
+    let pairs: Vec<&str> = text.split('-').collect();
+    if pairs.len() != octets.len() || pairs.iter().any(|pair| pair.len() != 2) {
+        return Err(format!("invalid EUI {}", text));
+    }
+
+    for (octet, pair) in octets.iter_mut().zip(pairs) {
+        *octet = u8::from_str_radix(pair, 16).map_err(|_| format!("invalid EUI {}", text))?;
+    }
+
+    Ok(())
+}
+
+/// Serialize a character string into a DNS protocol network ready format.
 pub fn serialize_character_string(
-    character_string: &String,
+    character_string: &[u8],
     buf: &mut Vec<u8>,
 ) -> Result<(), String> {
     // max length of character_string is 255 characters, plus the length octet
-    if character_string.len() >= 256 {
+    if character_string.len() > MAX_CHARACTER_STRING_LENGTH {
         return Err(format!(
-            "character_string length {} >= 256",
-            character_string.len()
+            "character_string length {} > {}",
+            character_string.len(),
+            MAX_CHARACTER_STRING_LENGTH
         ));
     }
 
     buf.push(character_string.len() as u8);
-    for ch in character_string.chars() {
-        buf.push(ch as u8);
-    }
+    buf.extend_from_slice(character_string);
 
     Ok(())
 }
 
+/// Resource record types whose RDATA domain names may be compressed when serializing.
+///
+/// Per RFC 3597 section 4 only the well-known types of RFC 1035 may be compressed.
+/// Every other type, including SRV (RFC 2782) and DNAME (RFC 6672), must be sent uncompressed.
+pub const COMPRESSIBLE_RDATA_TYPES: &[u16] = &[
+    DNS_TYPE_NS,
+    DNS_TYPE_MD,
+    DNS_TYPE_MF,
+    DNS_TYPE_CNAME,
+    DNS_TYPE_SOA,
+    DNS_TYPE_MB,
+    DNS_TYPE_MG,
+    DNS_TYPE_MR,
+    DNS_TYPE_PTR,
+    DNS_TYPE_MINFO,
+    DNS_TYPE_MX,
+];
+
+/// Returns true if domain names in the RDATA of rrtype may be compressed, see [COMPRESSIBLE_RDATA_TYPES].
+pub fn is_rdata_compressible(rrtype: u16) -> bool {
+    COMPRESSIBLE_RDATA_TYPES.contains(&rrtype)
+}
+
+/// Address family number for IPv4 in APL resource records.
+pub const APL_ADDRESS_FAMILY_IPV4: u16 = 1;
+/// Address family number for IPv6 in APL resource records.
+pub const APL_ADDRESS_FAMILY_IPV6: u16 = 2;
+
+/// A single address prefix item of an APL resource record.
+#[derive(Debug, Clone)]
+pub struct AplItem {
+    /// True if the prefix is excluded from the list ('!' in presentation format).
+    pub negation: bool,
+    /// Number of significant bits of the address.
+    pub prefix: u8,
+    /// The address, whose type selects the address family of the item.
+    pub address: IpAddr,
+}
+
+impl AplItem {
+    /// Read APL items from reader until it is empty.
+    pub fn read_items(reader: &mut WireReader) -> Result<Vec<AplItem>, String> {
+        let mut items = Vec::new();
+
+        while !reader.is_empty() {
+            let address_family = reader.read_u16()?;
+            let prefix = reader.read_u8()?;
+            let negation_and_length = reader.read_u8()?;
+            let negation = negation_and_length & 0x80 == 0x80;
+            let afd_length = (negation_and_length & 0x7F) as usize;
+            let afd_part = reader.read_bytes(afd_length)?;
+
+            let address = match address_family {
+                APL_ADDRESS_FAMILY_IPV4 if afd_length <= 4 && prefix <= 32 => {
+                    let mut octets = [0u8; 4];
+                    octets[..afd_length].copy_from_slice(afd_part);
+                    IpAddr::V4(Ipv4Addr::from(octets))
+                }
+                APL_ADDRESS_FAMILY_IPV6 if afd_length <= 16 && prefix <= 128 => {
+                    let mut octets = [0u8; 16];
+                    octets[..afd_length].copy_from_slice(afd_part);
+                    IpAddr::V6(Ipv6Addr::from(octets))
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid APL item ADDRESSFAMILY={} PREFIX={} AFDLENGTH={}",
+                        address_family, prefix, afd_length
+                    ));
+                }
+            };
+
+            items.push(AplItem {
+                negation,
+                prefix,
+                address,
+            });
+        }
+
+        Ok(items)
+    }
+
+    /// Parse an APL item from its presentation format, `[!]afi:address/prefix`.
+    pub fn from_presentation(text: &str) -> Result<AplItem, String> {
+        let invalid = || format!("invalid APL item {}", text);
+
+        let (negation, item) = match text.strip_prefix('!') {
+            Some(item) => (true, item),
+            None => (false, text),
+        };
+        let colon = item.find(':').ok_or_else(invalid)?;
+        let slash = item.rfind('/').ok_or_else(invalid)?;
+        if slash < colon {
+            return Err(invalid());
+        }
+
+        let address_family: u16 = item[..colon].parse().map_err(|_| invalid())?;
+        let address = &item[colon + 1..slash];
+        let prefix: u8 = item[slash + 1..].parse().map_err(|_| invalid())?;
+
+        let address = match address_family {
+            APL_ADDRESS_FAMILY_IPV4 if prefix <= 32 => {
+                IpAddr::V4(address.parse().map_err(|_| invalid())?)
+            }
+            APL_ADDRESS_FAMILY_IPV6 if prefix <= 128 => {
+                IpAddr::V6(address.parse().map_err(|_| invalid())?)
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(AplItem {
+            negation,
+            prefix,
+            address,
+        })
+    }
+
+    /// Write the APL item to writer, omitting trailing zero octets of the address.
+    pub fn write(&self, writer: &mut WireWriter) {
+        let (address_family, octets) = match self.address {
+            IpAddr::V4(address) => (APL_ADDRESS_FAMILY_IPV4, Vec::from(address.octets())),
+            IpAddr::V6(address) => (APL_ADDRESS_FAMILY_IPV6, Vec::from(address.octets())),
+        };
+        let afd_length = octets
+            .iter()
+            .rposition(|octet| *octet != 0)
+            .map_or(0, |position| position + 1);
+
+        writer.write_u16(address_family);
+        writer.write_u8(self.prefix);
+        writer.write_u8((self.negation as u8) << 7 | afd_length as u8);
+        writer.write_bytes(&octets[..afd_length]);
+    }
+}
+
+/// Formats the APL item as `[!]afi:address/prefix`.
+impl fmt::Display for AplItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let address_family = match self.address {
+            IpAddr::V4(_) => APL_ADDRESS_FAMILY_IPV4,
+            IpAddr::V6(_) => APL_ADDRESS_FAMILY_IPV6,
+        };
+        let negation = if self.negation { "!" } else { "" };
+
+        write!(
+            f,
+            "{}{}:{}/{}",
+            negation, address_family, self.address, self.prefix
+        )
+    }
+}
+
+/// The gateway of an IPSECKEY resource record.
+#[derive(Debug, Clone)]
+pub enum IpsecKeyGateway {
+    /// No gateway is present.
+    None,
+    /// An IPv4 gateway address.
+    Ipv4(Ipv4Addr),
+    /// An IPv6 gateway address.
+    Ipv6(Ipv6Addr),
+    /// A gateway domain name.
+    DomainName(String),
+}
+
+impl IpsecKeyGateway {
+    /// The gateway type value that identifies this gateway in wire format.
+    pub fn gateway_type(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Ipv4(_) => 1,
+            Self::Ipv6(_) => 2,
+            Self::DomainName(_) => 3,
+        }
+    }
+}
+
+/// Formats the gateway as it appears in presentation format, with '.' for no gateway.
+impl fmt::Display for IpsecKeyGateway {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "."),
+            Self::Ipv4(address) => write!(f, "{}", address),
+            Self::Ipv6(address) => write!(f, "{}", address),
+            Self::DomainName(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Format an EUI-48 or EUI-64 identifier as hexadecimal pairs separated by '-', per RFC 7043.
+fn eui_to_presentation(octets: &[u8]) -> String {
+    octets
+        .iter()
+        .map(|octet| format!("{:02x}", octet))
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
 /// Represents the data stored in DNS resource records
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DnsResourceRecordData {
     /// An IPv4 host address.
     A(Ipv4Addr),
@@ -88,89 +459,165 @@ pub enum DnsResourceRecordData {
     /// A domain name pointer.
     PTR(String),
     /// Host information.
-    HINFO((String, String)),
+    HINFO((Vec<u8>, Vec<u8>)),
     /// Mailbox or mail list information.
     MINFO((String, String)),
     /// Mail exchange.
     MX((u16, String)),
-    /// Text strings.
-    TXT(String),
+    /// Text strings, one or more character-strings.
+    TXT(Vec<Vec<u8>>),
     /// An IPv6 host address.
     AAAA(Ipv6Addr),
     /// Specifies location of a service for a specific protocol.
     SRV((u16, u16, u16, String)),
+    /// Delegation of an entire subtree of the domain name space to another domain.
+    DNAME(String),
+    /// Responsible person, the mailbox and the domain name of associated TXT records.
+    RP((String, String)),
+    /// AFS database location, the subtype and the hostname of the server.
+    AFSDB((u16, String)),
+    /// X.25 PSDN address.
+    X25(Vec<u8>),
+    /// ISDN address, and the optional subaddress.
+    ISDN((Vec<u8>, Option<Vec<u8>>)),
+    /// Route through, the preference and the intermediate host.
+    RT((u16, String)),
+    /// A certificate, the certificate type, key tag, algorithm, and certificate or CRL.
+    CERT((u16, u16, u8, Vec<u8>)),
+    /// A mapping from a hostname to a URI, the priority, weight, and target URI.
+    URI((u16, u16, String)),
+    /// Lists of address prefixes.
+    APL(Vec<AplItem>),
+    /// A 48-bit extended unique identifier (MAC address).
+    EUI48([u8; 6]),
+    /// A 64-bit extended unique identifier.
+    EUI64([u8; 8]),
+    /// A public key for IPsec, the precedence, algorithm, gateway, and public key.
+    IPSECKEY((u8, u8, IpsecKeyGateway, Vec<u8>)),
+    /// The EDNS(0) OPT pseudo-RR options, each an option code and its data.
+    OPT(Vec<(u16, Vec<u8>)>),
+    /// The type and opaque data of a resource record of a type without its own variant (RFC 3597).
+    Unknown((u16, Vec<u8>)),
 }
 
 impl DnsResourceRecordData {
+    /// Create TXT resource record data from text of any length,
+    /// splitting it into as many character-strings of up to 255 octets as needed.
+    pub fn new_txt(text: &[u8]) -> DnsResourceRecordData {
+        if text.is_empty() {
+            return Self::TXT(vec![Vec::new()]);
+        }
+
+        Self::TXT(
+            text.chunks(MAX_CHARACTER_STRING_LENGTH)
+                .map(Vec::from)
+                .collect(),
+        )
+    }
+
+    /// For TXT resource record data, returns all of the character-strings concatenated together.
+    /// This is how multi-string records such as SPF (RFC 7208) and DKIM (RFC 6376) are interpreted.
+    pub fn txt_data(&self) -> Option<Vec<u8>> {
+        match self {
+            Self::TXT(txt_data) => Some(txt_data.concat()),
+            _ => None,
+        }
+    }
+
+    /// The resource record type this data belongs to.
+    pub fn rrtype(&self) -> u16 {
+        match self {
+            Self::A(_) => DNS_TYPE_A,
+            Self::NS(_) => DNS_TYPE_NS,
+            Self::MD(_) => DNS_TYPE_MD,
+            Self::MF(_) => DNS_TYPE_MF,
+            Self::CNAME(_) => DNS_TYPE_CNAME,
+            Self::SOA(_) => DNS_TYPE_SOA,
+            Self::MB(_) => DNS_TYPE_MB,
+            Self::MG(_) => DNS_TYPE_MG,
+            Self::MR(_) => DNS_TYPE_MR,
+            Self::NULL(_) => DNS_TYPE_NULL,
+            Self::WKS(_) => DNS_TYPE_WKS,
+            Self::PTR(_) => DNS_TYPE_PTR,
+            Self::HINFO(_) => DNS_TYPE_HINFO,
+            Self::MINFO(_) => DNS_TYPE_MINFO,
+            Self::MX(_) => DNS_TYPE_MX,
+            Self::TXT(_) => DNS_TYPE_TXT,
+            Self::AAAA(_) => DNS_TYPE_AAAA,
+            Self::SRV(_) => DNS_TYPE_SRV,
+            Self::DNAME(_) => DNS_TYPE_DNAME,
+            Self::RP(_) => DNS_TYPE_RP,
+            Self::AFSDB(_) => DNS_TYPE_AFSDB,
+            Self::X25(_) => DNS_TYPE_X25,
+            Self::ISDN(_) => DNS_TYPE_ISDN,
+            Self::RT(_) => DNS_TYPE_RT,
+            Self::CERT(_) => DNS_TYPE_CERT,
+            Self::URI(_) => DNS_TYPE_URI,
+            Self::APL(_) => DNS_TYPE_APL,
+            Self::EUI48(_) => DNS_TYPE_EUI48,
+            Self::EUI64(_) => DNS_TYPE_EUI64,
+            Self::IPSECKEY(_) => DNS_TYPE_IPSECKEY,
+            Self::OPT(_) => DNS_TYPE_OPT,
+            Self::Unknown((rrtype, _)) => *rrtype,
+        }
+    }
+
     /// Parse the data for a resource record from buf
     pub fn parse(
         rrtype: u16,
-        buf: &Vec<u8>,
+        buf: &[u8],
         start: usize,
         rdlength: u16,
     ) -> Result<DnsResourceRecordData, String> {
-        let limit = start + rdlength as usize;
+        let mut reader = WireReader::with_bounds(buf, start, start + rdlength as usize)?;
 
-        let data: DnsResourceRecordData;
+        DnsResourceRecordData::read(rrtype, &mut reader)
+    }
+
+    /// Read the data for a resource record from reader, which must be limited to the RDATA.
+    /// Returns an error unless the data is exactly RDLENGTH bytes long.
+    pub fn read(rrtype: u16, reader: &mut WireReader) -> Result<DnsResourceRecordData, String> {
+        let rdlength = reader.remaining();
 
-        match rrtype {
+        let data = match rrtype {
             DNS_TYPE_A => {
                 if rdlength != 4 {
                     return Err("rdata length incorrect for A record".into());
                 }
 
-                data = Self::A(Ipv4Addr::new(
-                    buf[start],
-                    buf[start + 1],
-                    buf[start + 2],
-                    buf[start + 3],
-                ));
+                let mut octets = [0u8; 4];
+                octets.copy_from_slice(reader.read_bytes(4)?);
+
+                Self::A(Ipv4Addr::from(octets))
             }
             DNS_TYPE_AAAA => {
                 if rdlength != 16 {
-                    return Err("rdata length incorrect for A record".into());
+                    return Err("rdata length incorrect for AAAA record".into());
                 }
-                data = Self::AAAA(Ipv6Addr::new(
-                    (buf[start + 0] as u16) << 8 | buf[1] as u16,
-                    (buf[start + 2] as u16) << 8 | buf[start + 3] as u16,
-                    (buf[start + 4] as u16) << 8 | buf[start + 5] as u16,
-                    (buf[start + 6] as u16) << 8 | buf[start + 7] as u16,
-                    (buf[start + 8] as u16) << 8 | buf[start + 9] as u16,
-                    (buf[start + 10] as u16) << 8 | buf[start + 11] as u16,
-                    (buf[start + 12] as u16) << 8 | buf[start + 13] as u16,
-                    (buf[start + 14] as u16) << 8 | buf[start + 15] as u16,
-                ));
-            }
-            DNS_TYPE_CNAME => {
-                let (cname, _) = parse_domain_name(buf, start, limit)?;
 
-                data = Self::CNAME(cname);
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(reader.read_bytes(16)?);
+
+                Self::AAAA(Ipv6Addr::from(octets))
             }
+            DNS_TYPE_CNAME => Self::CNAME(reader.read_name()?),
             DNS_TYPE_MX => {
                 if rdlength <= 2 {
                     return Err(format!("{} is too short an rdlength for type MX", rdlength));
                 }
 
-                let preference = (buf[start] as u16) << 8 | buf[start + 1] as u16;
-                let (exchange, _) = parse_domain_name(buf, start + 2, limit)?;
+                let preference = reader.read_u16()?;
+                let exchange = reader.read_name()?;
 
-                data = Self::MX((preference, exchange));
-            }
-            DNS_TYPE_NS => {
-                let (nsdname, _) = parse_domain_name(buf, start, limit)?;
-
-                data = Self::NS(nsdname);
-            }
-            DNS_TYPE_PTR => {
-                let (ptrdname, _) = parse_domain_name(buf, start, limit)?;
-
-                data = Self::PTR(ptrdname);
+                Self::MX((preference, exchange))
             }
+            DNS_TYPE_NS => Self::NS(reader.read_name()?),
+            DNS_TYPE_PTR => Self::PTR(reader.read_name()?),
             DNS_TYPE_SOA => {
-                let (mname, end) = parse_domain_name(buf, start, limit)?;
-                let (rname, end) = parse_domain_name(buf, end, limit)?;
+                let mname = reader.read_name()?;
+                let rname = reader.read_name()?;
 
-                if (rdlength - (end - start) as u16) < 20 {
+                if reader.remaining() < 20 {
                     // not enough to parse remaining fields
                     return Err(format!(
                         "{} is too short an rdlength for given SOA record",
@@ -178,33 +625,25 @@ impl DnsResourceRecordData {
                     ));
                 }
 
-                let serial = (buf[end] as u32) << 24
-                    | (buf[end + 1] as u32) << 16
-                    | (buf[end + 2] as u32) << 8
-                    | buf[end + 3] as u32;
-                let refresh = (buf[end + 4] as u32) << 24
-                    | (buf[end + 5] as u32) << 16
-                    | (buf[end + 6] as u32) << 8
-                    | buf[end + 7] as u32;
-                let retry = (buf[end + 8] as u32) << 24
-                    | (buf[end + 9] as u32) << 16
-                    | (buf[end + 10] as u32) << 8
-                    | buf[end + 11] as u32;
-                let expire = (buf[end + 12] as u32) << 24
-                    | (buf[end + 13] as u32) << 16
-                    | (buf[end + 14] as u32) << 8
-                    | buf[end + 15] as u32;
-                let minimum = (buf[end + 16] as u32) << 24
-                    | (buf[end + 17] as u32) << 16
-                    | (buf[end + 18] as u32) << 8
-                    | buf[end + 19] as u32;
-
-                data = Self::SOA((mname, rname, serial, refresh, retry, expire, minimum));
+                let serial = reader.read_u32()?;
+                let refresh = reader.read_u32()?;
+                let retry = reader.read_u32()?;
+                let expire = reader.read_u32()?;
+                let minimum = reader.read_u32()?;
+
+                Self::SOA((mname, rname, serial, refresh, retry, expire, minimum))
             }
             DNS_TYPE_TXT => {
-                let (txtdata, _) = parse_character_string(buf, start, limit)?;
+                if rdlength == 0 {
+                    return Err("TXT record must contain at least one character-string".into());
+                }
+
+                let mut txtdata = Vec::new();
+                while !reader.is_empty() {
+                    txtdata.push(reader.read_character_string()?);
+                }
 
-                data = Self::TXT(txtdata);
+                Self::TXT(txtdata)
             }
             DNS_TYPE_SRV => {
                 if rdlength < 7 {
@@ -214,16 +653,14 @@ impl DnsResourceRecordData {
                     ));
                 }
 
-                let priority = (buf[start] as u16) << 8 | buf[start + 1] as u16;
-                let weight = (buf[start + 2] as u16) << 8 | buf[start + 3] as u16;
-                let port = (buf[start + 4] as u16) << 8 | buf[start + 5] as u16;
-                let (target, _) = parse_domain_name(buf, start, limit)?;
+                let priority = reader.read_u16()?;
+                let weight = reader.read_u16()?;
+                let port = reader.read_u16()?;
+                let target = reader.read_name()?;
 
-                data = Self::SRV((priority, weight, port, target));
-            }
-            DNS_TYPE_NULL => {
-                data = Self::NULL(Vec::from(&buf[start..limit]));
+                Self::SRV((priority, weight, port, target))
             }
+            DNS_TYPE_NULL => Self::NULL(Vec::from(reader.read_remaining())),
             DNS_TYPE_WKS => {
                 if rdlength < 5 {
                     return Err(format!(
@@ -232,150 +669,516 @@ impl DnsResourceRecordData {
                     ));
                 }
 
-                let address =
-                    Ipv4Addr::new(buf[start], buf[start + 1], buf[start + 2], buf[start + 3]);
-
-                let protocol = buf[start + 4];
-                let bitmap = Vec::from(&buf[start + 5..limit]);
+                let mut octets = [0u8; 4];
+                octets.copy_from_slice(reader.read_bytes(4)?);
+                let protocol = reader.read_u8()?;
+                let bitmap = Vec::from(reader.read_remaining());
 
-                data = Self::WKS((address, protocol, bitmap));
+                Self::WKS((Ipv4Addr::from(octets), protocol, bitmap))
             }
             DNS_TYPE_HINFO => {
-                let (cpu, end) = parse_character_string(buf, start, limit)?;
-                let (os, _) = parse_character_string(buf, end, limit)?;
-                data = Self::HINFO((cpu, os));
+                let cpu = reader.read_character_string()?;
+                let os = reader.read_character_string()?;
+
+                Self::HINFO((cpu, os))
             }
-            DNS_TYPE_MB => {
-                let (madname, _) = parse_domain_name(buf, start, limit)?;
+            DNS_TYPE_MB => Self::MB(reader.read_name()?),
+            DNS_TYPE_MD => Self::MD(reader.read_name()?),
+            DNS_TYPE_MF => Self::MF(reader.read_name()?),
+            DNS_TYPE_MG => Self::MG(reader.read_name()?),
+            DNS_TYPE_MR => Self::MR(reader.read_name()?),
+            DNS_TYPE_DNAME => Self::DNAME(reader.read_name()?),
+            DNS_TYPE_MINFO => {
+                let rmailbx = reader.read_name()?;
+                let emailbx = reader.read_name()?;
 
-                data = Self::MB(madname);
+                Self::MINFO((rmailbx, emailbx))
             }
-            DNS_TYPE_MD => {
-                let (madname, _) = parse_domain_name(buf, start, limit)?;
+            DNS_TYPE_RP => {
+                let mbox_dname = reader.read_name()?;
+                let txt_dname = reader.read_name()?;
 
-                data = Self::MD(madname);
+                Self::RP((mbox_dname, txt_dname))
             }
-            DNS_TYPE_MF => {
-                let (madname, _) = parse_domain_name(buf, start, limit)?;
+            DNS_TYPE_AFSDB => {
+                if rdlength <= 2 {
+                    return Err(format!(
+                        "{} is too short an rdlength for an AFSDB record",
+                        rdlength
+                    ));
+                }
+
+                let subtype = reader.read_u16()?;
+                let hostname = reader.read_name()?;
 
-                data = Self::MF(madname);
+                Self::AFSDB((subtype, hostname))
             }
-            DNS_TYPE_MG => {
-                let (madname, _) = parse_domain_name(buf, start, limit)?;
+            DNS_TYPE_X25 => Self::X25(reader.read_character_string()?),
+            DNS_TYPE_ISDN => {
+                let isdn_address = reader.read_character_string()?;
 
-                data = Self::MG(madname);
+                let subaddress = if reader.is_empty() {
+                    None
+                } else {
+                    Some(reader.read_character_string()?)
+                };
+
+                Self::ISDN((isdn_address, subaddress))
             }
-            DNS_TYPE_MR => {
-                let (newname, _) = parse_domain_name(buf, start, limit)?;
+            DNS_TYPE_RT => {
+                if rdlength <= 2 {
+                    return Err(format!(
+                        "{} is too short an rdlength for an RT record",
+                        rdlength
+                    ));
+                }
 
-                data = Self::MR(newname);
+                let preference = reader.read_u16()?;
+                let intermediate_host = reader.read_name()?;
+
+                Self::RT((preference, intermediate_host))
             }
-            DNS_TYPE_MINFO => {
-                let (rmailbx, end) = parse_domain_name(buf, start, limit)?;
-                let (emailbx, _) = parse_domain_name(buf, end, limit)?;
-                data = Self::MINFO((rmailbx, emailbx));
+            DNS_TYPE_CERT => {
+                if rdlength < 5 {
+                    return Err(format!(
+                        "{} is too short an rdlength for a CERT record",
+                        rdlength
+                    ));
+                }
+
+                let cert_type = reader.read_u16()?;
+                let key_tag = reader.read_u16()?;
+                let algorithm = reader.read_u8()?;
+                let certificate = Vec::from(reader.read_remaining());
+
+                Self::CERT((cert_type, key_tag, algorithm, certificate))
             }
-            _ => {
-                return Err(format!("not supported resource record type {}", rrtype));
+            DNS_TYPE_URI => {
+                if rdlength <= 4 {
+                    return Err(format!(
+                        "{} is too short an rdlength for a URI record",
+                        rdlength
+                    ));
+                }
+
+                let priority = reader.read_u16()?;
+                let weight = reader.read_u16()?;
+                let target = String::from_utf8(Vec::from(reader.read_remaining()))
+                    .map_err(|_| String::from("URI record target is not valid UTF-8"))?;
+
+                Self::URI((priority, weight, target))
+            }
+            DNS_TYPE_APL => Self::APL(AplItem::read_items(reader)?),
+            DNS_TYPE_EUI48 => {
+                if rdlength != 6 {
+                    return Err("rdata length incorrect for EUI48 record".into());
+                }
+
+                let mut address = [0u8; 6];
+                address.copy_from_slice(reader.read_bytes(6)?);
+
+                Self::EUI48(address)
+            }
+            DNS_TYPE_EUI64 => {
+                if rdlength != 8 {
+                    return Err("rdata length incorrect for EUI64 record".into());
+                }
+
+                let mut address = [0u8; 8];
+                address.copy_from_slice(reader.read_bytes(8)?);
+
+                Self::EUI64(address)
+            }
+            DNS_TYPE_IPSECKEY => {
+                if rdlength < 3 {
+                    return Err(format!(
+                        "{} is too short an rdlength for an IPSECKEY record",
+                        rdlength
+                    ));
+                }
+
+                let precedence = reader.read_u8()?;
+                let gateway_type = reader.read_u8()?;
+                let algorithm = reader.read_u8()?;
+
+                let gateway = match gateway_type {
+                    0 => IpsecKeyGateway::None,
+                    1 => {
+                        let mut octets = [0u8; 4];
+                        octets.copy_from_slice(reader.read_bytes(4)?);
+                        IpsecKeyGateway::Ipv4(Ipv4Addr::from(octets))
+                    }
+                    2 => {
+                        let mut octets = [0u8; 16];
+                        octets.copy_from_slice(reader.read_bytes(16)?);
+                        IpsecKeyGateway::Ipv6(Ipv6Addr::from(octets))
+                    }
+                    3 => IpsecKeyGateway::DomainName(reader.read_name()?),
+                    _ => {
+                        return Err(format!("invalid IPSECKEY gateway type {}", gateway_type));
+                    }
+                };
+                let public_key = Vec::from(reader.read_remaining());
+
+                Self::IPSECKEY((precedence, algorithm, gateway, public_key))
+            }
+            DNS_TYPE_OPT => {
+                let mut options = Vec::new();
+                while !reader.is_empty() {
+                    let code = reader.read_u16()?;
+                    let length = reader.read_u16()?;
+                    options.push((code, Vec::from(reader.read_bytes(length as usize)?)));
+                }
+
+                Self::OPT(options)
+            }
+            _ => Self::Unknown((rrtype, Vec::from(reader.read_remaining()))),
+        };
+
+        reader.finish()?;
+
+        Ok(data)
+    }
+
+    /// Parse resource record data of type rrtype from the fields of its presentation format, as found
+    /// in master files. Relative domain names are made absolute with origin.
+    ///
+    /// Fields are whitespace separated tokens with any enclosing quotes removed. The generic encoding
+    /// of RFC 3597, `\# length hex`, is accepted for every type.
+    pub fn from_presentation(
+        rrtype: u16,
+        fields: &[&str],
+        origin: &str,
+    ) -> Result<DnsResourceRecordData, String> {
+        if rrtype == DNS_TYPE_OPT {
+            return Err(
+                "OPT pseudo-RRs only exist in DNS messages, not in presentation format".into(),
+            );
+        } else if fields.first() == Some(&"\\#") {
+            let length: usize = fields
+                .get(1)
+                .ok_or("missing length of generic RDATA")?
+                .parse()
+                .map_err(|_| format!("invalid length of generic RDATA {}", fields[1]))?;
+            let data = hex_decode(&fields[2..].concat())?;
+            if data.len() != length || length > u16::MAX as usize {
+                return Err(format!(
+                    "generic RDATA has {} octets, but a length of {}",
+                    data.len(),
+                    length
+                ));
             }
+
+            return DnsResourceRecordData::parse(rrtype, &data, 0, length as u16);
         }
 
+        let mut fields = PresentationFields {
+            fields,
+            position: 0,
+            origin,
+        };
+
+        let data = match rrtype {
+            DNS_TYPE_A => Self::A(fields.next_address()?),
+            DNS_TYPE_AAAA => Self::AAAA(fields.next_address()?),
+            DNS_TYPE_NS => Self::NS(fields.next_name()?),
+            DNS_TYPE_MD => Self::MD(fields.next_name()?),
+            DNS_TYPE_MF => Self::MF(fields.next_name()?),
+            DNS_TYPE_CNAME => Self::CNAME(fields.next_name()?),
+            DNS_TYPE_MB => Self::MB(fields.next_name()?),
+            DNS_TYPE_MG => Self::MG(fields.next_name()?),
+            DNS_TYPE_MR => Self::MR(fields.next_name()?),
+            DNS_TYPE_PTR => Self::PTR(fields.next_name()?),
+            DNS_TYPE_DNAME => Self::DNAME(fields.next_name()?),
+            DNS_TYPE_SOA => Self::SOA((
+                fields.next_name()?,
+                fields.next_name()?,
+                fields.next_number()?,
+                parse_ttl(fields.next()?)?,
+                parse_ttl(fields.next()?)?,
+                parse_ttl(fields.next()?)?,
+                parse_ttl(fields.next()?)?,
+            )),
+            DNS_TYPE_WKS => {
+                let address = fields.next_address()?;
+                let protocol = match fields.next()? {
+                    protocol if protocol.eq_ignore_ascii_case("tcp") => 6,
+                    protocol if protocol.eq_ignore_ascii_case("udp") => 17,
+                    protocol => protocol
+                        .parse()
+                        .map_err(|_| format!("invalid protocol {}", protocol))?,
+                };
+
+                let mut bit_map = Vec::new();
+                while !fields.is_empty() {
+                    let port: u16 = fields.next_number()?;
+                    let index = port as usize / 8;
+                    if bit_map.len() <= index {
+                        bit_map.resize(index + 1, 0);
+                    }
+                    bit_map[index] |= 0x80 >> (port % 8);
+                }
+
+                Self::WKS((address, protocol, bit_map))
+            }
+            DNS_TYPE_HINFO => Self::HINFO((
+                fields.next_character_string()?,
+                fields.next_character_string()?,
+            )),
+            DNS_TYPE_MINFO => Self::MINFO((fields.next_name()?, fields.next_name()?)),
+            DNS_TYPE_MX => Self::MX((fields.next_number()?, fields.next_name()?)),
+            DNS_TYPE_TXT => {
+                let mut txt_data = vec![fields.next_character_string()?];
+                while !fields.is_empty() {
+                    txt_data.push(fields.next_character_string()?);
+                }
+
+                Self::TXT(txt_data)
+            }
+            DNS_TYPE_SRV => Self::SRV((
+                fields.next_number()?,
+                fields.next_number()?,
+                fields.next_number()?,
+                fields.next_name()?,
+            )),
+            DNS_TYPE_RP => Self::RP((fields.next_name()?, fields.next_name()?)),
+            DNS_TYPE_AFSDB => Self::AFSDB((fields.next_number()?, fields.next_name()?)),
+            DNS_TYPE_X25 => Self::X25(fields.next_character_string()?),
+            DNS_TYPE_ISDN => {
+                let isdn_address = fields.next_character_string()?;
+                let subaddress = if fields.is_empty() {
+                    None
+                } else {
+                    Some(fields.next_character_string()?)
+                };
+
+                Self::ISDN((isdn_address, subaddress))
+            }
+            DNS_TYPE_RT => Self::RT((fields.next_number()?, fields.next_name()?)),
+            DNS_TYPE_CERT => {
+                let cert_type = match fields.next()? {
+                    mnemonic if mnemonic.eq_ignore_ascii_case("PKIX") => 1,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("SPKI") => 2,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("PGP") => 3,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("IPKIX") => 4,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("ISPKI") => 5,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("IPGP") => 6,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("ACPKIX") => 7,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("IACPKIX") => 8,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("URI") => 253,
+                    mnemonic if mnemonic.eq_ignore_ascii_case("OID") => 254,
+                    cert_type => cert_type
+                        .parse()
+                        .map_err(|_| format!("invalid certificate type {}", cert_type))?,
+                };
+                let key_tag = fields.next_number()?;
+                let algorithm = fields.next_number()?;
+                let certificate = base64_decode(&fields.rest())?;
+
+                Self::CERT((cert_type, key_tag, algorithm, certificate))
+            }
+            DNS_TYPE_URI => {
+                let priority = fields.next_number()?;
+                let weight = fields.next_number()?;
+                // The target is the rest of the RDATA (RFC 7553 section 4.5), not a character string.
+                let target = String::from_utf8(fields.next_quoted()?)
+                    .map_err(|_| "URI target is not valid UTF-8")?;
+
+                Self::URI((priority, weight, target))
+            }
+            DNS_TYPE_APL => {
+                let mut items = Vec::new();
+                while !fields.is_empty() {
+                    items.push(AplItem::from_presentation(fields.next()?)?);
+                }
+
+                Self::APL(items)
+            }
+            DNS_TYPE_EUI48 => {
+                let mut address = [0u8; 6];
+                eui_from_presentation(fields.next()?, &mut address)?;
+                Self::EUI48(address)
+            }
+            DNS_TYPE_EUI64 => {
+                let mut address = [0u8; 8];
+                eui_from_presentation(fields.next()?, &mut address)?;
+                Self::EUI64(address)
+            }
+            DNS_TYPE_IPSECKEY => {
+                let precedence = fields.next_number()?;
+                let gateway_type: u8 = fields.next_number()?;
+                let algorithm = fields.next_number()?;
+                let gateway = match gateway_type {
+                    0 => {
+                        fields.next()?;
+                        IpsecKeyGateway::None
+                    }
+                    1 => IpsecKeyGateway::Ipv4(fields.next_address()?),
+                    2 => IpsecKeyGateway::Ipv6(fields.next_address()?),
+                    3 => IpsecKeyGateway::DomainName(fields.next_name()?),
+                    _ => {
+                        return Err(format!("invalid IPSECKEY gateway type {}", gateway_type));
+                    }
+                };
+                let public_key = base64_decode(&fields.rest())?;
+
+                Self::IPSECKEY((precedence, algorithm, gateway, public_key))
+            }
+            _ => {
+                return Err(format!(
+                    "resource record type {} has no supported presentation format",
+                    type_to_string(rrtype)
+                ));
+            }
+        };
+
+        fields.finish()?;
+
         Ok(data)
     }
 
+    /// Serialize the resource record data on its own into a DNS protocol network ready format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut writer = WireWriter::new();
+        self.write(&mut writer)?;
+
+        Ok(writer.into_bytes())
+    }
+
     /// Serialize the resource record data into a DNS protocol network ready format
     pub fn serialize(
         &self,
         buf: &mut Vec<u8>,
         domain_name_offsets: &mut HashMap<String, u16>,
     ) -> Result<(), String> {
+        write_with_parts(buf, domain_name_offsets, |writer| self.write(writer))
+    }
+
+    /// Write the resource record data to writer.
+    /// Domain names are only compressed if the type allows it, see [is_rdata_compressible].
+    pub fn write(&self, writer: &mut WireWriter) -> Result<(), String> {
+        let compress = is_rdata_compressible(self.rrtype());
+
         match self {
-            Self::A(address) => {
-                buf.append(&mut Vec::from(address.octets()));
+            Self::A(address) => writer.write_bytes(&address.octets()),
+            Self::AAAA(address) => writer.write_bytes(&address.octets()),
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name)
+            | Self::DNAME(name) => {
+                writer.write_name(name, compress)?;
             }
-            Self::AAAA(address) => {
-                buf.append(&mut Vec::from(address.octets()));
+            Self::SOA((mname, rname, serial, refresh, retry, expire, minimum)) => {
+                writer.write_name(mname, compress)?;
+                writer.write_name(rname, compress)?;
+                writer.write_u32(*serial);
+                writer.write_u32(*refresh);
+                writer.write_u32(*retry);
+                writer.write_u32(*expire);
+                writer.write_u32(*minimum);
             }
-            Self::CNAME(cname) => {
-                serialize_domain_name(cname, buf, &mut HashMap::new())?;
+            Self::NULL(data) | Self::Unknown((_, data)) => writer.write_bytes(data),
+            Self::TXT(txt_data) => {
+                if txt_data.is_empty() {
+                    return Err("TXT record must contain at least one character-string".into());
+                }
+
+                for character_string in txt_data {
+                    writer.write_character_string(character_string)?;
+                }
             }
-            Self::NS(nsdname) => {
-                serialize_domain_name(nsdname, buf, &mut HashMap::new())?;
+            Self::SRV((priority, weight, port, target)) => {
+                writer.write_u16(*priority);
+                writer.write_u16(*weight);
+                writer.write_u16(*port);
+                writer.write_name(target, compress)?;
             }
-            Self::MX((preference, exchange)) => {
-                buf.push(((preference & 0xFF00) >> 8) as u8);
-                buf.push((preference & 0xFF) as u8);
-                serialize_domain_name(exchange, buf, &mut HashMap::new())?;
+            Self::WKS((address, protocol, bit_map)) => {
+                writer.write_bytes(&address.octets());
+                writer.write_u8(*protocol);
+                writer.write_bytes(bit_map);
             }
-            Self::PTR(ptrdname) => {
-                serialize_domain_name(ptrdname, buf, &mut HashMap::new())?;
+            Self::HINFO((cpu, os)) => {
+                writer.write_character_string(cpu)?;
+                writer.write_character_string(os)?;
             }
-            Self::SOA((mname, rname, serial, refresh, retry, expire, minimum)) => {
-                serialize_domain_name(mname, buf, domain_name_offsets)?;
-                serialize_domain_name(rname, buf, domain_name_offsets)?;
-                buf.push(((serial & 0xFF000000) >> 24) as u8);
-                buf.push(((serial & 0xFF0000) >> 16) as u8);
-                buf.push(((serial & 0xFF00) >> 8) as u8);
-                buf.push((serial & 0xFF) as u8);
-                buf.push(((refresh & 0xFF000000) >> 24) as u8);
-                buf.push(((refresh & 0xFF0000) >> 16) as u8);
-                buf.push(((refresh & 0xFF00) >> 8) as u8);
-                buf.push((refresh & 0xFF) as u8);
-                buf.push(((retry & 0xFF000000) >> 24) as u8);
-                buf.push(((retry & 0xFF0000) >> 16) as u8);
-                buf.push(((retry & 0xFF00) >> 8) as u8);
-                buf.push((retry & 0xFF) as u8);
-                buf.push(((expire & 0xFF000000) >> 24) as u8);
-                buf.push(((expire & 0xFF0000) >> 16) as u8);
-                buf.push(((expire & 0xFF00) >> 8) as u8);
-                buf.push((expire & 0xFF) as u8);
-                buf.push(((minimum & 0xFF000000) >> 24) as u8);
-                buf.push(((minimum & 0xFF0000) >> 16) as u8);
-                buf.push(((minimum & 0xFF00) >> 8) as u8);
-                buf.push((minimum & 0xFF) as u8);
-            }
-            Self::NULL(data) => {
-                buf.append(&mut data.clone());
+            Self::MINFO((rmailbx, emailbx)) => {
+                writer.write_name(rmailbx, compress)?;
+                writer.write_name(emailbx, compress)?;
             }
-            Self::TXT(txt_data) => {
-                serialize_character_string(txt_data, buf)?;
+            Self::MX((preference, exchange)) => {
+                writer.write_u16(*preference);
+                writer.write_name(exchange, compress)?;
             }
-            Self::SRV((priority, weight, port, target)) => {
-                buf.push(((priority & 0xFF00) >> 8) as u8);
-                buf.push((priority & 0xFF) as u8);
-                buf.push(((weight & 0xFF00) >> 8) as u8);
-                buf.push((weight & 0xFF) as u8);
-                buf.push(((port & 0xFF00) >> 8) as u8);
-                buf.push((port & 0xFF) as u8);
-                serialize_domain_name(target, buf, domain_name_offsets)?;
+            Self::RP((mbox_dname, txt_dname)) => {
+                writer.write_name(mbox_dname, compress)?;
+                writer.write_name(txt_dname, compress)?;
             }
-            Self::WKS((address, protocol, bit_map)) => {
-                buf.append(&mut Vec::from(address.octets()));
-                buf.push(*protocol);
-                buf.append(&mut bit_map.clone());
+            Self::AFSDB((subtype, hostname)) => {
+                writer.write_u16(*subtype);
+                writer.write_name(hostname, compress)?;
             }
-            Self::MD(madname) => {
-                serialize_domain_name(madname, buf, domain_name_offsets)?;
+            Self::X25(psdn_address) => {
+                writer.write_character_string(psdn_address)?;
             }
-            Self::MB(madname) => {
-                serialize_domain_name(madname, buf, domain_name_offsets)?;
+            Self::ISDN((isdn_address, subaddress)) => {
+                writer.write_character_string(isdn_address)?;
+                if let Some(subaddress) = subaddress {
+                    writer.write_character_string(subaddress)?;
+                }
             }
-            Self::MF(madname) => {
-                serialize_domain_name(madname, buf, domain_name_offsets)?;
+            Self::RT((preference, intermediate_host)) => {
+                writer.write_u16(*preference);
+                writer.write_name(intermediate_host, compress)?;
             }
-            Self::MR(madname) => {
-                serialize_domain_name(madname, buf, domain_name_offsets)?;
+            Self::CERT((cert_type, key_tag, algorithm, certificate)) => {
+                writer.write_u16(*cert_type);
+                writer.write_u16(*key_tag);
+                writer.write_u8(*algorithm);
+                writer.write_bytes(certificate);
             }
-            Self::MG(madname) => {
-                serialize_domain_name(madname, buf, domain_name_offsets)?;
+            Self::URI((priority, weight, target)) => {
+                if target.is_empty() {
+                    return Err("URI record target must not be empty".into());
+                }
+
+                writer.write_u16(*priority);
+                writer.write_u16(*weight);
+                writer.write_bytes(target.as_bytes());
             }
-            Self::HINFO((cpu, os)) => {
-                serialize_character_string(cpu, buf)?;
-                serialize_character_string(os, buf)?;
+            Self::APL(items) => {
+                for item in items {
+                    item.write(writer);
+                }
             }
-            Self::MINFO((rmailbx, emailbx)) => {
-                serialize_domain_name(rmailbx, buf, domain_name_offsets)?;
-                serialize_domain_name(emailbx, buf, domain_name_offsets)?;
+            Self::EUI48(address) => writer.write_bytes(address),
+            Self::EUI64(address) => writer.write_bytes(address),
+            Self::IPSECKEY((precedence, algorithm, gateway, public_key)) => {
+                writer.write_u8(*precedence);
+                writer.write_u8(gateway.gateway_type());
+                writer.write_u8(*algorithm);
+                match gateway {
+                    IpsecKeyGateway::None => {}
+                    IpsecKeyGateway::Ipv4(address) => writer.write_bytes(&address.octets()),
+                    IpsecKeyGateway::Ipv6(address) => writer.write_bytes(&address.octets()),
+                    IpsecKeyGateway::DomainName(name) => writer.write_name(name, compress)?,
+                }
+                writer.write_bytes(public_key);
+            }
+            Self::OPT(options) => {
+                for (code, data) in options {
+                    if data.len() > u16::MAX as usize {
+                        return Err(format!("EDNS option {} is too long", code));
+                    }
+                    writer.write_u16(*code);
+                    writer.write_u16(data.len() as u16);
+                    writer.write_bytes(data);
+                }
             }
         }
 
@@ -383,8 +1186,134 @@ impl DnsResourceRecordData {
     }
 }
 
+/// Formats the resource record data in the RFC 1035 presentation format used by master files.
+impl fmt::Display for DnsResourceRecordData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::A(address) => write!(f, "{}", address),
+            Self::AAAA(address) => write!(f, "{}", address),
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name)
+            | Self::DNAME(name) => write!(f, "{}", name),
+            Self::SOA((mname, rname, serial, refresh, retry, expire, minimum)) => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                mname, rname, serial, refresh, retry, expire, minimum
+            ),
+            // RFC 3597 section 5, the generic encoding for data without a presentation format.
+            Self::NULL(data) | Self::Unknown((_, data)) => {
+                write!(f, "\\# {}", data.len())?;
+                if !data.is_empty() {
+                    write!(f, " {}", hex_encode(data))?;
+                }
+                Ok(())
+            }
+            Self::WKS((address, protocol, bit_map)) => {
+                write!(f, "{} {}", address, protocol)?;
+                for (index, byte) in bit_map.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0x80 >> bit) != 0 {
+                            write!(f, " {}", index * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Self::HINFO((cpu, os)) => write!(
+                f,
+                "{} {}",
+                character_string_to_presentation(cpu),
+                character_string_to_presentation(os)
+            ),
+            Self::MINFO((rmailbx, emailbx)) => write!(f, "{} {}", rmailbx, emailbx),
+            Self::MX((preference, exchange)) => write!(f, "{} {}", preference, exchange),
+            Self::TXT(txt_data) => {
+                let character_strings: Vec<String> = txt_data
+                    .iter()
+                    .map(|character_string| character_string_to_presentation(character_string))
+                    .collect();
+                write!(f, "{}", character_strings.join(" "))
+            }
+            Self::SRV((priority, weight, port, target)) => {
+                write!(f, "{} {} {} {}", priority, weight, port, target)
+            }
+            Self::RP((mbox_dname, txt_dname)) => write!(f, "{} {}", mbox_dname, txt_dname),
+            Self::AFSDB((subtype, hostname)) => write!(f, "{} {}", subtype, hostname),
+            Self::X25(psdn_address) => {
+                write!(f, "{}", character_string_to_presentation(psdn_address))
+            }
+            Self::ISDN((isdn_address, subaddress)) => {
+                write!(f, "{}", character_string_to_presentation(isdn_address))?;
+                if let Some(subaddress) = subaddress {
+                    write!(f, " {}", character_string_to_presentation(subaddress))?;
+                }
+                Ok(())
+            }
+            Self::RT((preference, intermediate_host)) => {
+                write!(f, "{} {}", preference, intermediate_host)
+            }
+            Self::CERT((cert_type, key_tag, algorithm, certificate)) => write!(
+                f,
+                "{} {} {} {}",
+                cert_type,
+                key_tag,
+                algorithm,
+                base64_encode(certificate)
+            ),
+            Self::URI((priority, weight, target)) => write!(
+                f,
+                "{} {} {}",
+                priority,
+                weight,
+                character_string_to_presentation(target.as_bytes())
+            ),
+            Self::APL(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "{}", items.join(" "))
+            }
+            Self::EUI48(address) => write!(f, "{}", eui_to_presentation(address)),
+            Self::EUI64(address) => write!(f, "{}", eui_to_presentation(address)),
+            Self::IPSECKEY((precedence, algorithm, gateway, public_key)) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    precedence,
+                    gateway.gateway_type(),
+                    algorithm,
+                    gateway
+                )?;
+                if !public_key.is_empty() {
+                    write!(f, " {}", base64_encode(public_key))?;
+                }
+                Ok(())
+            }
+            // OPT has no presentation format, so like NULL it uses the generic encoding.
+            Self::OPT(options) => {
+                let mut data = Vec::new();
+                for (code, option_data) in options {
+                    data.extend_from_slice(&code.to_be_bytes());
+                    data.extend_from_slice(&(option_data.len() as u16).to_be_bytes());
+                    data.extend_from_slice(option_data);
+                }
+
+                write!(f, "\\# {}", data.len())?;
+                if !data.is_empty() {
+                    write!(f, " {}", hex_encode(&data))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 /// DNS Resource Record.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DnsResourceRecord {
     /// Name of the resource record.
     pub name: String,
@@ -394,13 +1323,29 @@ pub struct DnsResourceRecord {
     pub class: u16,
     /// TTL (Time to Live) of the resource record.
     pub ttl: u32,
-    /// Length in bytes of the resource record data.
+    /// Length in bytes of the resource record data, as parsed.
+    /// When serializing the length is always recomputed from the data.
     pub rdlength: u16,
     /// The actual data for the resource record.
     pub rdata: DnsResourceRecordData,
 }
 // any class/type combo not supported results in FORMERR responses?
 
+/// Formats the resource record as a line of a master file, "www.example.com. 600 IN A 192.0.2.1".
+impl fmt::Display for DnsResourceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.name,
+            self.ttl,
+            class_to_string(self.class),
+            type_to_string(self.rrtype),
+            self.rdata
+        )
+    }
+}
+
 impl DnsResourceRecord {
     /// Create a DNS resource record.
     pub fn new(
@@ -425,48 +1370,75 @@ impl DnsResourceRecord {
 
     /// Parse a DNS resource record section (i.e. Answer, Additional) from a raw DNS packet.
     pub fn parse_resource_records(
-        buf: &Vec<u8>,
-        mut start: usize,
+        buf: &[u8],
+        start: usize,
         rrcount: u16,
     ) -> Result<(Vec<DnsResourceRecord>, usize), String> {
+        let mut reader = WireReader::with_bounds(buf, start, buf.len())?;
+        let resource_records = DnsResourceRecord::read_resource_records(&mut reader, rrcount)?;
+
+        Ok((resource_records, reader.position()))
+    }
+
+    /// Read rrcount DNS resource records from reader.
+    pub fn read_resource_records(
+        reader: &mut WireReader,
+        rrcount: u16,
+    ) -> Result<Vec<DnsResourceRecord>, String> {
         let mut resource_records = Vec::new();
 
-        // This should be a seperate function.
         for _ in 0..rrcount {
-            let (name, end) = parse_domain_name(buf, start, buf.len())?;
-            start = end;
+            resource_records.push(DnsResourceRecord::read(reader)?);
+        }
 
-            if start + 9 >= buf.len() {
-                return Err("resource record too short, missing fields".into());
-            }
+        Ok(resource_records)
+    }
 
-            let rrtype = (buf[start] as u16) << 8 | buf[start + 1] as u16;
-            let class = (buf[start + 2] as u16) << 8 | buf[start + 3] as u16;
-            let ttl = (buf[start + 4] as u32) << 24
-                | (buf[start + 5] as u32) << 16
-                | (buf[start + 6] as u32) << 8
-                | buf[start + 7] as u32;
-            let rdlength = (buf[start + 8] as u16) << 8 | (buf[start + 9] as u16);
+    /// Read a single DNS resource record from reader.
+    pub fn read(reader: &mut WireReader) -> Result<DnsResourceRecord, String> {
+        let name = reader.read_name()?;
 
-            start += 10;
+        if reader.remaining() < 10 {
+            return Err("resource record too short, missing fields".into());
+        }
 
-            if start + rdlength as usize > buf.len() {
-                return Err("resource record too short, no rdata".into());
-            }
+        let rrtype = reader.read_u16()?;
+        let class = reader.read_u16()?;
+        let ttl = reader.read_u32()?;
+        let rdlength = reader.read_u16()?;
 
-            // we need to figure out how to hold a thing of any type
-            // most likely will be some inheritence, which supports some parse/serialize funcs
-            let rdata = DnsResourceRecordData::parse(rrtype, buf, start, rdlength)?;
+        if rdlength as usize > reader.remaining() {
+            return Err("resource record too short, no rdata".into());
+        }
 
-            let dns_resource_record =
-                DnsResourceRecord::new(name, rrtype, class, ttl, rdlength, rdata)?;
+        let mut rdata_reader = reader.sub_reader(rdlength as usize)?;
+        let rdata = DnsResourceRecordData::read(rrtype, &mut rdata_reader)?;
 
-            resource_records.push(dns_resource_record);
+        DnsResourceRecord::new(name, rrtype, class, ttl, rdlength, rdata)
+    }
 
-            start += rdlength as usize;
+    /// Write the DNS resource record to writer.
+    /// RDLENGTH is computed from the written RDATA, as compression may change its length.
+    pub fn write(&self, writer: &mut WireWriter) -> Result<(), String> {
+        writer.write_name(&self.name, true)?;
+        writer.write_u16(self.rrtype);
+        writer.write_u16(self.class);
+        writer.write_u32(self.ttl);
+
+        // RDLENGTH placeholder, filled in once the RDATA has been written.
+        let rdlength_index = writer.len();
+        writer.write_u16(0);
+
+        self.rdata.write(writer)?;
+        let computed_rdlength = writer.len() - rdlength_index - 2;
+        if computed_rdlength > u16::MAX as usize {
+            return Err(format!(
+                "computed rdlength {} does not fit in RDLENGTH",
+                computed_rdlength
+            ));
         }
 
-        Ok((resource_records, start))
+        writer.set_u16(rdlength_index, computed_rdlength as u16)
     }
 
     /// Serialize the DNS resource records into a DNS protocol conformant, network ready buffer.
@@ -476,34 +1448,56 @@ impl DnsResourceRecord {
         buf: &mut Vec<u8>,
         domain_name_offsets: &mut HashMap<String, u16>,
     ) -> Result<usize, String> {
-        serialize_domain_name(&self.name, buf, domain_name_offsets)?;
+        write_with_parts(buf, domain_name_offsets, |writer| self.write(writer))?;
 
-        buf.push(((self.rrtype >> 8) & 0xFF) as u8);
-        buf.push((self.rrtype & 0xFF) as u8);
+        Ok(start + buf.len())
+    }
 
-        buf.push(((self.class >> 8) & 0xFF) as u8);
-        buf.push((self.class & 0xFF) as u8);
+    /// Synthesize the CNAME resource record for qname from this DNAME resource record, per RFC 6672 section 2.2.
+    ///
+    /// Returns `Ok(None)` if this is not a DNAME resource record or qname is not strictly below its owner name.
+    /// Returns `Err(DNS_RCODE_YXDOMAIN)` if the synthesized name would be longer than 255 octets.
+    pub fn synthesize_cname(&self, qname: &str) -> Result<Option<DnsResourceRecord>, u8> {
+        let target = match &self.rdata {
+            DnsResourceRecordData::DNAME(target) => target,
+            _ => return Ok(None),
+        };
 
-        buf.push(((self.ttl >> 24) & 0xFF) as u8);
-        buf.push(((self.ttl >> 16) & 0xFF) as u8);
-        buf.push(((self.ttl >> 8) & 0xFF) as u8);
-        buf.push((self.ttl & 0xFF) as u8);
+        if !is_subdomain_of(qname, &self.name) || domain_names_equal(qname, &self.name) {
+            return Ok(None);
+        }
 
-        buf.push(((self.rdlength >> 8) & 0xFF) as u8);
-        buf.push((self.rdlength & 0xFF) as u8);
+        // Replace the labels of the DNAME owner at the end of qname with those of the target.
+        let (mut labels, owner_labels, target_labels) = match (
+            domain_name_to_labels(qname),
+            domain_name_to_labels(&self.name),
+            domain_name_to_labels(target),
+        ) {
+            (Ok(labels), Ok(owner_labels), Ok(target_labels)) => {
+                (labels, owner_labels, target_labels)
+            }
+            _ => return Err(DNS_RCODE_SERVER_ERROR),
+        };
+        labels.truncate(labels.len() - owner_labels.len());
+        labels.extend(target_labels);
 
-        let computed_rdlength = buf.len();
-        self.rdata.serialize(buf, domain_name_offsets)?;
-        let computed_rdlength = buf.len() - computed_rdlength;
-        if computed_rdlength != self.rdlength as usize {
-            return Err(format!(
-                "rdlength {} does not match computed rdlength {}",
-                self.rdlength, computed_rdlength
-            ));
+        let rdlength = labels.iter().map(|label| label.len() + 1).sum::<usize>() + 1;
+        if rdlength > MAX_DOMAIN_NAME_LENGTH {
+            return Err(DNS_RCODE_YXDOMAIN);
         }
+        let cname = labels_to_domain_name(&labels);
 
-        let start = start + buf.len();
-        Ok(start)
+        let cname_resource_record = DnsResourceRecord::new(
+            String::from(qname),
+            DNS_TYPE_CNAME,
+            self.class,
+            self.ttl,
+            rdlength as u16,
+            DnsResourceRecordData::CNAME(cname),
+        )
+        .map_err(|_| DNS_RCODE_SERVER_ERROR)?;
+
+        Ok(Some(cname_resource_record))
     }
 }
 
@@ -513,16 +1507,16 @@ mod tests {
     use crate::{classes::*, dns_packet::dns_header::*, query_examples::*};
 
     mod example_rrtypes {
-        pub const A: &'static [u8] = &[0x7F, 0x00, 0x00, 0x01]; // 127.0.0.1
+        pub const A: &[u8] = &[0x7F, 0x00, 0x00, 0x01]; // 127.0.0.1
 
-        pub const CNAME: &'static [u8] = &[
+        pub const CNAME: &[u8] = &[
             0x03, 0x77, 0x77, 0x77, // www
             0x06, 0x67, 0x6F, 0x6F, 0x67, 0x6C, 0x65, // google
             0x03, 0x63, 0x6F, 0x6D, // com
             0x00, // root
         ];
 
-        pub const MX: &'static [u8] = &[
+        pub const MX: &[u8] = &[
             0x01, 0xA4, // PREFERENCE = 420
             0x03, 0x77, 0x77, 0x77, // www
             0x06, 0x67, 0x6F, 0x6F, 0x67, 0x6C, 0x65, // google
@@ -530,7 +1524,7 @@ mod tests {
             0x00, // root
         ];
 
-        pub const SOA: &'static [u8] = &[
+        pub const SOA: &[u8] = &[
             0x03, 0x77, 0x77, 0x77, // www
             0x06, 0x67, 0x6F, 0x6F, 0x67, 0x6C, 0x65, // google
             0x03, 0x63, 0x6F, 0x6D, // com
@@ -543,10 +1537,86 @@ mod tests {
             0x00, 0x01, 0x00, 0x00, // 65,536
         ];
 
-        pub const TXT: &'static [u8] = &[
+        pub const TXT: &[u8] = &[
             0x0B, // length=11
             0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x57, 0x6f, 0x72, 0x6c, 0x64, // "Hello World"
         ];
+
+        pub const TXT_MULTIPLE_STRINGS: &[u8] = &[
+            0x07, // length=7
+            0x76, 0x3D, 0x73, 0x70, 0x66, 0x31, 0x20, // "v=spf1 "
+            0x04, // length=4
+            0x2D, 0x61, 0x6C, 0x6C, // "-all"
+            0x0A, // length=10
+            0x63, 0x61, 0x66, 0xC3, 0xA9, 0x20, 0x22, 0x71, 0x22, 0x5C, // "café \"q\"\\"
+        ];
+
+        pub const RP: &[u8] = &[
+            0x05, 0x61, 0x64, 0x6D, 0x69, 0x6E, // admin
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, // example
+            0x03, 0x63, 0x6F, 0x6D, // com
+            0x00, // root
+            0x04, 0x69, 0x6E, 0x66, 0x6F, // info
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, // example
+            0x03, 0x63, 0x6F, 0x6D, // com
+            0x00, // root
+        ];
+
+        pub const AFSDB: &[u8] = &[
+            0x00, 0x01, // SUBTYPE = 1
+            0x03, 0x61, 0x66, 0x73, // afs
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, // example
+            0x03, 0x63, 0x6F, 0x6D, // com
+            0x00, // root
+        ];
+
+        pub const ISDN: &[u8] = &[
+            0x0F, // length=15
+            0x31, 0x35, 0x30, 0x38, 0x36, 0x32, 0x30, 0x32, 0x38, 0x30, 0x30, 0x33, 0x32, 0x31,
+            0x37, // "150862028003217"
+            0x03, // length=3
+            0x30, 0x30, 0x34, // "004"
+        ];
+
+        pub const URI: &[u8] = &[
+            0x00, 0x0A, // PRIORITY = 10
+            0x00, 0x01, // WEIGHT = 1
+            0x66, 0x74, 0x70, 0x3A, 0x2F, 0x2F, 0x66, 0x74, 0x70, 0x31, 0x2E, 0x65, 0x78, 0x61,
+            0x6D, 0x70, 0x6C, 0x65, 0x2E, 0x63, 0x6F, 0x6D, 0x2F, // "ftp://ftp1.example.com/"
+        ];
+
+        pub const CERT: &[u8] = &[
+            0x00, 0x01, // TYPE = PKIX
+            0x30, 0x39, // KEY TAG = 12345
+            0x08, // ALGORITHM = 8
+            0x66, 0x6F, 0x6F, 0x62, 0x61, 0x72, // "foobar"
+        ];
+
+        pub const APL: &[u8] = &[
+            0x00, 0x01, 0x15, 0x03, 0xC0, 0xA8, 0x20, // 1:192.168.32.0/21
+            0x00, 0x01, 0x1C, 0x84, 0xC0, 0xA8, 0x26, 0x01, // !1:192.168.38.1/28
+            0x00, 0x02, 0x00, 0x00, // 2:::/0
+        ];
+
+        pub const EUI48: &[u8] = &[0x00, 0x00, 0x5E, 0x00, 0x53, 0x2A];
+
+        pub const IPSECKEY: &[u8] = &[
+            0x0A, // PRECEDENCE = 10
+            0x01, // GATEWAY TYPE = IPv4
+            0x02, // ALGORITHM = RSA
+            0xC0, 0x00, 0x02, 0x26, // GATEWAY = 192.0.2.38
+            0x66, 0x6F, 0x6F, 0x62, 0x61, 0x72, // "foobar"
+        ];
+
+        pub const IPSECKEY_DOMAIN_NAME: &[u8] = &[
+            0x0A, // PRECEDENCE = 10
+            0x03, // GATEWAY TYPE = domain name
+            0x02, // ALGORITHM = RSA
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, // example
+            0x03, 0x63, 0x6F, 0x6D, // com
+            0x00, // root
+            0x66, 0x6F, 0x6F, 0x62, 0x61, 0x72, // "foobar"
+        ];
     }
     use example_rrtypes::*;
 
@@ -702,13 +1772,380 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_unknown_resource_record_data() -> Result<(), String> {
+        // An HTTPS record (type 65) is kept as opaque data.
+        let rdata: &[u8] = &[0x00, 0x01, 0x00];
+        let record_data = DnsResourceRecordData::parse(65, rdata, 0, rdata.len() as u16)?;
+        assert_eq!(record_data.rrtype(), 65);
+        assert_eq!(record_data.to_bytes()?, rdata);
+        assert_eq!(record_data.to_string(), "\\# 3 000100");
+
+        let parsed = DnsResourceRecordData::from_presentation(65, &["\\#", "3", "000100"], ".")?;
+        assert_eq!(parsed.to_bytes()?, rdata);
+        assert!(DnsResourceRecordData::from_presentation(65, &["1", "."], ".").is_err());
+
+        let empty = DnsResourceRecordData::parse(65, rdata, 0, 0)?;
+        assert_eq!(empty.to_string(), "\\# 0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rdata_must_match_rdlength() -> Result<(), String> {
+        // Each example with one trailing byte, which must be rejected rather than ignored.
+        let examples: [(u16, &[u8]); 6] = [
+            (DNS_TYPE_A, A),
+            (DNS_TYPE_CNAME, CNAME),
+            (DNS_TYPE_MX, MX),
+            (DNS_TYPE_SOA, SOA),
+            (DNS_TYPE_RP, RP),
+            (DNS_TYPE_EUI48, EUI48),
+        ];
+
+        for (rrtype, example) in examples.iter() {
+            let mut record_data_buf = Vec::from(*example);
+            record_data_buf.push(0);
+            let rdlength = record_data_buf.len() as u16;
+            assert!(
+                DnsResourceRecordData::parse(*rrtype, &record_data_buf, 0, rdlength).is_err(),
+                "type {} accepted trailing data",
+                rrtype
+            );
+
+            // Truncated RDATA must error, never panic.
+            for len in 0..example.len() {
+                assert!(DnsResourceRecordData::parse(*rrtype, example, 0, len as u16).is_err());
+            }
+        }
+
+        // RDLENGTH past the end of the buffer.
+        assert!(DnsResourceRecordData::parse(DNS_TYPE_A, A, 1, 4).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_resource_record_data() -> Result<(), String> {
+        let examples: [(u16, &[u8], &str); 5] = [
+            (DNS_TYPE_RP, RP, "admin.example.com. info.example.com."),
+            (DNS_TYPE_AFSDB, AFSDB, "1 afs.example.com."),
+            (DNS_TYPE_ISDN, ISDN, "\"150862028003217\" \"004\""),
+            (DNS_TYPE_URI, URI, "10 1 \"ftp://ftp1.example.com/\""),
+            (DNS_TYPE_CERT, CERT, "1 12345 8 Zm9vYmFy"),
+        ];
+
+        for (rrtype, example, presentation) in examples.iter() {
+            let record_data_buf = Vec::from(*example);
+            let record_data =
+                DnsResourceRecordData::parse(*rrtype, &record_data_buf, 0, example.len() as u16)?;
+            assert_eq!(record_data.to_string(), *presentation);
+
+            let record_data_serialized = &record_data.to_bytes()?;
+            assert_eq!(*example, record_data_serialized.as_slice());
+        }
+
+        // Names in RDATA of newer types must not be compressed, even when a suffix was already written.
+        let mut writer = WireWriter::new();
+        writer.write_name(&String::from("example.com."), true)?;
+        let start = writer.len();
+        DnsResourceRecordData::RT((10, "relay.example.com.".into())).write(&mut writer)?;
+        assert_eq!(writer.len() - start, 2 + 19);
+
+        let record_data = DnsResourceRecordData::X25("311061700956".into());
+        assert_eq!(record_data.to_string(), "\"311061700956\"");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_address_family_resource_record_data() -> Result<(), String> {
+        let examples: [(u16, &[u8], &str); 4] = [
+            (
+                DNS_TYPE_APL,
+                APL,
+                "1:192.168.32.0/21 !1:192.168.38.1/28 2:::/0",
+            ),
+            (DNS_TYPE_EUI48, EUI48, "00-00-5e-00-53-2a"),
+            (DNS_TYPE_IPSECKEY, IPSECKEY, "10 1 2 192.0.2.38 Zm9vYmFy"),
+            (
+                DNS_TYPE_IPSECKEY,
+                IPSECKEY_DOMAIN_NAME,
+                "10 3 2 example.com. Zm9vYmFy",
+            ),
+        ];
+
+        for (rrtype, example, presentation) in examples.iter() {
+            let record_data_buf = Vec::from(*example);
+            let record_data =
+                DnsResourceRecordData::parse(*rrtype, &record_data_buf, 0, example.len() as u16)?;
+            assert_eq!(record_data.to_string(), *presentation);
+
+            let record_data_serialized = &record_data.to_bytes()?;
+            assert_eq!(*example, record_data_serialized.as_slice());
+        }
+
+        let record_data =
+            DnsResourceRecordData::EUI64([0x00, 0x00, 0x5E, 0xEF, 0x10, 0x00, 0x00, 0x2A]);
+        assert_eq!(record_data.to_string(), "00-00-5e-ef-10-00-00-2a");
+
+        let record_data = DnsResourceRecordData::IPSECKEY((10, 0, IpsecKeyGateway::None, vec![]));
+        assert_eq!(record_data.to_string(), "10 0 0 .");
+
+        // An IPv4 item can not carry more than 4 address octets.
+        let invalid_apl = [0x00, 0x01, 0x08, 0x05, 0x0A, 0x00, 0x00, 0x00, 0x00];
+        assert!(
+            DnsResourceRecordData::parse(DNS_TYPE_APL, &Vec::from(&invalid_apl[..]), 0, 9).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_presentation() -> Result<(), String> {
+        let origin = "example.com.";
+        let examples: &[(u16, &[&str], &str)] = &[
+            (DNS_TYPE_A, &["192.0.2.1"], "192.0.2.1"),
+            (DNS_TYPE_AAAA, &["2001:db8::1"], "2001:db8::1"),
+            (DNS_TYPE_CNAME, &["www"], "www.example.com."),
+            (DNS_TYPE_NS, &["@"], "example.com."),
+            (
+                DNS_TYPE_SOA,
+                &[
+                    "ns1",
+                    "hostmaster.example.org.",
+                    "2024010101",
+                    "1h",
+                    "15m",
+                    "1w",
+                    "300",
+                ],
+                "ns1.example.com. hostmaster.example.org. 2024010101 3600 900 604800 300",
+            ),
+            (
+                DNS_TYPE_WKS,
+                &["192.0.2.1", "tcp", "25", "80"],
+                "192.0.2.1 6 25 80",
+            ),
+            (
+                DNS_TYPE_HINFO,
+                &["INTEL-386", "Unix"],
+                "\"INTEL-386\" \"Unix\"",
+            ),
+            (
+                DNS_TYPE_MINFO,
+                &["admin", "errors"],
+                "admin.example.com. errors.example.com.",
+            ),
+            (
+                DNS_TYPE_MX,
+                &["10", "mail.example.net."],
+                "10 mail.example.net.",
+            ),
+            (
+                DNS_TYPE_TXT,
+                &["v=spf1 -all", "a\\\"b\\\\c\\255"],
+                "\"v=spf1 -all\" \"a\\\"b\\\\c\\255\"",
+            ),
+            (
+                DNS_TYPE_SRV,
+                &["0", "5", "5060", "sip"],
+                "0 5 5060 sip.example.com.",
+            ),
+            (DNS_TYPE_RP, &["admin", "."], "admin.example.com. ."),
+            (DNS_TYPE_AFSDB, &["1", "afs"], "1 afs.example.com."),
+            (DNS_TYPE_X25, &["311061700956"], "\"311061700956\""),
+            (DNS_TYPE_ISDN, &["150862028003217"], "\"150862028003217\""),
+            (DNS_TYPE_RT, &["10", "relay"], "10 relay.example.com."),
+            (
+                DNS_TYPE_CERT,
+                &["PGP", "0", "0", "Zm9v", "YmFy"],
+                "3 0 0 Zm9vYmFy",
+            ),
+            (
+                DNS_TYPE_URI,
+                &["10", "1", "ftp://ftp1.example.com/public"],
+                "10 1 \"ftp://ftp1.example.com/public\"",
+            ),
+            (DNS_TYPE_DNAME, &["example.net."], "example.net."),
+            (
+                DNS_TYPE_APL,
+                &["1:192.168.32.0/21", "!1:192.168.38.0/28", "2:2001:db8::/32"],
+                "1:192.168.32.0/21 !1:192.168.38.0/28 2:2001:db8::/32",
+            ),
+            (DNS_TYPE_APL, &[], ""),
+            (DNS_TYPE_EUI48, &["00-00-5e-00-53-2a"], "00-00-5e-00-53-2a"),
+            (
+                DNS_TYPE_EUI64,
+                &["00-00-5E-EF-10-00-00-2A"],
+                "00-00-5e-ef-10-00-00-2a",
+            ),
+            (
+                DNS_TYPE_IPSECKEY,
+                &["10", "3", "2", "gateway", "Zm9vYmFy"],
+                "10 3 2 gateway.example.com. Zm9vYmFy",
+            ),
+            (DNS_TYPE_IPSECKEY, &["10", "0", "2", "."], "10 0 2 ."),
+            (DNS_TYPE_NULL, &["\\#", "3", "ABCD", "EF"], "\\# 3 ABCDEF"),
+            (DNS_TYPE_A, &["\\#", "4", "C0000201"], "192.0.2.1"),
+        ];
+
+        for (rrtype, fields, presentation) in examples.iter() {
+            let record_data = DnsResourceRecordData::from_presentation(*rrtype, fields, origin)?;
+            assert_eq!(record_data.to_string(), *presentation);
+        }
+
+        let invalid: &[(u16, &[&str])] = &[
+            (DNS_TYPE_A, &["192.0.2"]),
+            (DNS_TYPE_A, &["192.0.2.1", "192.0.2.2"]),
+            (DNS_TYPE_MX, &["10"]),
+            (DNS_TYPE_MX, &["65536", "mail"]),
+            (DNS_TYPE_CNAME, &["a..b"]),
+            (DNS_TYPE_TXT, &[]),
+            (DNS_TYPE_TXT, &["\\256"]),
+            (DNS_TYPE_EUI48, &["00-00-5e-00-53"]),
+            (DNS_TYPE_APL, &["3:192.0.2.0/24"]),
+            (DNS_TYPE_A, &["\\#", "5", "C0000201"]),
+            (DNS_TYPE_NULL, &["data"]),
+            (DNS_TYPE_OPT, &["\\#", "0"]),
+        ];
+
+        for (rrtype, fields) in invalid.iter() {
+            let result = DnsResourceRecordData::from_presentation(*rrtype, fields, origin);
+            assert!(result.is_err(), "{} {:?}", rrtype, fields);
+        }
+
+        let long_string = "a".repeat(MAX_CHARACTER_STRING_LENGTH + 1);
+        assert!(character_string_from_presentation(&long_string).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ttl() -> Result<(), String> {
+        assert_eq!(parse_ttl("3600")?, 3600);
+        assert_eq!(parse_ttl("1h30m")?, 5400);
+        assert_eq!(parse_ttl("1W2d3H4m5S")?, 788645);
+        assert_eq!(parse_ttl("2147483647")?, MAX_TTL);
+        assert!(parse_ttl("2147483648").is_err());
+        assert!(parse_ttl("1h30").is_err());
+        assert!(parse_ttl("h").is_err());
+        assert!(parse_ttl("1y").is_err());
+        assert!(parse_ttl("").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_synthesize_cname() -> Result<(), String> {
+        let dname = DnsResourceRecord::new(
+            "example.com.".into(),
+            DNS_TYPE_DNAME,
+            DNS_CLASS_IN,
+            300,
+            13,
+            DnsResourceRecordData::DNAME("example.net.".into()),
+        )?;
+
+        let cname = dname
+            .synthesize_cname("www.sub.EXAMPLE.com.")
+            .map_err(|rcode| format!("RCODE={}", rcode))?
+            .ok_or("no CNAME synthesized")?;
+        assert_eq!(cname.name, "www.sub.EXAMPLE.com.");
+        assert_eq!(cname.rrtype, DNS_TYPE_CNAME);
+        assert_eq!(cname.ttl, 300);
+        assert_eq!(cname.rdlength, 21);
+        assert!(match cname.rdata {
+            DnsResourceRecordData::CNAME(target) => target == "www.sub.example.net.",
+            _ => false,
+        });
+
+        // The owner itself and names outside of the subtree are not redirected.
+        assert!(matches!(dname.synthesize_cname("example.com."), Ok(None)));
+        assert!(matches!(dname.synthesize_cname("example.org."), Ok(None)));
+        assert!(matches!(dname.synthesize_cname("éxample.com."), Ok(None)));
+        assert!(matches!(
+            dname.synthesize_cname("bücher.example.com."),
+            Ok(None)
+        ));
+
+        let long_label = "a".repeat(63);
+        let long_target = format!("{0}.{0}.{0}.", long_label);
+        let dname = DnsResourceRecord::new(
+            "example.com.".into(),
+            DNS_TYPE_DNAME,
+            DNS_CLASS_IN,
+            300,
+            domain_name_wire_length(&long_target) as u16,
+            DnsResourceRecordData::DNAME(long_target),
+        )?;
+        let qname = format!("{}.example.com.", long_label);
+        assert!(matches!(
+            dname.synthesize_cname(&qname),
+            Err(DNS_RCODE_YXDOMAIN)
+        ));
+
+        let serialized = dname.rdata.to_bytes()?;
+        let parsed =
+            DnsResourceRecordData::parse(DNS_TYPE_DNAME, &serialized, 0, serialized.len() as u16)?;
+        assert!(match parsed {
+            DnsResourceRecordData::DNAME(target) => target == format!("{0}.{0}.{0}.", long_label),
+            _ => false,
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_txt_resource_record_data() -> Result<(), String> {
+        let record_data_buf = Vec::from(TXT_MULTIPLE_STRINGS);
+        let record_data = DnsResourceRecordData::parse(
+            DNS_TYPE_TXT,
+            &record_data_buf,
+            0,
+            record_data_buf.len() as u16,
+        )?;
+        assert_eq!(
+            record_data.to_string(),
+            "\"v=spf1 \" \"-all\" \"caf\\195\\169 \\\"q\\\"\\\\\""
+        );
+        assert_eq!(
+            record_data.txt_data().ok_or("no txt data")?,
+            b"v=spf1 -allcaf\xC3\xA9 \"q\"\\"
+        );
+
+        let record_data_serialized = &record_data.to_bytes()?;
+        assert_eq!(TXT_MULTIPLE_STRINGS, record_data_serialized.as_slice());
+
+        let long_text = vec![b'k'; 600];
+        let record_data = DnsResourceRecordData::new_txt(&long_text);
+        match &record_data {
+            DnsResourceRecordData::TXT(txt_data) => {
+                let lengths: Vec<usize> = txt_data.iter().map(|string| string.len()).collect();
+                assert_eq!(lengths, vec![255, 255, 90]);
+            }
+            _ => return Err("new_txt did not create TXT data".into()),
+        }
+        assert_eq!(record_data.txt_data().ok_or("no txt data")?, long_text);
+
+        let record_data_serialized = &record_data.to_bytes()?;
+        assert_eq!(record_data_serialized.len(), 603);
+
+        let record_data = DnsResourceRecordData::new_txt(b"");
+        assert_eq!(record_data.to_string(), "\"\"");
+
+        assert!(serialize_character_string(&[0; 256], &mut Vec::new()).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_character_string() -> Result<(), String> {
         let buf = Vec::from(TXT);
 
         let (character_string, end) = parse_character_string(&buf, 0, buf.len())?;
 
-        assert_eq!(character_string, String::from("Hello World"));
+        assert_eq!(character_string, b"Hello World");
         assert_eq!(end, buf.len());
 
         Ok(())
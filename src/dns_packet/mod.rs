@@ -8,15 +8,14 @@ pub mod dns_question;
 pub mod dns_resource_record;
 
 use crate::domain_name::*;
-use crate::{classes::*, *};
+use crate::{classes::*, encoding::*, mnemonics::*, wire::*, *};
 use dns_header::*;
 use dns_question::*;
 use dns_resource_record::*;
-
-use std::collections::HashMap;
+use std::fmt;
 
 /// DNS Packet.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DnsPacket {
     /// DNS Header for the DNS packet.
     pub header: DnsHeader,
@@ -31,10 +30,12 @@ pub struct DnsPacket {
 }
 
 impl DnsPacket {
+    /// Create a query for domain_name, which may contain Unicode labels that are converted with IDNA.
     pub fn new(domain_name: &String, resource_record_type: u16) -> Result<DnsPacket, String> {
         let mut header = DnsHeader::new()?;
 
-        let domain_name = normalize_domain_name(domain_name);
+        let domain_name = crate::idna::domain_name_to_ascii(&normalize_domain_name(domain_name))
+            .map_err(|error| format!("DnsPacket::new() invalid domain name: {}", error))?;
         if !is_domain_name_valid(&domain_name) {
             return Err(format!(
                 "DnsPacket::new() invalid domain name: {}",
@@ -62,17 +63,14 @@ impl DnsPacket {
     }
 
     /// Parse a DNS packet from a raw DNS packet.
-    pub fn parse_dns_packet(dns_packet_buf: &Vec<u8>) -> Result<DnsPacket, String> {
-        let header = DnsHeader::parse(dns_packet_buf)?;
-
-        let start = DNS_HEADER_SIZE;
-        let (questions, start) = DnsQuestion::parse_questions(dns_packet_buf, &header, start)?;
-        let (answers, start) =
-            DnsResourceRecord::parse_resource_records(dns_packet_buf, start, header.ancount)?;
-        let (authorities, start) =
-            DnsResourceRecord::parse_resource_records(dns_packet_buf, start, header.nscount)?;
-        let (additionals, _) =
-            DnsResourceRecord::parse_resource_records(dns_packet_buf, start, header.arcount)?;
+    pub fn parse_dns_packet(dns_packet_buf: &[u8]) -> Result<DnsPacket, String> {
+        let mut reader = WireReader::new(dns_packet_buf);
+
+        let header = DnsHeader::read(&mut reader)?;
+        let questions = DnsQuestion::read_questions(&mut reader, header.qdcount)?;
+        let answers = DnsResourceRecord::read_resource_records(&mut reader, header.ancount)?;
+        let authorities = DnsResourceRecord::read_resource_records(&mut reader, header.nscount)?;
+        let additionals = DnsResourceRecord::read_resource_records(&mut reader, header.arcount)?;
 
         let dns_packet: DnsPacket = DnsPacket {
             header,
@@ -86,36 +84,291 @@ impl DnsPacket {
     }
 
     /// Serialize the DNS packet into a DNS protocol conformant, network ready buffer.
+    /// Names are compressed unless message compression has been turned off with
+    /// [set_message_compression].
     pub fn serialize(&self) -> Result<Vec<u8>, String> {
-        let mut buf = Vec::new();
-        let mut domain_name_offsets = HashMap::new();
+        self.serialize_with_compression(message_compression())
+    }
 
-        buf.append(&mut self.header.serialize());
+    /// Serialize the DNS packet into a DNS protocol conformant, network ready buffer.
+    /// If compress is false no message compression is used at all, otherwise owner names are always
+    /// compressed and RDATA domain names are compressed according to their type's policy.
+    pub fn serialize_with_compression(&self, compress: bool) -> Result<Vec<u8>, String> {
+        let mut writer = WireWriter::with_compression(compress);
 
-        let mut curr_index = DNS_HEADER_SIZE;
+        self.header.write(&mut writer);
 
         for question in &self.question {
-            curr_index = question.serialize(curr_index, &mut buf, &mut domain_name_offsets)?;
+            question.write(&mut writer)?;
         }
 
-        for resource_record in &self.answer {
-            curr_index =
-                resource_record.serialize(curr_index, &mut buf, &mut domain_name_offsets)?;
+        for resource_record in self
+            .answer
+            .iter()
+            .chain(self.authority.iter())
+            .chain(self.additional.iter())
+        {
+            resource_record.write(&mut writer)?;
         }
 
-        for resource_record in &self.authority {
-            curr_index =
-                resource_record.serialize(curr_index, &mut buf, &mut domain_name_offsets)?;
+        Ok(writer.into_bytes())
+    }
+
+    /// Serialize the DNS packet like [DnsPacket::serialize], leaving out what does not fit in max_size
+    /// octets. Whole RRsets are included in priority order, the answer section, then the authority
+    /// section, then the additional section, and the OPT record is always kept. TC is set only if part
+    /// of the answer or authority section was left out (RFC 2181 section 9), as the additional section
+    /// holds no required data. Fails if the header and question alone do not fit.
+    pub fn serialize_with_limit(&self, max_size: usize) -> Result<Vec<u8>, String> {
+        let serialized = self.serialize()?;
+        if serialized.len() <= max_size {
+            return Ok(serialized);
+        }
+
+        let mut writer = WireWriter::new();
+        self.header.write(&mut writer);
+        for question in &self.question {
+            question.write(&mut writer)?;
         }
 
-        for resource_record in &self.additional {
-            resource_record.serialize(curr_index, &mut buf, &mut domain_name_offsets)?;
+        // Room is kept for the OPT record until it has been written.
+        let mut reserved = 0;
+        if let Some(opt) = self.opt() {
+            let mut opt_writer = WireWriter::new();
+            opt.write(&mut opt_writer)?;
+            reserved = opt_writer.len();
+        }
+        if writer.len() + reserved > max_size {
+            return Err(format!(
+                "DNS packet header and question do not fit in {} octets",
+                max_size
+            ));
         }
 
-        Ok(buf)
+        let mut packet = DnsPacket {
+            header: self.header.clone(),
+            question: self.question.clone(),
+            answer: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+        };
+        let mut truncated = false;
+        for (section, kept, required) in [
+            (&self.answer, &mut packet.answer, true),
+            (&self.authority, &mut packet.authority, true),
+            (&self.additional, &mut packet.additional, false),
+        ] {
+            for rrset in rrsets(section) {
+                let is_opt = rrset[0].rrtype == types::DNS_TYPE_OPT;
+                if truncated && !is_opt {
+                    continue;
+                }
+
+                let len = writer.len();
+                for record in &rrset {
+                    record.write(&mut writer)?;
+                }
+                if is_opt {
+                    reserved = 0;
+                } else if writer.len() + reserved > max_size {
+                    writer.truncate(len);
+                    truncated = required;
+                    continue;
+                }
+                kept.extend(rrset.into_iter().cloned());
+            }
+        }
+
+        packet.header.tc |= truncated;
+        packet.update_counts();
+        packet.serialize()
+    }
+
+    /// Set the QDCOUNT, ANCOUNT, NSCOUNT and ARCOUNT of the header to the number of entries in
+    /// each section.
+    pub fn update_counts(&mut self) {
+        self.header.qdcount = self.question.len() as u16;
+        self.header.ancount = self.answer.len() as u16;
+        self.header.nscount = self.authority.len() as u16;
+        self.header.arcount = self.additional.len() as u16;
+    }
+
+    /// The EDNS(0) OPT pseudo-RR in the additional section, if there is one.
+    pub fn opt(&self) -> Option<&DnsResourceRecord> {
+        self.additional
+            .iter()
+            .find(|record| record.rrtype == types::DNS_TYPE_OPT)
+    }
+
+    /// The RCODE of the response, including the upper 8 bits carried by the OPT pseudo-RR if present.
+    pub fn extended_rcode(&self) -> u16 {
+        match self.opt() {
+            Some(opt) => ((opt.ttl >> 24) as u16) << 4 | self.header.rcode as u16,
+            None => self.header.rcode as u16,
+        }
+    }
+
+    /// Follow the CNAME and DNAME resource records in the answer section starting at qname,
+    /// returning the canonical name the chain ends at.
+    pub fn follow_alias_chain(&self, qname: &str) -> Result<String, String> {
+        let mut chain = self.alias_chain(qname)?;
+        Ok(chain.pop().unwrap_or_else(|| String::from(qname)))
+    }
+
+    /// The names on the chain of CNAME and DNAME resource records in the answer section starting at
+    /// qname, from qname to the canonical name.
+    pub fn alias_chain(&self, qname: &str) -> Result<Vec<String>, String> {
+        let mut name = String::from(qname);
+        let mut chain = Vec::new();
+
+        for _ in 0..MAX_ALIAS_CHAIN_LENGTH {
+            chain.push(name.clone());
+            let cname = self.answer.iter().find_map(|record| match &record.rdata {
+                DnsResourceRecordData::CNAME(target) if domain_names_equal(&record.name, &name) => {
+                    Some(target.clone())
+                }
+                _ => None,
+            });
+
+            if let Some(cname) = cname {
+                name = cname;
+                continue;
+            }
+
+            let synthesized = self
+                .answer
+                .iter()
+                .find_map(|record| record.synthesize_cname(&name).transpose());
+
+            match synthesized {
+                Some(Ok(DnsResourceRecord {
+                    rdata: DnsResourceRecordData::CNAME(target),
+                    ..
+                })) => {
+                    name = target;
+                }
+                Some(Err(rcode)) => {
+                    return Err(format!(
+                        "DNAME substitution for {} failed with RCODE={}",
+                        name, rcode
+                    ));
+                }
+                _ => {
+                    return Ok(chain);
+                }
+            }
+        }
+
+        Err(format!("alias chain starting at {} is too long", qname))
+    }
+}
+
+/// Formats the DNS packet like dig does, with a header, the OPT pseudo-section, and each
+/// non-empty section in presentation format.
+impl fmt::Display for DnsPacket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = &self.header;
+
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            opcode_to_string(header.opcode),
+            rcode_to_string(self.extended_rcode()),
+            header.id
+        )?;
+
+        // The Z field holds the AD and CD bits of RFC 4035 in its lower two bits.
+        let flags: Vec<&str> = [
+            (header.qr, "qr"),
+            (header.aa, "aa"),
+            (header.tc, "tc"),
+            (header.rd, "rd"),
+            (header.ra, "ra"),
+            (header.z & 0x2 != 0, "ad"),
+            (header.z & 0x1 != 0, "cd"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| *flag)
+        .collect();
+        writeln!(
+            f,
+            ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            flags.join(" "),
+            header.qdcount,
+            header.ancount,
+            header.nscount,
+            header.arcount
+        )?;
+
+        if let Some(opt) = self.opt() {
+            writeln!(f, "\n;; OPT PSEUDOSECTION:")?;
+            writeln!(
+                f,
+                "; EDNS: version: {}, flags:{}; udp: {}",
+                (opt.ttl >> 16) & 0xFF,
+                if opt.ttl & 0x8000 != 0 { " do" } else { "" },
+                opt.class
+            )?;
+
+            if let DnsResourceRecordData::OPT(options) = &opt.rdata {
+                for (code, data) in options {
+                    match code {
+                        3 => writeln!(f, "; NSID: {}", hex_encode(data))?,
+                        10 => writeln!(f, "; COOKIE: {}", hex_encode(data))?,
+                        _ => writeln!(f, "; OPT={}: {}", code, hex_encode(data))?,
+                    }
+                }
+            }
+        }
+
+        writeln!(f, "\n;; QUESTION SECTION:")?;
+        for question in &self.question {
+            writeln!(f, ";{}", question)?;
+        }
+
+        let sections = [
+            ("ANSWER", &self.answer),
+            ("AUTHORITY", &self.authority),
+            ("ADDITIONAL", &self.additional),
+        ];
+        for (section, resource_records) in sections.iter() {
+            let resource_records: Vec<&DnsResourceRecord> = resource_records
+                .iter()
+                .filter(|record| record.rrtype != types::DNS_TYPE_OPT)
+                .collect();
+            if resource_records.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "\n;; {} SECTION:", section)?;
+            for resource_record in resource_records {
+                writeln!(f, "{}", resource_record)?;
+            }
+        }
+
+        Ok(())
     }
 }
 
+/// The RRsets of a section, in the order their first record appears.
+fn rrsets(records: &[DnsResourceRecord]) -> Vec<Vec<&DnsResourceRecord>> {
+    let mut rrsets: Vec<Vec<&DnsResourceRecord>> = Vec::new();
+    for record in records {
+        let rrset = rrsets.iter_mut().find(|rrset| {
+            rrset[0].rrtype == record.rrtype
+                && rrset[0].class == record.class
+                && domain_names_equal(&rrset[0].name, &record.name)
+        });
+        match rrset {
+            Some(rrset) => rrset.push(record),
+            None => rrsets.push(vec![record]),
+        }
+    }
+
+    rrsets
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,4 +386,377 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_new() -> Result<(), String> {
+        for domain_name in [
+            "_dmarc.example.com",
+            "_sip._tcp.example.com.",
+            "3com.com.",
+            "*.example.com.",
+            "dot\\.inlabel.example.com.",
+            "xn--bcher-kva.example.",
+        ]
+        .iter()
+        {
+            let dns_packet = DnsPacket::new(&String::from(*domain_name), types::DNS_TYPE_TXT)?;
+            let parsed = DnsPacket::parse_dns_packet(&dns_packet.serialize()?)?;
+            assert_eq!(
+                parsed.question[0].qname,
+                normalize_domain_name(&(*domain_name).into())
+            );
+        }
+
+        assert!(DnsPacket::new(&String::from("space in.example.com."), types::DNS_TYPE_A).is_err());
+        assert!(DnsPacket::new(&String::from("empty..example.com."), types::DNS_TYPE_A).is_err());
+
+        let dns_packet = DnsPacket::new(&String::from("Bücher.example"), types::DNS_TYPE_A)?;
+        assert_eq!(dns_packet.question[0].qname, "xn--bcher-kva.example.");
+        assert!(DnsPacket::new(&String::from("bü☃.example"), types::DNS_TYPE_A).is_err());
+
+        Ok(())
+    }
+
+    fn compression_example_packet() -> Result<DnsPacket, String> {
+        let mut dns_packet = DnsPacket::new(&String::from("example.com."), types::DNS_TYPE_MX)?;
+        dns_packet.header.id = 0xBEEF;
+        dns_packet.header.ancount = 3;
+
+        dns_packet.answer.push(DnsResourceRecord::new(
+            "example.com.".into(),
+            types::DNS_TYPE_MX,
+            DNS_CLASS_IN,
+            3600,
+            0,
+            DnsResourceRecordData::MX((10, "mail.example.com.".into())),
+        )?);
+        dns_packet.answer.push(DnsResourceRecord::new(
+            "sip.example.com.".into(),
+            types::DNS_TYPE_SRV,
+            DNS_CLASS_IN,
+            3600,
+            0,
+            DnsResourceRecordData::SRV((0, 5, 5060, "sip.example.com.".into())),
+        )?);
+        dns_packet.answer.push(DnsResourceRecord::new(
+            "www.example.com.".into(),
+            types::DNS_TYPE_CNAME,
+            DNS_CLASS_IN,
+            3600,
+            0,
+            DnsResourceRecordData::CNAME("example.com.".into()),
+        )?);
+
+        Ok(dns_packet)
+    }
+
+    #[test]
+    fn test_serialize_compression_policy() -> Result<(), String> {
+        let dns_packet = compression_example_packet()?;
+
+        let correct: &[u8] = &[
+            0xBE, 0xEF, 0x01, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, // header
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, // example
+            0x03, 0x63, 0x6F, 0x6D, // com
+            0x00, // root
+            0x00, 0x0F, 0x00, 0x01, // QTYPE=MX, QCLASS=IN
+            0xC0, 0x0C, // example.com.
+            0x00, 0x0F, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, // MX IN 3600
+            0x00, 0x09, // RDLENGTH=9
+            0x00, 0x0A, // PREFERENCE=10
+            0x04, 0x6D, 0x61, 0x69, 0x6C, 0xC0, 0x0C, // mail.example.com. compressed
+            0x03, 0x73, 0x69, 0x70, 0xC0, 0x0C, // sip.example.com.
+            0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, // SRV IN 3600
+            0x00, 0x17, // RDLENGTH=23
+            0x00, 0x00, 0x00, 0x05, 0x13, 0xC4, // PRIORITY=0 WEIGHT=5 PORT=5060
+            0x03, 0x73, 0x69, 0x70, // sip, never compressed per RFC 2782
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, // example
+            0x03, 0x63, 0x6F, 0x6D, // com
+            0x00, // root
+            0x03, 0x77, 0x77, 0x77, 0xC0, 0x0C, // www.example.com.
+            0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, // CNAME IN 3600
+            0x00, 0x02, // RDLENGTH=2
+            0xC0, 0x0C, // example.com. compressed
+        ];
+
+        let res = dns_packet.serialize()?;
+        assert_eq!(correct, res.as_slice(), "\nres: {:02X?}", res);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_sections() -> Result<(), String> {
+        let dns_packet = compression_example_packet()?;
+
+        // Serializing the sections one by one into a shared buffer gives the same message.
+        let mut writer = WireWriter::new();
+        dns_packet.header.write(&mut writer);
+        let mut buf = writer.into_bytes();
+        let mut domain_name_offsets = std::collections::HashMap::new();
+        dns_packet.question[0].serialize(0, &mut buf, &mut domain_name_offsets)?;
+        for resource_record in &dns_packet.answer {
+            resource_record.serialize(0, &mut buf, &mut domain_name_offsets)?;
+        }
+        assert_eq!(buf, dns_packet.serialize_with_compression(true)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_uncompressed() -> Result<(), String> {
+        let dns_packet = compression_example_packet()?;
+
+        let correct: &[u8] = &[
+            0xBE, 0xEF, 0x01, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, // header
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+            0x00, 0x0F, 0x00, 0x01, // QTYPE=MX, QCLASS=IN
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+            0x00, 0x0F, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, // MX IN 3600
+            0x00, 0x14, // RDLENGTH=20
+            0x00, 0x0A, // PREFERENCE=10
+            0x04, 0x6D, 0x61, 0x69, 0x6C, // mail
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+            0x03, 0x73, 0x69, 0x70, // sip
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+            0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, // SRV IN 3600
+            0x00, 0x17, // RDLENGTH=23
+            0x00, 0x00, 0x00, 0x05, 0x13, 0xC4, // PRIORITY=0 WEIGHT=5 PORT=5060
+            0x03, 0x73, 0x69, 0x70, // sip
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+            0x03, 0x77, 0x77, 0x77, // www
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+            0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, // CNAME IN 3600
+            0x00, 0x0D, // RDLENGTH=13
+            0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // example.com.
+        ];
+
+        let res = dns_packet.serialize_with_compression(false)?;
+        assert_eq!(correct, res.as_slice(), "\nres: {:02X?}", res);
+
+        // Both forms must parse back to the same records.
+        let parsed = DnsPacket::parse_dns_packet(&res)?;
+        assert_eq!(parsed.serialize()?, dns_packet.serialize()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_arbitrary_input() -> Result<(), String> {
+        use rand::prelude::*;
+
+        let response = compression_example_packet()?.serialize()?;
+
+        // Every truncation of a valid packet must be rejected without panicking.
+        for len in 0..response.len() {
+            assert!(DnsPacket::parse_dns_packet(&response[..len]).is_err());
+        }
+
+        // Corrupting single bytes may or may not produce a valid packet, but must never panic.
+        for index in 0..response.len() {
+            for value in [0x00, 0x3F, 0x40, 0x80, 0xC0, 0xFF].iter() {
+                let mut corrupted = response.clone();
+                corrupted[index] = *value;
+                let _ = DnsPacket::parse_dns_packet(&corrupted);
+            }
+        }
+
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let len = rng.gen_range(0, 600);
+            let mut random: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            if random.len() >= DNS_HEADER_SIZE {
+                // Keep the section counts small so the records themselves get parsed.
+                for count in (4..DNS_HEADER_SIZE).step_by(2) {
+                    random[count] = 0;
+                    random[count + 1] &= 0x07;
+                }
+            }
+            let _ = DnsPacket::parse_dns_packet(&random);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> Result<(), String> {
+        let mut dns_packet = compression_example_packet()?;
+        dns_packet.header.qr = true;
+        dns_packet.header.ra = true;
+        dns_packet.header.z = 0x2;
+        dns_packet.header.rcode = rcodes::DNS_RCODE_BADVERS & 0xF;
+        dns_packet.header.arcount = 2;
+        dns_packet.additional.push(DnsResourceRecord::new(
+            "mail.example.com.".into(),
+            types::DNS_TYPE_A,
+            DNS_CLASS_IN,
+            600,
+            4,
+            DnsResourceRecordData::A("192.0.2.1".parse().map_err(|_| "bad address")?),
+        )?);
+        dns_packet.additional.push(DnsResourceRecord::new(
+            ".".into(),
+            types::DNS_TYPE_OPT,
+            1232,
+            0x0100_8000, // extended RCODE 1, version 0, DO
+            0,
+            DnsResourceRecordData::OPT(vec![(10, vec![0x01, 0x23, 0x45, 0x67])]),
+        )?);
+
+        let correct = "\
+;; ->>HEADER<<- opcode: QUERY, status: BADVERS, id: 48879
+;; flags: qr rd ra ad; QUERY: 1, ANSWER: 3, AUTHORITY: 0, ADDITIONAL: 2
+
+;; OPT PSEUDOSECTION:
+; EDNS: version: 0, flags: do; udp: 1232
+; COOKIE: 01234567
+
+;; QUESTION SECTION:
+;example.com. IN MX
+
+;; ANSWER SECTION:
+example.com. 3600 IN MX 10 mail.example.com.
+sip.example.com. 3600 IN SRV 0 5 5060 sip.example.com.
+www.example.com. 3600 IN CNAME example.com.
+
+;; ADDITIONAL SECTION:
+mail.example.com. 600 IN A 192.0.2.1
+";
+        assert_eq!(dns_packet.to_string(), correct);
+
+        // The OPT pseudo-RR survives a round trip through the wire format.
+        let parsed = DnsPacket::parse_dns_packet(&dns_packet.serialize()?)?;
+        assert_eq!(parsed.to_string(), correct);
+        assert_eq!(
+            parsed.opt().map(|opt| opt.rdata.to_string()),
+            Some(String::from("\\# 8 000A000401234567"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_with_limit() -> Result<(), String> {
+        let record = |name: &str, rdata: DnsResourceRecordData| {
+            DnsResourceRecord::new(name.into(), rdata.rrtype(), DNS_CLASS_IN, 3600, 0, rdata)
+        };
+        let address = |address: &str| -> Result<DnsResourceRecordData, String> {
+            Ok(DnsResourceRecordData::A(
+                address.parse().map_err(|_| "bad address")?,
+            ))
+        };
+
+        let mut dns_packet = DnsPacket::new(&String::from("example.com."), qtypes::DNS_QTYPE_ANY)?;
+        dns_packet.header.qr = true;
+        let ns1 = record(
+            "example.com.",
+            DnsResourceRecordData::NS("ns1.example.com.".into()),
+        )?;
+        let ns2 = record(
+            "example.com.",
+            DnsResourceRecordData::NS("ns2.example.com.".into()),
+        )?;
+        let mx = record(
+            "example.com.",
+            DnsResourceRecordData::MX((10, "mail.example.com.".into())),
+        )?;
+        dns_packet.answer = vec![ns1.clone(), mx.clone(), ns2.clone()];
+        let opt = DnsResourceRecord::new(
+            ".".into(),
+            types::DNS_TYPE_OPT,
+            512,
+            0,
+            0,
+            DnsResourceRecordData::OPT(Vec::new()),
+        )?;
+        dns_packet.additional = vec![
+            opt,
+            record("ns1.example.com.", address("192.0.2.1")?)?,
+            record("ns2.example.com.", address("192.0.2.2")?)?,
+        ];
+        dns_packet.update_counts();
+
+        // A packet that fits is serialized as it is.
+        let serialized = dns_packet.serialize()?;
+        assert_eq!(
+            dns_packet.serialize_with_limit(serialized.len())?,
+            serialized
+        );
+
+        // Glue that does not fit is left out without setting TC, keeping the OPT record.
+        let limited = dns_packet.serialize_with_limit(serialized.len() - 1)?;
+        assert!(limited.len() < serialized.len());
+        let parsed = DnsPacket::parse_dns_packet(&limited)?;
+        assert!(!parsed.header.tc);
+        assert_eq!(parsed.answer.len(), 3);
+        assert_eq!(parsed.additional.len(), 2);
+        assert!(parsed.opt().is_some());
+
+        // Answer RRsets are kept whole, with the records of each together, and TC set for the rest.
+        let mut expected = dns_packet.clone();
+        expected.answer = vec![ns1, ns2];
+        expected.additional.truncate(1);
+        expected.header.tc = true;
+        expected.update_counts();
+        let expected = expected.serialize()?;
+        assert_eq!(dns_packet.serialize_with_limit(expected.len())?, expected);
+        let parsed = DnsPacket::parse_dns_packet(&expected)?;
+        assert_eq!(parsed.answer.len(), 2);
+        assert!(parsed.opt().is_some());
+
+        let limited = dns_packet.serialize_with_limit(expected.len() - 1)?;
+        let parsed = DnsPacket::parse_dns_packet(&limited)?;
+        assert!(parsed.header.tc);
+        assert!(parsed.answer.is_empty());
+        assert_eq!(parsed.additional.len(), 1);
+
+        assert!(dns_packet.serialize_with_limit(DNS_HEADER_SIZE).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_follow_alias_chain() -> Result<(), String> {
+        let mut dns_packet = DnsPacket::new(&String::from("www.example.com."), types::DNS_TYPE_A)?;
+
+        dns_packet.answer.push(DnsResourceRecord::new(
+            "www.example.com.".into(),
+            types::DNS_TYPE_CNAME,
+            DNS_CLASS_IN,
+            300,
+            22,
+            DnsResourceRecordData::CNAME("host.sub.example.com.".into()),
+        )?);
+        dns_packet.answer.push(DnsResourceRecord::new(
+            "sub.example.com.".into(),
+            types::DNS_TYPE_DNAME,
+            DNS_CLASS_IN,
+            300,
+            13,
+            DnsResourceRecordData::DNAME("example.net.".into()),
+        )?);
+
+        let canonical_name = dns_packet.follow_alias_chain("www.example.com.")?;
+        assert_eq!(canonical_name, "host.example.net.");
+        assert_eq!(
+            dns_packet.alias_chain("www.example.com.")?,
+            [
+                "www.example.com.",
+                "host.sub.example.com.",
+                "host.example.net."
+            ]
+        );
+
+        let canonical_name = dns_packet.follow_alias_chain("other.example.org.")?;
+        assert_eq!(canonical_name, "other.example.org.");
+
+        Ok(())
+    }
 }
@@ -1,10 +1,24 @@
 use std::collections::HashMap;
 
+/// The maximum length of a domain name in wire format, including all length octets.
+pub const MAX_DOMAIN_NAME_LENGTH: usize = 255;
+
+/// The maximum number of compression pointers followed while parsing a single domain name.
+pub const MAX_COMPRESSION_POINTERS: usize = 64;
+
 // Should we use a seperate struct to represent domain names? Makes easier to not f up
 
 /// Parse a DNS domain name from a raw DNS packet, taking into account DNS message compression.
+///
+/// Labels may contain any octets, which are escaped in the returned presentation format name.
+/// Labels starting at start may not run past limit. Compression pointers may only point to earlier
+/// offsets, at most [MAX_COMPRESSION_POINTERS] are followed, and the whole name may not be longer than
+/// [MAX_DOMAIN_NAME_LENGTH] octets. The extended (0x40) and reserved (0x80) label types are rejected.
+///
+/// Returns the domain name and the offset just past it at its original position, that is
+/// after the terminating root label or after the first compression pointer.
 pub fn parse_domain_name(
-    buf: &Vec<u8>,
+    buf: &[u8],
     start: usize,
     limit: usize,
 ) -> Result<(String, usize), String> {
@@ -16,168 +30,478 @@ pub fn parse_domain_name(
 
     let mut domain_name = String::new();
     let mut curr = start;
+    // Labels must never run past end, which after a pointer is the position of that pointer.
+    let mut end = limit.min(buf.len());
+    // The offset just past the name at its original position, known once a pointer is followed.
+    let mut original_end = None;
+    let mut pointers_followed = 0;
+    let mut name_length = 1; // the terminating root label
+
+    loop {
+        if curr >= end {
+            return Err(format!(
+                "domain name at offset {} has no terminating root label before offset {}",
+                start, end
+            ));
+        }
 
-    while (curr as usize) < buf.len() {
         let label_length = buf[curr] as usize;
 
-        if label_length == 0
-        //null label = root zone
-        {
-            curr = curr + 1;
-            break;
-        } else if (label_length & 0xC0usize) == 0xC0usize
-        //message compression pointer
-        {
-            if curr + 1 >= buf.len() {
-                return Err("domain name pointer appears at end of buf".into());
+        match label_length & 0xC0 {
+            0x00 if label_length == 0 => {
+                //null label = root zone
+                curr += 1;
+                break;
             }
+            0x00 => {
+                if curr + label_length >= end {
+                    return Err(format!(
+                        "domain name label length {} at offset {} runs past offset {}",
+                        label_length, curr, end
+                    ));
+                }
+
+                name_length += label_length + 1;
+                if name_length > MAX_DOMAIN_NAME_LENGTH {
+                    return Err(format!(
+                        "domain name at offset {} is longer than {} octets",
+                        start, MAX_DOMAIN_NAME_LENGTH
+                    ));
+                }
 
-            let offset = (label_length & 0x3Fusize) << 8 | buf[curr + 1] as usize & 0xFFusize;
-            let res = parse_domain_name(buf, offset, curr);
+                domain_name.push_str(&escape_label(&buf[curr + 1..curr + label_length + 1]));
+                domain_name.push('.');
 
-            match res {
-                Ok((domain_name_suffix, _)) => {
-                    domain_name.push_str(domain_name_suffix.as_str());
+                curr += label_length + 1;
+            }
+            0xC0 => {
+                //message compression pointer
+                if curr + 1 >= end {
+                    return Err("domain name pointer appears at end of buf".into());
                 }
-                Err(error) => {
-                    return Err(format!("error parsing domain name pointer: {}", error).into());
+
+                let offset = (label_length & 0x3F) << 8 | buf[curr + 1] as usize;
+                if offset >= curr {
+                    return Err(format!(
+                        "domain name pointer at offset {} points forward to offset {}",
+                        curr, offset
+                    ));
                 }
-            };
 
-            curr += 2;
+                pointers_followed += 1;
+                if pointers_followed > MAX_COMPRESSION_POINTERS {
+                    return Err(format!(
+                        "domain name at offset {} follows more than {} pointers",
+                        start, MAX_COMPRESSION_POINTERS
+                    ));
+                }
 
-            break;
-        } else if (label_length + curr) >= buf.len() {
-            return Err("domain name label length octet too large".into());
-        } else {
-            let mut label = String::new();
-            for ch in buf[curr + 1..curr + label_length + 1].iter() {
-                label.push(*ch as char);
-            }
-            domain_name.push_str(label.as_str());
-            domain_name.push('.');
+                if original_end.is_none() {
+                    original_end = Some(curr + 2);
+                }
 
-            curr += label_length + 1;
+                end = curr;
+                curr = offset;
+            }
+            label_type => {
+                return Err(format!(
+                    "domain name label at offset {} has unsupported label type 0x{:02X}",
+                    curr, label_type
+                ));
+            }
         }
     }
 
+    let curr = original_end.unwrap_or(curr);
+
     if domain_name.is_empty() {
         domain_name.push('.');
     }
 
-    if !is_domain_name_valid(&domain_name) {
+    Ok((domain_name, curr))
+}
+
+/// The rules the labels of a domain name must follow for it to be considered valid.
+///
+/// Every policy also requires the name to be absolute, with labels of 1 to [MAX_LABEL_LENGTH] octets
+/// and no more than [MAX_DOMAIN_NAME_LENGTH] octets in total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainNamePolicy {
+    /// Labels may contain any octets, as allowed by RFC 2181 section 11. Used for names received on the wire.
+    AnyOctet,
+    /// Host names per RFC 1123 section 2.1, labels of letters, digits and interior hyphens.
+    Hostname,
+    /// Host names which may also contain service labels starting with an underscore, such as `_sip._tcp` (RFC 8552).
+    Service,
+    /// Service names whose leftmost label may also be the wildcard label `*` (RFC 4592).
+    Wildcard,
+}
+
+/// The maximum length of a single label in octets, excluding its length octet.
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+/// Returns true if domain_name is a valid DNS domain name in presentation format, allowing any octets in its labels.
+pub fn is_domain_name_valid(domain_name: &str) -> bool {
+    is_domain_name_valid_for(domain_name, DomainNamePolicy::AnyOctet)
+}
+
+/// Returns true if domain_name is a valid domain name in presentation format under policy.
+pub fn is_domain_name_valid_for(domain_name: &str, policy: DomainNamePolicy) -> bool {
+    let labels = match domain_name_to_labels(domain_name) {
+        Ok(labels) => labels,
+        Err(_) => return false,
+    };
+
+    match policy {
+        DomainNamePolicy::AnyOctet => true,
+        DomainNamePolicy::Hostname => labels.iter().all(|label| is_hostname_label(label)),
+        DomainNamePolicy::Service => labels
+            .iter()
+            .all(|label| is_hostname_label(label) || is_service_label(label)),
+        DomainNamePolicy::Wildcard => labels.iter().enumerate().all(|(index, label)| {
+            (index == 0 && label.as_slice() == b"*")
+                || is_hostname_label(label)
+                || is_service_label(label)
+        }),
+    }
+}
+
+/// A label of letters, digits and hyphens, not starting or ending with a hyphen. Unlike RFC 952,
+/// RFC 1123 allows a label to start with a digit.
+fn is_hostname_label(label: &[u8]) -> bool {
+    !label.is_empty()
+        && label
+            .iter()
+            .all(|&ch| ch.is_ascii_alphanumeric() || ch == b'-')
+        && !label.starts_with(b"-")
+        && !label.ends_with(b"-")
+}
+
+/// An underscore followed by a host name label, such as `_tcp` or `_acme-challenge`.
+fn is_service_label(label: &[u8]) -> bool {
+    label.starts_with(b"_") && is_hostname_label(&label[1..])
+}
+
+/// Split a domain name in presentation format into its labels, resolving RFC 1035 section 5.1
+/// escapes (`\.`, `\\`, `\DDD`).
+///
+/// Returns the labels and whether the name is absolute, that is ends with an unescaped '.'.
+/// Whitespace, control characters and non-ASCII characters must be escaped.
+pub fn split_domain_name(domain_name: &str) -> Result<(Vec<Vec<u8>>, bool), String> {
+    if domain_name.is_empty() {
+        return Err("domain name is empty".into());
+    } else if domain_name == "." {
+        return Ok((Vec::new(), true));
+    }
+
+    let mut labels = Vec::new();
+    let mut label = Vec::new();
+    let mut absolute = false;
+    let mut chars = domain_name.chars();
+
+    while let Some(ch) = chars.next() {
+        absolute = false;
+
+        match ch {
+            '.' => {
+                if label.is_empty() {
+                    return Err(format!("domain name {} has an empty label", domain_name));
+                }
+                labels.push(std::mem::take(&mut label));
+                absolute = true;
+            }
+            '\\' => match chars.next() {
+                Some(digit) if digit.is_ascii_digit() => {
+                    let digits: String = std::iter::once(digit)
+                        .chain(chars.by_ref().take(2))
+                        .collect();
+                    let value = match digits.parse::<u16>() {
+                        Ok(value) if digits.len() == 3 && value <= 0xFF => value,
+                        _ => {
+                            return Err(format!(
+                                "domain name {} has an invalid \\DDD escape",
+                                domain_name
+                            ))
+                        }
+                    };
+                    label.push(value as u8);
+                }
+                Some(escaped) if escaped.is_ascii_graphic() || escaped == ' ' => {
+                    label.push(escaped as u8)
+                }
+                _ => return Err(format!("domain name {} has an invalid escape", domain_name)),
+            },
+            ch if ch.is_ascii_graphic() => label.push(ch as u8),
+            ch => {
+                return Err(format!(
+                    "domain name {} has unescaped character {:?}",
+                    domain_name, ch
+                ))
+            }
+        }
+
+        if label.len() > MAX_LABEL_LENGTH {
+            return Err(format!(
+                "domain name {} has a label longer than {} octets",
+                domain_name, MAX_LABEL_LENGTH
+            ));
+        }
+    }
+
+    if !label.is_empty() {
+        labels.push(label);
+    }
+
+    let wire_length: usize = labels.iter().map(|label| label.len() + 1).sum::<usize>() + 1;
+    if wire_length > MAX_DOMAIN_NAME_LENGTH {
         return Err(format!(
-            "parse_domain_name() invalid domain name: {}",
-            domain_name
+            "domain name {} is longer than {} octets",
+            domain_name, MAX_DOMAIN_NAME_LENGTH
         ));
     }
 
-    Ok((domain_name, curr))
+    Ok((labels, absolute))
+}
+
+/// Split an absolute domain name in presentation format into its labels, without the root label.
+pub fn domain_name_to_labels(domain_name: &str) -> Result<Vec<Vec<u8>>, String> {
+    match split_domain_name(domain_name)? {
+        (labels, true) => Ok(labels),
+        (_, false) => Err(format!("domain name {} is not absolute", domain_name)),
+    }
 }
 
-/// Returns true if domain_name represents a valid DNS domain name.
-pub fn is_domain_name_valid(domain_name: &String) -> bool {
-    if domain_name == "." {
-        return true;
-    } else if domain_name.is_empty() || domain_name.starts_with(".") {
-        return false;
-    }
-
-    /*
-     IDNA was proposed in RFC 3490, but it only applies to application code. We are not that.
-     We won't handle punycode, but instead just ensure that the domain names are valid per
-     RFC 1035.
-
-     Each label must:
-      1. start with a letter
-      2. End with a letter or digit
-      3. Have as interior characters only letters, digits, and hyphen.
-      4. Must be 63 characters or less. (This means first two bits of all labels are always 0).
-    */
-    for label in domain_name.split_terminator('.') {
-        if label.len() > 63
-            || !label.starts_with(|c: char| c.is_ascii_alphabetic())
-            || !label.ends_with(|c: char| c.is_ascii_alphanumeric())
-            || label.contains(|c: char| c != '-' && !c.is_ascii_alphanumeric())
-        {
-            return false;
+/// Join labels into an absolute domain name in presentation format, escaping them as needed.
+pub fn labels_to_domain_name<L: AsRef<[u8]>>(labels: &[L]) -> String {
+    if labels.is_empty() {
+        return String::from(".");
+    }
+
+    let mut domain_name = String::new();
+    for label in labels {
+        domain_name.push_str(&escape_label(label.as_ref()));
+        domain_name.push('.');
+    }
+
+    domain_name
+}
+
+/// Escape a label for the presentation format. Characters special to master files are escaped
+/// with a backslash, and any other non-printable octet as `\DDD`.
+pub fn escape_label(label: &[u8]) -> String {
+    let mut escaped = String::with_capacity(label.len());
+
+    for &ch in label {
+        match ch {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                escaped.push('\\');
+                escaped.push(ch as char);
+            }
+            0x21..=0x7E => escaped.push(ch as char),
+            _ => escaped.push_str(&format!("\\{:03}", ch)),
         }
     }
 
-    domain_name.ends_with('.')
+    escaped
+}
+
+/// Returns true if domain_name ends with an unescaped '.', that is it is absolute.
+fn ends_with_root(domain_name: &str) -> bool {
+    match domain_name.strip_suffix('.') {
+        Some(rest) => {
+            let backslashes = rest.chars().rev().take_while(|&ch| ch == '\\').count();
+            backslashes.is_multiple_of(2)
+        }
+        None => false,
+    }
 }
 
-/// Will attempt to massage a given domain name into a valid one by removing leading '.' and append '.' to end
-// TODO Should this be extended to be more aggressive/convert to IDNA?
-// Should this remove whitespace at all? Maybe just dns specific things like dots
+/// Will attempt to massage a given domain name into a valid one by removing leading '.' and append '.' to end.
+/// Unicode names are converted separately, see [crate::idna::domain_name_to_ascii].
+// TODO Should this remove whitespace at all? Maybe just dns specific things like dots
 pub fn normalize_domain_name(domain_name: &String) -> String {
     if domain_name.is_empty() || domain_name == "." {
         return domain_name.clone();
     }
 
-    let mut domain_name: String = domain_name
-        .strip_prefix(".")
-        .or(Some(domain_name))
-        .unwrap()
-        .into();
+    let mut domain_name: String = domain_name.strip_prefix('.').unwrap_or(domain_name).into();
 
-    if !domain_name.ends_with('.') {
+    if !ends_with_root(&domain_name) {
         domain_name.push('.');
     }
 
     domain_name
 }
 
-/// Serialize domain_name into a DNS protocol conformant, network ready buffer, using message compression.
-pub fn serialize_domain_name(
-    domain_name: &String,
-    buf: &mut Vec<u8>,
-    domain_name_offsets: &mut HashMap<String, u16>,
-) -> Result<(), String> {
-    if !is_domain_name_valid(domain_name) {
-        return Err(format!(
-            "serialize_domain_name() invalid domain name: {}",
-            domain_name
-        ));
+/// Make domain_name absolute by appending origin to it, unless it already ends with an unescaped '.'.
+/// "@" stands for origin itself, as in master files.
+pub fn absolute_domain_name(domain_name: &str, origin: &str) -> Result<String, String> {
+    let absolute = if domain_name == "@" {
+        String::from(origin)
+    } else if ends_with_root(domain_name) {
+        String::from(domain_name)
+    } else if origin == "." {
+        format!("{}.", domain_name)
+    } else {
+        format!("{}.{}", domain_name, origin)
+    };
+
+    domain_name_to_labels(&absolute)?;
+
+    Ok(absolute)
+}
+
+/// The inverse of [absolute_domain_name], writing domain_name relative to origin if it is below
+/// it, "@" if it is origin itself, and otherwise absolute. The labels of origin must match exactly,
+/// including case, so that making the result absolute again gives back the same labels.
+pub fn relative_domain_name(domain_name: &str, origin: &str) -> Result<String, String> {
+    let labels = domain_name_to_labels(domain_name)?;
+    let origin_labels = domain_name_to_labels(origin)?;
+
+    if labels.len() < origin_labels.len()
+        || labels[labels.len() - origin_labels.len()..] != origin_labels[..]
+    {
+        return Ok(labels_to_domain_name(&labels));
+    } else if labels.len() == origin_labels.len() {
+        return Ok(String::from("@"));
     }
-    let mut subdomain = &domain_name[0..domain_name.len()];
 
-    loop {
-        if subdomain.is_empty() || subdomain == "." {
-            buf.push(0);
-            break;
+    let relative = labels_to_domain_name(&labels[..labels.len() - origin_labels.len()]);
+    Ok(String::from(&relative[..relative.len() - 1]))
+}
+
+/// Returns true if the two domain names are equal, ignoring ASCII case.
+pub fn domain_names_equal(lhs: &str, rhs: &str) -> bool {
+    match (domain_name_to_labels(lhs), domain_name_to_labels(rhs)) {
+        (Ok(lhs), Ok(rhs)) => {
+            lhs.len() == rhs.len()
+                && lhs
+                    .iter()
+                    .zip(rhs.iter())
+                    .all(|(lhs, rhs)| lhs.eq_ignore_ascii_case(rhs))
         }
+        _ => lhs.eq_ignore_ascii_case(rhs),
+    }
+}
 
-        match domain_name_offsets.get(subdomain) {
-            Some(offset) => {
-                buf.push(((offset >> 8) & 0x3F) as u8 | 0xC0);
-                buf.push((offset & 0xFF) as u8);
-                break;
+/// Returns true if the two domain names are the same octet for octet, including the case of ASCII letters.
+pub fn domain_names_identical(lhs: &str, rhs: &str) -> bool {
+    match (domain_name_to_labels(lhs), domain_name_to_labels(rhs)) {
+        (Ok(lhs), Ok(rhs)) => lhs == rhs,
+        _ => lhs == rhs,
+    }
+}
+
+/// Randomly change the case of each ASCII letter in domain_name, as done by DNS 0x20
+/// (draft-vixie-dnsext-dns0x20) to add entropy to queries.
+pub fn randomize_case(domain_name: &str) -> String {
+    domain_name
+        .chars()
+        .map(|ch| {
+            if !ch.is_ascii_alphabetic() {
+                ch
+            } else if rand::random() {
+                ch.to_ascii_uppercase()
+            } else {
+                ch.to_ascii_lowercase()
             }
-            None => {
-                let label = &subdomain[0..subdomain.find('.').ok_or("subdomain had no \'.\'")?];
+        })
+        .collect()
+}
 
-                // Max offset is 0x3FFF, since the two high order bits are always set.
-                // If we go past the possible offset value, no point in storing pointer.
-                if (buf.len()) <= 0x3FFFusize {
-                    domain_name_offsets.insert(subdomain.into(), buf.len() as u16);
-                }
+/// Returns true if domain_name is equal to or below ancestor in the domain name space, ignoring ASCII case.
+pub fn is_subdomain_of(domain_name: &str, ancestor: &str) -> bool {
+    let (labels, ancestor_labels) = match (
+        domain_name_to_labels(domain_name),
+        domain_name_to_labels(ancestor),
+    ) {
+        (Ok(labels), Ok(ancestor_labels)) => (labels, ancestor_labels),
+        _ => return false,
+    };
+
+    labels.len() >= ancestor_labels.len()
+        && labels
+            .iter()
+            .rev()
+            .zip(ancestor_labels.iter().rev())
+            .all(|(label, ancestor_label)| label.eq_ignore_ascii_case(ancestor_label))
+}
 
-                buf.push(label.len() as u8);
+/// The domain name PTR records for address are found at, below in-addr.arpa. for IPv4 (RFC 1035
+/// section 3.5) and ip6.arpa. for IPv6 (RFC 3596 section 2.5).
+pub fn reverse_domain_name(address: &std::net::IpAddr) -> String {
+    match address {
+        std::net::IpAddr::V4(address) => {
+            let [a, b, c, d] = address.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa.", d, c, b, a)
+        }
+        std::net::IpAddr::V6(address) => {
+            let mut domain_name = String::new();
+            for octet in address.octets().iter().rev() {
+                domain_name.push_str(&format!("{:x}.{:x}.", octet & 0xF, octet >> 4));
+            }
+            domain_name.push_str("ip6.arpa.");
+            domain_name
+        }
+    }
+}
 
-                for ch in label.chars() {
-                    buf.push(ch as u8);
-                }
+/// Returns the length in octets domain_name occupies in wire format without message compression.
+pub fn domain_name_wire_length(domain_name: &str) -> usize {
+    match split_domain_name(domain_name) {
+        Ok((labels, _)) => labels.iter().map(|label| label.len() + 1).sum::<usize>() + 1,
+        Err(_) => domain_name.len() + 1,
+    }
+}
 
-                subdomain = &subdomain
-                    [subdomain.find('.').ok_or("subdomain had no \'.\'")? + 1..subdomain.len()];
-            }
+/// Serialize domain_name into a DNS protocol conformant, network ready buffer, using message compression.
+pub fn serialize_domain_name(
+    domain_name: &str,
+    buf: &mut Vec<u8>,
+    domain_name_offsets: &mut HashMap<String, u16>,
+) -> Result<(), String> {
+    let labels = domain_name_to_labels(domain_name)
+        .map_err(|error| format!("serialize_domain_name() invalid domain name: {}", error))?;
+
+    for index in 0..labels.len() {
+        let subdomain = labels_to_domain_name(&labels[index..]);
+
+        if let Some(offset) = domain_name_offsets.get(&subdomain) {
+            buf.push(((offset >> 8) & 0x3F) as u8 | 0xC0);
+            buf.push((offset & 0xFF) as u8);
+            return Ok(());
         }
+
+        // Max offset is 0x3FFF, since the two high order bits are always set.
+        // If we go past the possible offset value, no point in storing pointer.
+        if (buf.len()) <= 0x3FFFusize {
+            domain_name_offsets.insert(subdomain, buf.len() as u16);
+        }
+
+        buf.push(labels[index].len() as u8);
+        buf.extend_from_slice(&labels[index]);
     }
 
+    buf.push(0);
+
     Ok(())
 }
 
+/// Serialize domain_name into a DNS protocol conformant, network ready buffer,
+/// only using and recording message compression offsets if compress is true.
+pub fn serialize_domain_name_with_compression(
+    domain_name: &str,
+    buf: &mut Vec<u8>,
+    domain_name_offsets: &mut HashMap<String, u16>,
+    compress: bool,
+) -> Result<(), String> {
+    if compress {
+        serialize_domain_name(domain_name, buf, domain_name_offsets)
+    } else {
+        serialize_domain_name(domain_name, buf, &mut HashMap::new())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -204,6 +528,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_malformed_domain_name() -> Result<(), String> {
+        // Pointer to itself, and pointer forward to a valid name.
+        assert!(parse_domain_name(&[0xC0, 0x00], 0, 2).is_err());
+        assert!(parse_domain_name(&[0xC0, 0x02, 0x01, 0x61, 0x00], 0, 5).is_err());
+
+        // Pointer back to a label which leads to the same pointer again.
+        let buf = [0x01, 0x61, 0xC0, 0x00];
+        assert!(parse_domain_name(&buf, 2, buf.len()).is_err());
+
+        // Extended (0x40) and reserved (0x80) label types.
+        let error = parse_domain_name(&[0x41, 0x00], 0, 2).unwrap_err();
+        assert!(error.contains("label type 0x40"), "{}", error);
+        let error = parse_domain_name(&[0x81, 0x61, 0x00], 0, 3).unwrap_err();
+        assert!(error.contains("label type 0x80"), "{}", error);
+
+        // Missing the terminating root label within limit.
+        assert!(parse_domain_name(&[0x01, 0x61, 0x00], 0, 2).is_err());
+
+        // A chain of pointers, each pointing to the previous one, ending at the root label.
+        let mut buf = vec![0x00];
+        for hop in 0..MAX_COMPRESSION_POINTERS {
+            buf.push(0xC0);
+            buf.push(if hop == 0 { 0 } else { (hop * 2 - 1) as u8 });
+        }
+        let start = buf.len() - 2;
+        let (domain_name, end) = parse_domain_name(&buf, start, buf.len())?;
+        assert_eq!(domain_name, ".");
+        assert_eq!(end, buf.len());
+
+        buf.push(0xC0);
+        buf.push(start as u8);
+        assert!(parse_domain_name(&buf, buf.len() - 2, buf.len()).is_err());
+
+        // 255 octets is the longest legal name, whether or not it is compressed.
+        let mut buf = Vec::new();
+        for _ in 0..3 {
+            buf.push(63);
+            buf.extend_from_slice(&[b'a'; 63]);
+        }
+        buf.push(61);
+        buf.extend_from_slice(&[b'a'; 61]);
+        buf.push(0);
+        assert_eq!(buf.len(), MAX_DOMAIN_NAME_LENGTH);
+        let (_, end) = parse_domain_name(&buf, 0, buf.len())?;
+        assert_eq!(end, MAX_DOMAIN_NAME_LENGTH);
+
+        let start = buf.len();
+        buf.extend_from_slice(&[0x01, 0x62, 0xC0, 0x00]);
+        assert!(parse_domain_name(&buf, start, buf.len()).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_is_domain_name_valid() -> Result<(), String> {
         let domain_name = String::from("www.google.com.");
@@ -230,11 +608,228 @@ mod tests {
         let domain_name = String::from(".");
         assert!(is_domain_name_valid(&domain_name));
 
+        let domain_name = format!("{0}.{0}.{0}.{0}.", "a".repeat(63));
+        assert!(!is_domain_name_valid(&domain_name));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_subdomain_of() -> Result<(), String> {
+        assert!(is_subdomain_of("www.example.com.", "example.com."));
+        assert!(is_subdomain_of("www.EXAMPLE.com.", "example.COM."));
+        assert!(is_subdomain_of("example.com.", "example.com."));
+        assert!(is_subdomain_of("example.com.", "."));
+        assert!(!is_subdomain_of("wwwexample.com.", "example.com."));
+        assert!(!is_subdomain_of("example.com.", "www.example.com."));
+
+        // Names are compared label by label, so non-ASCII input is rejected rather than split
+        // inside a character.
+        assert!(!is_subdomain_of("é.com.", "x.com."));
+        assert!(!is_subdomain_of("www.bücher.example.", "bücher.example."));
+
+        assert_eq!(domain_name_wire_length("."), 1);
+        assert_eq!(domain_name_wire_length("www.google.com."), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_name_policies() -> Result<(), String> {
+        use DomainNamePolicy::*;
+
+        let cases = [
+            // (domain name, AnyOctet, Hostname, Service, Wildcard)
+            ("www.example.com.", true, true, true, true),
+            ("3com.com.", true, true, true, true),
+            ("_dmarc.example.com.", true, false, true, true),
+            ("_sip._tcp.example.com.", true, false, true, true),
+            ("*.example.com.", true, false, false, true),
+            ("www.*.example.com.", true, false, false, false),
+            ("-start.example.com.", true, false, false, false),
+            ("under_score.example.com.", true, false, false, false),
+            ("dot\\.inlabel.example.com.", true, false, false, false),
+            ("\\000\\255.example.com.", true, false, false, false),
+            ("relative.example.com", false, false, false, false),
+        ];
+
+        for (domain_name, any_octet, hostname, service, wildcard) in cases.iter() {
+            assert_eq!(
+                is_domain_name_valid_for(domain_name, AnyOctet),
+                *any_octet,
+                "{}",
+                domain_name
+            );
+            assert_eq!(
+                is_domain_name_valid_for(domain_name, Hostname),
+                *hostname,
+                "{}",
+                domain_name
+            );
+            assert_eq!(
+                is_domain_name_valid_for(domain_name, Service),
+                *service,
+                "{}",
+                domain_name
+            );
+            assert_eq!(
+                is_domain_name_valid_for(domain_name, Wildcard),
+                *wildcard,
+                "{}",
+                domain_name
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_presentation_escapes() -> Result<(), String> {
+        assert_eq!(
+            domain_name_to_labels("a\\.b\\\\c\\032d\\255.example.")?,
+            vec![b"a.b\\c d\xFF".to_vec(), b"example".to_vec()]
+        );
+        assert_eq!(
+            split_domain_name("www.example")?,
+            (vec![b"www".to_vec(), b"example".to_vec()], false)
+        );
+        assert!(split_domain_name("a\\256.").is_err());
+        assert!(split_domain_name("a\\25.").is_err());
+        assert!(split_domain_name("a\\").is_err());
+        assert!(split_domain_name("caf\u{e9}.").is_err());
+        assert!(split_domain_name(&format!("{}.", "\\046".repeat(64))).is_err());
+
+        assert_eq!(
+            labels_to_domain_name(&[&b"a.b\\c d\xFF"[..], b"(x);@$", b"example"]),
+            "a\\.b\\\\c\\032d\\255.\\(x\\)\\;\\@\\$.example."
+        );
+        assert_eq!(labels_to_domain_name::<Vec<u8>>(&[]), ".");
+
+        // Labels containing dots and arbitrary octets round trip through the wire format.
+        let domain_name = String::from("dot\\.label.\\000\\1272.Example.");
+        let mut buf = Vec::new();
+        serialize_domain_name(&domain_name, &mut buf, &mut HashMap::new())?;
+        assert_eq!(buf, b"\x09dot.label\x03\x00\x7F2\x07Example\x00".to_vec());
+        assert_eq!(
+            parse_domain_name(&buf, 0, buf.len())?,
+            (domain_name.clone(), buf.len())
+        );
+
+        // Escaped and unescaped spellings of the same name are equal, and compress against each other.
+        assert!(domain_names_equal("\\065.example.", "a.EXAMPLE."));
+        assert!(is_subdomain_of(
+            "www.dot\\.label.example.",
+            "DOT\\.label.example."
+        ));
+        assert!(!is_subdomain_of(
+            "www.dot\\.label.example.",
+            "label.example."
+        ));
+        assert_eq!(
+            domain_name_wire_length("dot\\.label.\\000\\1272.Example."),
+            buf.len()
+        );
+
+        let mut offsets = HashMap::new();
+        let mut buf = Vec::new();
+        serialize_domain_name(&String::from("A.example."), &mut buf, &mut offsets)?;
+        serialize_domain_name(&String::from("\\065.example."), &mut buf, &mut offsets)?;
+        assert_eq!(&buf[11..], &[0xC0, 0x00]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_randomize_case() -> Result<(), String> {
+        let domain_name = "www.dot\\.label.\\255example.com.";
+        let randomized = randomize_case(domain_name);
+
+        assert!(domain_names_equal(&randomized, domain_name));
+        assert!(domain_names_identical(
+            &randomized.to_ascii_lowercase(),
+            domain_name
+        ));
+        assert!(domain_names_identical("\\065.example.", "A.example."));
+        assert!(!domain_names_identical("a.example.", "A.example."));
+
+        // With this many letters, leaving every one of them lowercase is vanishingly unlikely.
+        let domain_name = format!("{}.example.", "a".repeat(63));
+        assert!(!domain_names_identical(
+            &randomize_case(&domain_name),
+            &domain_name
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_absolute_domain_name() -> Result<(), String> {
+        assert_eq!(
+            absolute_domain_name("www", "example.com.")?,
+            "www.example.com."
+        );
+        assert_eq!(
+            absolute_domain_name("www.example.org.", "example.com.")?,
+            "www.example.org."
+        );
+        assert_eq!(absolute_domain_name("@", "example.com.")?, "example.com.");
+        assert_eq!(absolute_domain_name("www", ".")?, "www.");
+        assert_eq!(
+            absolute_domain_name("dot\\.", "example.com.")?,
+            "dot\\..example.com."
+        );
+        assert!(absolute_domain_name("www", "example.com").is_err());
+        assert!(absolute_domain_name("a..b", "example.com.").is_err());
+
+        assert_eq!(
+            relative_domain_name("www.example.com.", "example.com.")?,
+            "www"
+        );
+        assert_eq!(relative_domain_name("example.com.", "example.com.")?, "@");
+        assert_eq!(
+            relative_domain_name("www.Example.com.", "example.com.")?,
+            "www.Example.com."
+        );
+        assert_eq!(
+            relative_domain_name("www.example.org.", "example.com.")?,
+            "www.example.org."
+        );
+        assert_eq!(relative_domain_name("a\\.b.c.", "c.")?, "a\\.b");
+        assert_eq!(relative_domain_name("\\@.c.", "c.")?, "\\@");
+        assert_eq!(relative_domain_name("www.", ".")?, "www");
+        assert_eq!(relative_domain_name(".", ".")?, "@");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_domain_name() -> Result<(), String> {
+        let address: std::net::IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(reverse_domain_name(&address), "1.2.0.192.in-addr.arpa.");
+
+        let address: std::net::IpAddr = "2001:db8::567:89ab".parse().unwrap();
+        assert_eq!(
+            reverse_domain_name(&address),
+            "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+        );
+
         Ok(())
     }
 
     #[test]
     fn test_normalize_domain_name() -> Result<(), String> {
+        assert_eq!(
+            normalize_domain_name(&"www.example.com".into()),
+            "www.example.com."
+        );
+        assert_eq!(
+            normalize_domain_name(&".www.example.com.".into()),
+            "www.example.com."
+        );
+        assert_eq!(normalize_domain_name(&"dot\\.".into()), "dot\\..");
+        assert_eq!(normalize_domain_name(&"slash\\\\.".into()), "slash\\\\.");
+        assert_eq!(normalize_domain_name(&".".into()), ".");
+
         Ok(())
     }
 }
@@ -0,0 +1,140 @@
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode data as Base64 (RFC 4648 section 4), as used by the presentation format of key and certificate data.
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = *chunk.get(1).unwrap_or(&0) as u32;
+        let b2 = *chunk.get(2).unwrap_or(&0) as u32;
+        let triple = b0 << 16 | b1 << 8 | b2;
+
+        encoded.push(BASE64_ALPHABET[(triple >> 18) as usize & 0x3F] as char);
+        encoded.push(BASE64_ALPHABET[(triple >> 12) as usize & 0x3F] as char);
+
+        if chunk.len() > 1 {
+            encoded.push(BASE64_ALPHABET[(triple >> 6) as usize & 0x3F] as char);
+        } else {
+            encoded.push('=');
+        }
+
+        if chunk.len() > 2 {
+            encoded.push(BASE64_ALPHABET[triple as usize & 0x3F] as char);
+        } else {
+            encoded.push('=');
+        }
+    }
+
+    encoded
+}
+
+/// Decode Base64 (RFC 4648 section 4) text, ignoring any whitespace.
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    let mut accumulator: u32 = 0;
+    let mut bits = 0;
+    let mut padding = 0;
+
+    for ch in text.chars().filter(|ch| !ch.is_ascii_whitespace()) {
+        if ch == '=' {
+            padding += 1;
+            continue;
+        } else if padding > 0 {
+            return Err(format!("base64 text {} has data after padding", text));
+        }
+
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|&symbol| symbol as char == ch)
+            .ok_or(format!("invalid base64 character {:?}", ch))?;
+
+        accumulator = accumulator << 6 | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((accumulator >> bits) as u8);
+        }
+    }
+
+    if padding > 2 || bits >= 6 || (padding > 0 && bits / 2 != padding) {
+        return Err(format!(
+            "base64 text {} has invalid length or padding",
+            text
+        ));
+    }
+
+    Ok(decoded)
+}
+
+/// Encode data as uppercase hexadecimal (RFC 4648 section 8).
+pub fn hex_encode(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Decode hexadecimal text, ignoring any whitespace.
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = text
+        .chars()
+        .filter(|ch| !ch.is_ascii_whitespace())
+        .collect();
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("hex text {} has an odd number of digits", text));
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let high = pair[0]
+                .to_digit(16)
+                .ok_or(format!("invalid hex character {:?}", pair[0]))?;
+            let low = pair[1]
+                .to_digit(16)
+                .ok_or(format!("invalid hex character {:?}", pair[1]))?;
+            Ok((high << 4 | low) as u8)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() -> Result<(), String> {
+        // Test vectors from RFC 4648 section 10.
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+
+        for (data, encoded) in vectors.iter() {
+            assert_eq!(base64_encode(data.as_bytes()), *encoded);
+            assert_eq!(base64_decode(encoded)?, data.as_bytes());
+        }
+
+        assert_eq!(base64_decode("Zm9v\n YmFy")?, b"foobar");
+        assert!(base64_decode("Zm9v!").is_err());
+        assert!(base64_decode("Zg=").is_err());
+        assert!(base64_decode("Zg==Zg==").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hex() -> Result<(), String> {
+        assert_eq!(hex_encode(&[0x00, 0xAB, 0x7F]), "00AB7F");
+        assert_eq!(hex_decode("00ab 7F")?, vec![0x00, 0xAB, 0x7F]);
+        assert!(hex_decode("ABC").is_err());
+        assert!(hex_decode("GG").is_err());
+
+        Ok(())
+    }
+}
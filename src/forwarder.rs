@@ -0,0 +1,228 @@
+use crate::{
+    cache::*,
+    dns_packet::{dns_question::*, dns_resource_record::*, *},
+    opcodes::*,
+    rcodes::*,
+    response_matches_query, send_dns_query_with_options,
+    server::*,
+    types::*,
+    QueryOptions, MAX_ALIAS_CHAIN_LENGTH,
+};
+use rand::random;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The TTL given to expired answers served stale, as recommended by RFC 8767 section 4.
+pub const STALE_ANSWER_TTL: u32 = 30;
+
+/// How long expired answers are kept to be served stale by default, one day (RFC 8767 section 5).
+pub const DEFAULT_MAX_STALE: u32 = 86400;
+
+/// The configuration of a [Forwarder].
+#[derive(Debug, Clone)]
+pub struct ForwarderConfig {
+    /// The recursive name servers queries are forwarded to, tried in turn.
+    pub upstreams: Vec<SocketAddr>,
+    /// The options queries are forwarded with.
+    pub options: QueryOptions,
+    /// The limits of the cache. Expired answers are served stale for up to max_stale seconds when no
+    /// upstream answers, and never if it is 0.
+    pub cache: CacheConfig,
+}
+
+impl ForwarderConfig {
+    /// A configuration forwarding to upstreams with the default options, serving stale answers for up
+    /// to [DEFAULT_MAX_STALE] seconds.
+    pub fn new(upstreams: Vec<SocketAddr>) -> ForwarderConfig {
+        ForwarderConfig {
+            upstreams,
+            options: QueryOptions::default(),
+            cache: CacheConfig {
+                max_stale: DEFAULT_MAX_STALE,
+                ..CacheConfig::default()
+            },
+        }
+    }
+}
+
+/// A caching forwarder, answering queries from its cache or by forwarding them to upstream recursive
+/// name servers. When no upstream answers it serves expired answers from its cache, or SERVFAIL.
+#[derive(Debug)]
+pub struct Forwarder {
+    config: ForwarderConfig,
+    cache: Cache,
+    next_upstream: AtomicUsize,
+}
+
+impl Forwarder {
+    /// Create a forwarder with config, which must have at least one upstream.
+    pub fn new(config: ForwarderConfig) -> Result<Forwarder, String> {
+        if config.upstreams.is_empty() {
+            return Err("forwarder has no upstream name servers".into());
+        }
+
+        Ok(Forwarder {
+            cache: Cache::new(config.cache.clone()),
+            config,
+            next_upstream: AtomicUsize::new(0),
+        })
+    }
+
+    /// The configuration of the forwarder.
+    pub fn config(&self) -> &ForwarderConfig {
+        &self.config
+    }
+
+    /// The cache of upstream answers.
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    /// Serve queries on UDP and TCP at address until the returned handle is shut down, see
+    /// [spawn_server].
+    pub fn spawn(self, address: &str) -> Result<ServerHandle, String> {
+        spawn_server(self, address)
+    }
+
+    /// Forward query to the upstreams in turn under a new ID, starting with a different one each time
+    /// to spread the load. Upstreams answering SERVFAIL or REFUSED are passed over.
+    fn forward(&self, query: &DnsPacket) -> Result<DnsPacket, String> {
+        let mut upstream_query = query.clone();
+        upstream_query.header.id = random();
+
+        let upstreams = &self.config.upstreams;
+        let first = self.next_upstream.fetch_add(1, Ordering::Relaxed);
+        let mut result = Err(String::from("no upstream answered"));
+        for index in 0..upstreams.len() {
+            let upstream = upstreams[(first + index) % upstreams.len()].to_string();
+            match send_dns_query_with_options(&upstream_query, &upstream, &self.config.options) {
+                Ok(response) if !response_matches_query(&upstream_query, &response, false) => {
+                    result = Err(format!("{} answered a different query", upstream));
+                }
+                Ok(response)
+                    if !matches!(
+                        response.header.rcode,
+                        DNS_RCODE_SERVER_ERROR | DNS_RCODE_REFUSED
+                    ) =>
+                {
+                    return Ok(response);
+                }
+                Ok(response) => {
+                    result = Err(format!(
+                        "{} answered with RCODE={}",
+                        upstream, response.header.rcode
+                    ))
+                }
+                Err(error) => result = Err(format!("{}: {}", upstream, error)),
+            }
+        }
+
+        result
+    }
+
+    /// Fill in response with the answer to question from the cache, following cached CNAME records.
+    /// Returns false if the cache cannot answer. Stale answers are used if stale is true.
+    fn answer_from_cache(
+        &self,
+        question: &DnsQuestion,
+        response: &mut DnsPacket,
+        stale: bool,
+    ) -> bool {
+        let get = |name: &str, rrtype: u16| {
+            if stale {
+                self.cache.get_stale(name, rrtype, question.qclass)
+            } else {
+                self.cache.get(name, rrtype, question.qclass)
+            }
+        };
+
+        let mut answer = Vec::new();
+        let mut name = question.qname.clone();
+        for _ in 0..MAX_ALIAS_CHAIN_LENGTH {
+            let (rcode, authority) = match get(&name, question.qtype) {
+                Some(CachedAnswer::Records(records)) => {
+                    answer.extend(records);
+                    (DNS_RCODE_NO_ERROR, Vec::new())
+                }
+                Some(CachedAnswer::NameError(soa)) => (DNS_RCODE_NAME_ERROR, vec![soa]),
+                Some(CachedAnswer::NoData(soa)) => (DNS_RCODE_NO_ERROR, vec![soa]),
+                None => {
+                    let cname = match get(&name, DNS_TYPE_CNAME) {
+                        Some(CachedAnswer::Records(records))
+                            if question.qtype != DNS_TYPE_CNAME =>
+                        {
+                            records
+                        }
+                        _ => return false,
+                    };
+                    name = match cname.first().map(|record| &record.rdata) {
+                        Some(DnsResourceRecordData::CNAME(target)) => target.clone(),
+                        _ => return false,
+                    };
+                    answer.extend(cname);
+                    continue;
+                }
+            };
+
+            response.header.rcode = rcode;
+            response.answer = answer;
+            response.authority = authority;
+            for record in response
+                .answer
+                .iter_mut()
+                .chain(response.authority.iter_mut())
+            {
+                if record.ttl == 0 && stale {
+                    record.ttl = STALE_ANSWER_TTL;
+                }
+            }
+            response.update_counts();
+            return true;
+        }
+
+        false
+    }
+}
+
+impl QueryHandler for Forwarder {
+    /// Queries are answered from the cache if possible, and otherwise forwarded with the upstream
+    /// response returned under the ID and question of the query. Opcodes other than QUERY are NOTIMP,
+    /// and queries without exactly one question are FORMERR.
+    fn respond(&self, query: &DnsPacket) -> DnsPacket {
+        let mut response = start_response(query);
+        if response.extended_rcode() == DNS_RCODE_BADVERS as u16 {
+            return response;
+        }
+        response.header.ra = true;
+
+        let question = match (query.header.opcode, query.question.as_slice()) {
+            (DNS_OPCODE_QUERY, [question]) => question,
+            (opcode, _) => {
+                response.header.rcode = if opcode == DNS_OPCODE_QUERY {
+                    DNS_RCODE_FORMAT_ERROR
+                } else {
+                    DNS_RCODE_NOT_IMPLEMENTED
+                };
+                return response;
+            }
+        };
+
+        if self.answer_from_cache(question, &mut response, false) {
+            return response;
+        }
+
+        if let Ok(mut upstream_response) = self.forward(query) {
+            let _ = self.cache.insert_response(&upstream_response);
+            upstream_response.header.id = query.header.id;
+            upstream_response.question = query.question.clone();
+            return upstream_response;
+        }
+
+        if self.config.cache.max_stale == 0
+            || !self.answer_from_cache(question, &mut response, true)
+        {
+            response.header.rcode = DNS_RCODE_SERVER_ERROR;
+        }
+        response
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    dns_packet::{dns_resource_record::*, *},
+    domain_name::*,
+    idna,
+    resolver::*,
+    types::*,
+};
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::Path;
+
+/// The location of the system hosts file.
+pub const HOSTS_PATH: &str = "/etc/hosts";
+
+/// The location of the system name service switch configuration.
+pub const NSSWITCH_PATH: &str = "/etc/nsswitch.conf";
+
+/// A line of a hosts file, an address and the names it has.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostsEntry {
+    /// The IPv4 or IPv6 address.
+    pub address: IpAddr,
+    /// The first name given for the address, as an absolute domain name.
+    pub canonical_name: String,
+    /// The other names given for the address, as absolute domain names.
+    pub aliases: Vec<String>,
+}
+
+impl HostsEntry {
+    /// The canonical name followed by the aliases.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.canonical_name).chain(self.aliases.iter())
+    }
+}
+
+/// The static table of addresses and names in a hosts file, see hosts(5).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hosts {
+    entries: Vec<HostsEntry>,
+}
+
+impl Hosts {
+    /// Parse a hosts file. Each line holds an address followed by its canonical name and any aliases,
+    /// separated by whitespace, and '#' starts a comment running to the end of the line. As in glibc,
+    /// lines with an invalid address or canonical name are skipped, as are invalid aliases. The zone
+    /// ID of an IPv6 address, as in "fe80::1%lo0", is dropped.
+    pub fn parse(text: &str) -> Hosts {
+        let mut entries = Vec::new();
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let address = match fields.next().and_then(parse_address) {
+                Some(address) => address,
+                None => continue,
+            };
+            let canonical_name = match fields.next().and_then(parse_name) {
+                Some(canonical_name) => canonical_name,
+                None => continue,
+            };
+
+            entries.push(HostsEntry {
+                address,
+                canonical_name,
+                aliases: fields.filter_map(parse_name).collect(),
+            });
+        }
+
+        Hosts { entries }
+    }
+
+    /// Read a hosts file.
+    pub fn from_file(path: &Path) -> Result<Hosts, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+
+        Ok(Hosts::parse(&text))
+    }
+
+    /// Read the system hosts file from [HOSTS_PATH].
+    pub fn system() -> Result<Hosts, String> {
+        Hosts::from_file(Path::new(HOSTS_PATH))
+    }
+
+    /// The entries, in the order of the file.
+    pub fn entries(&self) -> &[HostsEntry] {
+        &self.entries
+    }
+
+    /// The addresses of every entry naming domain_name, ignoring ASCII case.
+    pub fn addresses(&self, domain_name: &str) -> Vec<IpAddr> {
+        let domain_name = normalize_domain_name(&String::from(domain_name));
+        let mut addresses: Vec<IpAddr> = Vec::new();
+
+        for entry in self.entries.iter() {
+            if entry
+                .names()
+                .any(|name| domain_names_equal(name, &domain_name))
+                && !addresses.contains(&entry.address)
+            {
+                addresses.push(entry.address);
+            }
+        }
+
+        addresses
+    }
+
+    /// The names of every entry for address, each canonical name followed by its aliases.
+    pub fn names(&self, address: &IpAddr) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.address == *address)
+        {
+            for name in entry.names() {
+                if !names.iter().any(|known| domain_names_equal(known, name)) {
+                    names.push(name.clone());
+                }
+            }
+        }
+
+        names
+    }
+}
+
+/// A source of host names and addresses, as named on the hosts line of nsswitch.conf(5).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostsSource {
+    /// The hosts file.
+    Files,
+    /// The DNS, through a stub resolver.
+    Dns,
+}
+
+/// The order sources are consulted in without configuration, "hosts: files dns".
+pub const DEFAULT_HOSTS_ORDER: [HostsSource; 2] = [HostsSource::Files, HostsSource::Dns];
+
+/// Parse the order of the sources for host lookups from the hosts line of an nsswitch.conf file.
+/// Unknown sources and actions such as "[NOTFOUND=return]" are skipped. Without a hosts line the order
+/// is [DEFAULT_HOSTS_ORDER].
+pub fn parse_hosts_order(nsswitch: &str) -> Vec<HostsSource> {
+    let line = nsswitch.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or("").trim();
+        line.strip_prefix("hosts:")
+    });
+    let line = match line {
+        Some(line) => line,
+        None => return DEFAULT_HOSTS_ORDER.to_vec(),
+    };
+
+    let mut order = Vec::new();
+    for source in line.split_whitespace() {
+        let source = match source {
+            "files" => HostsSource::Files,
+            "dns" => HostsSource::Dns,
+            _ => continue,
+        };
+        if !order.contains(&source) {
+            order.push(source);
+        }
+    }
+
+    order
+}
+
+/// Looks up hosts like the system resolver, consulting a hosts file and the DNS in a configured order
+/// and stopping at the first source that knows the name or address.
+#[derive(Debug)]
+pub struct HostResolver {
+    hosts: Hosts,
+    resolver: Resolver,
+    order: Vec<HostsSource>,
+}
+
+impl HostResolver {
+    /// Create a host resolver consulting the sources of order in turn.
+    pub fn new(hosts: Hosts, resolver: Resolver, order: Vec<HostsSource>) -> HostResolver {
+        HostResolver {
+            hosts,
+            resolver,
+            order,
+        }
+    }
+
+    /// Create a host resolver from the system configuration: [HOSTS_PATH], [RESOLV_CONF_PATH], and the
+    /// order of [NSSWITCH_PATH]. A missing nsswitch.conf gives [DEFAULT_HOSTS_ORDER].
+    pub fn system() -> Result<HostResolver, String> {
+        let order = match std::fs::read_to_string(NSSWITCH_PATH) {
+            Ok(nsswitch) => parse_hosts_order(&nsswitch),
+            Err(_) => DEFAULT_HOSTS_ORDER.to_vec(),
+        };
+
+        Ok(HostResolver::new(
+            Hosts::system()?,
+            Resolver::system()?,
+            order,
+        ))
+    }
+
+    /// The hosts file consulted.
+    pub fn hosts(&self) -> &Hosts {
+        &self.hosts
+    }
+
+    /// The stub resolver consulted.
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    /// The order the sources are consulted in.
+    pub fn order(&self) -> &[HostsSource] {
+        &self.order
+    }
+
+    /// The IPv4 and IPv6 addresses of domain_name, from the first source that has any. Addresses from
+    /// the DNS are the A then AAAA records at the end of any CNAME chain.
+    pub fn lookup_host(&self, domain_name: &str) -> Result<Vec<IpAddr>, String> {
+        let mut error = format!("{} has no addresses", domain_name);
+
+        for source in self.order.iter() {
+            let addresses = match source {
+                HostsSource::Files => {
+                    let domain_name = idna::domain_name_to_ascii(domain_name)?;
+                    self.hosts.addresses(&domain_name)
+                }
+                HostsSource::Dns => match self.dns_addresses(domain_name) {
+                    Ok(addresses) => addresses,
+                    Err(dns_error) => {
+                        error = dns_error;
+                        continue;
+                    }
+                },
+            };
+
+            if !addresses.is_empty() {
+                return Ok(addresses);
+            }
+        }
+
+        Err(error)
+    }
+
+    /// The names of address, from the first source that has any. Names from the DNS are the targets of
+    /// the PTR records at the [reverse_domain_name] of address.
+    pub fn lookup_address(&self, address: &IpAddr) -> Result<Vec<String>, String> {
+        let mut error = format!("{} has no names", address);
+
+        for source in self.order.iter() {
+            let names = match source {
+                HostsSource::Files => self.hosts.names(address),
+                HostsSource::Dns => {
+                    let reverse_name = reverse_domain_name(address);
+                    match self.resolver.query(&reverse_name, DNS_TYPE_PTR) {
+                        Ok(response) => records_at_end_of_chain(&response, &reverse_name)?
+                            .filter_map(|record| match &record.rdata {
+                                DnsResourceRecordData::PTR(name) => Some(name.clone()),
+                                _ => None,
+                            })
+                            .collect(),
+                        Err(dns_error) => {
+                            error = dns_error;
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if !names.is_empty() {
+                return Ok(names);
+            }
+        }
+
+        Err(error)
+    }
+
+    /// The A then AAAA addresses of domain_name. The addresses of one type are returned even if the
+    /// lookup of the other fails.
+    fn dns_addresses(&self, domain_name: &str) -> Result<Vec<IpAddr>, String> {
+        let mut addresses = Vec::new();
+        let mut error = None;
+
+        for rrtype in [DNS_TYPE_A, DNS_TYPE_AAAA].iter() {
+            let response = match self.resolver.lookup(domain_name, *rrtype) {
+                Ok(response) => response,
+                Err(lookup_error) => {
+                    error = Some(lookup_error);
+                    continue;
+                }
+            };
+            let qname = match response.question.first() {
+                Some(question) => question.qname.clone(),
+                None => continue,
+            };
+            for record in records_at_end_of_chain(&response, &qname)? {
+                match &record.rdata {
+                    DnsResourceRecordData::A(address) => addresses.push(IpAddr::V4(*address)),
+                    DnsResourceRecordData::AAAA(address) => addresses.push(IpAddr::V6(*address)),
+                    _ => {}
+                }
+            }
+        }
+
+        match error {
+            Some(error) if addresses.is_empty() => Err(error),
+            _ => Ok(addresses),
+        }
+    }
+}
+
+/// Parse the address of a hosts file line, dropping the zone ID of an IPv6 address.
+fn parse_address(text: &str) -> Option<IpAddr> {
+    match text.split_once('%') {
+        Some((address, _)) => address.parse::<Ipv6Addr>().ok().map(IpAddr::V6),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parse a host name of a hosts file line as an absolute domain name.
+fn parse_name(text: &str) -> Option<String> {
+    let name = normalize_domain_name(&idna::domain_name_to_ascii(text).ok()?);
+    domain_name_to_labels(&name).ok()?;
+
+    Some(name)
+}
+
+/// The answer records of response owned by the name the CNAME chain from qname ends at.
+fn records_at_end_of_chain<'a>(
+    response: &'a DnsPacket,
+    qname: &str,
+) -> Result<impl Iterator<Item = &'a DnsResourceRecord>, String> {
+    let canonical_name = response.follow_alias_chain(qname)?;
+
+    Ok(response
+        .answer
+        .iter()
+        .filter(move |record| domain_names_equal(&record.name, &canonical_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hosts() -> Result<(), String> {
+        let hosts = Hosts::parse(
+            "# static host table\n\
+             127.0.0.1\tlocalhost\n\
+             ::1 localhost ip6-localhost ip6-loopback # loopback\n\
+             \n\
+             192.0.2.10 www.example.com. www mail\n\
+             192.0.2.11 WWW.example.com\n\
+             2001:db8::10 www.example.com\n\
+             192.0.2.10 web.example.com\n",
+        );
+        assert_eq!(hosts.entries().len(), 6);
+        assert_eq!(
+            hosts.entries()[1],
+            HostsEntry {
+                address: "::1".parse().unwrap(),
+                canonical_name: "localhost.".into(),
+                aliases: vec!["ip6-localhost.".into(), "ip6-loopback.".into()],
+            }
+        );
+
+        let addresses: Vec<String> = hosts
+            .addresses("www.EXAMPLE.com")
+            .iter()
+            .map(|address| address.to_string())
+            .collect();
+        assert_eq!(addresses, ["192.0.2.10", "192.0.2.11", "2001:db8::10"]);
+        assert_eq!(hosts.addresses("localhost").len(), 2);
+        assert!(hosts.addresses("missing.example.com").is_empty());
+
+        assert_eq!(
+            hosts.names(&"192.0.2.10".parse().unwrap()),
+            ["www.example.com.", "www.", "mail.", "web.example.com."]
+        );
+        assert!(hosts.names(&"192.0.2.99".parse().unwrap()).is_empty());
+
+        // Invalid lines and aliases are skipped, and zone IDs dropped.
+        let hosts = Hosts::parse(
+            "bücher.example 192.0.2.1\n\
+             192.0.2.1 # no names\n\
+             192.0.2.2 a..example\n\
+             192.0.2.3%eth0 host\n\
+             fe80::1%lo0 localhost a..example ip6-localhost\n\
+             192.0.2.4 bücher.example\n",
+        );
+        assert_eq!(
+            hosts.entries(),
+            [
+                HostsEntry {
+                    address: "fe80::1".parse().unwrap(),
+                    canonical_name: "localhost.".into(),
+                    aliases: vec!["ip6-localhost.".into()],
+                },
+                HostsEntry {
+                    address: "192.0.2.4".parse().unwrap(),
+                    canonical_name: "xn--bcher-kva.example.".into(),
+                    aliases: Vec::new(),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_hosts_order() -> Result<(), String> {
+        let nsswitch = "passwd: files\n\
+                        # hosts: dns\n\
+                        hosts: files mdns4_minimal [NOTFOUND=return] dns files # comment\n";
+        assert_eq!(
+            parse_hosts_order(nsswitch),
+            [HostsSource::Files, HostsSource::Dns]
+        );
+        assert_eq!(parse_hosts_order("hosts: dns\n"), [HostsSource::Dns]);
+        assert_eq!(parse_hosts_order("passwd: files\n"), DEFAULT_HOSTS_ORDER);
+
+        Ok(())
+    }
+}
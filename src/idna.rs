@@ -0,0 +1,700 @@
+use crate::domain_name::*;
+
+/// The prefix marking a label as the ASCII Compatible Encoding of a Unicode label (RFC 5890).
+pub const ACE_PREFIX: &str = "xn--";
+
+// Punycode parameters, RFC 3492 section 5.
+const BASE: u32 = 36;
+const TMIN: u32 = 1;
+const TMAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 0x80;
+
+/// Bias adaptation function, RFC 3492 section 6.1.
+fn adapt(delta: u32, numpoints: u32, firsttime: bool) -> u32 {
+    let mut delta = if firsttime { delta / DAMP } else { delta / 2 };
+    delta += delta / numpoints;
+
+    let mut k = 0;
+    while delta > ((BASE - TMIN) * TMAX) / 2 {
+        delta /= BASE - TMIN;
+        k += BASE;
+    }
+
+    k + (BASE - TMIN + 1) * delta / (delta + SKEW)
+}
+
+/// The threshold t for the digit at position k, RFC 3492 section 6.2.
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        TMIN
+    } else if k >= bias + TMAX {
+        TMAX
+    } else {
+        k - bias
+    }
+}
+
+fn encode_digit(digit: u32) -> char {
+    match digit {
+        0..=25 => (b'a' + digit as u8) as char,
+        _ => (b'0' + (digit - 26) as u8) as char,
+    }
+}
+
+fn decode_digit(ch: char) -> Option<u32> {
+    match ch {
+        'a'..='z' => Some(ch as u32 - 'a' as u32),
+        'A'..='Z' => Some(ch as u32 - 'A' as u32),
+        '0'..='9' => Some(ch as u32 - '0' as u32 + 26),
+        _ => None,
+    }
+}
+
+/// Encode a Unicode label with Punycode (RFC 3492), without the ACE prefix.
+pub fn punycode_encode(label: &str) -> Result<String, String> {
+    let overflow = || format!("punycode encoding of {} overflows", label);
+    let code_points: Vec<u32> = label.chars().map(|ch| ch as u32).collect();
+
+    let mut output: String = label.chars().filter(|ch| ch.is_ascii()).collect();
+    let basic_length = output.len() as u32;
+    let mut handled = basic_length;
+    if basic_length > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+
+    while (handled as usize) < code_points.len() {
+        let m = code_points
+            .iter()
+            .copied()
+            .filter(|&code_point| code_point >= n)
+            .min()
+            .ok_or_else(overflow)?;
+
+        delta = (m - n)
+            .checked_mul(handled + 1)
+            .and_then(|increment| delta.checked_add(increment))
+            .ok_or_else(overflow)?;
+        n = m;
+
+        for &code_point in code_points.iter() {
+            if code_point < n {
+                delta = delta.checked_add(1).ok_or_else(overflow)?;
+            } else if code_point == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+
+                bias = adapt(delta, handled + 1, handled == basic_length);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta = delta.checked_add(1).ok_or_else(overflow)?;
+        n += 1;
+    }
+
+    Ok(output)
+}
+
+/// Decode a Punycode (RFC 3492) label, without the ACE prefix, into Unicode.
+pub fn punycode_decode(encoded: &str) -> Result<String, String> {
+    let invalid = || format!("invalid punycode {}", encoded);
+
+    if !encoded.is_ascii() {
+        return Err(invalid());
+    }
+
+    let (basic, extended) = match encoded.rfind('-') {
+        Some(index) => (&encoded[..index], &encoded[index + 1..]),
+        None => ("", encoded),
+    };
+
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.chars().peekable();
+
+    while digits.peek().is_some() {
+        let old_i = i;
+        let mut w: u32 = 1;
+        let mut k = BASE;
+
+        loop {
+            let digit = digits.next().and_then(decode_digit).ok_or_else(invalid)?;
+            i = digit
+                .checked_mul(w)
+                .and_then(|increment| i.checked_add(increment))
+                .ok_or_else(invalid)?;
+
+            let t = threshold(k, bias);
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t).ok_or_else(invalid)?;
+            k += BASE;
+        }
+
+        let length = output.len() as u32 + 1;
+        bias = adapt(i - old_i, length, old_i == 0);
+        n = n.checked_add(i / length).ok_or_else(invalid)?;
+        i %= length;
+
+        let ch = std::char::from_u32(n)
+            .filter(|ch| !ch.is_ascii())
+            .ok_or_else(invalid)?;
+        output.insert(i as usize, ch);
+        i += 1;
+    }
+
+    Ok(output.into_iter().collect())
+}
+
+/// Convert a domain name which may contain Unicode labels into its ASCII form, per UTS #46
+/// nontransitional processing.
+///
+/// Labels containing non-ASCII characters are mapped (case folded, full width forms narrowed and
+/// ignored characters removed), validated and encoded as `xn--` labels. ASCII labels are left as they
+/// are, except that existing `xn--` labels must decode to valid Unicode labels. If any label is right
+/// to left, every label must satisfy the bidi rule of RFC 5893.
+///
+/// Rather than carry the full UTS #46 mapping table and NFC normalization, only a conservative set of
+/// code points is accepted: ASCII, and the letters and digits of a number of common scripts along
+/// with their upper case forms. Any other code point, including combining
+/// marks that would make the input differ from its Normalization Form C, is rejected with an error, so
+/// a name is never encoded as a different A-label than UTS #46 would give.
+pub fn domain_name_to_ascii(domain_name: &str) -> Result<String, String> {
+    let (labels, absolute) = split_unicode_domain_name(domain_name)?;
+
+    let mut ascii_labels = Vec::new();
+    let mut unicode_labels = Vec::new();
+
+    for label in labels {
+        if label.is_ascii() {
+            if has_ace_prefix(&label) {
+                unicode_labels.push(decode_ace_label(&label)?);
+            } else {
+                unicode_labels.push(label.clone());
+            }
+            ascii_labels.push(label);
+            continue;
+        }
+
+        if let Some(ch) = label.chars().find(|&ch| !is_supported_code_point(ch)) {
+            return Err(format!(
+                "label {} has unsupported code point U+{:04X}",
+                label, ch as u32
+            ));
+        }
+
+        let mapped = map_label(&label);
+        if mapped.is_ascii() {
+            ascii_labels.push(mapped.clone());
+            unicode_labels.push(mapped);
+            continue;
+        }
+
+        validate_unicode_label(&mapped)?;
+
+        let ascii_label = format!("{}{}", ACE_PREFIX, punycode_encode(&mapped)?);
+        if ascii_label.len() > MAX_LABEL_LENGTH {
+            return Err(format!(
+                "label {} is longer than {} octets once encoded as {}",
+                label, MAX_LABEL_LENGTH, ascii_label
+            ));
+        }
+
+        ascii_labels.push(ascii_label);
+        unicode_labels.push(mapped);
+    }
+
+    check_bidi(&unicode_labels)?;
+
+    if ascii_labels.is_empty() {
+        return Ok(String::from("."));
+    }
+
+    let mut ascii_domain_name = ascii_labels.join(".");
+    if absolute {
+        ascii_domain_name.push('.');
+    }
+
+    Ok(ascii_domain_name)
+}
+
+/// Convert a domain name in presentation format into Unicode for display, decoding every valid
+/// `xn--` label. Labels that are not valid ACE labels are left as they are.
+pub fn domain_name_to_unicode(domain_name: &str) -> String {
+    let (labels, absolute) = match split_domain_name(domain_name) {
+        Ok(split) => split,
+        Err(_) => return String::from(domain_name),
+    };
+
+    if labels.is_empty() {
+        return String::from(domain_name);
+    }
+
+    let mut unicode_domain_name = labels
+        .iter()
+        .map(|label| {
+            let unicode_label = std::str::from_utf8(label)
+                .ok()
+                .filter(|label| has_ace_prefix(label))
+                .and_then(|label| decode_ace_label(label).ok());
+
+            unicode_label.unwrap_or_else(|| escape_label(label))
+        })
+        .collect::<Vec<String>>()
+        .join(".");
+
+    if absolute {
+        unicode_domain_name.push('.');
+    }
+
+    unicode_domain_name
+}
+
+fn has_ace_prefix(label: &str) -> bool {
+    label.len() > ACE_PREFIX.len()
+        && label.is_char_boundary(ACE_PREFIX.len())
+        && label[..ACE_PREFIX.len()].eq_ignore_ascii_case(ACE_PREFIX)
+}
+
+/// Decode and validate an `xn--` label, which must decode to a label with non-ASCII characters.
+fn decode_ace_label(label: &str) -> Result<String, String> {
+    let unicode_label = punycode_decode(&label[ACE_PREFIX.len()..])
+        .map_err(|error| format!("label {}: {}", label, error))?;
+
+    if unicode_label.is_ascii() {
+        return Err(format!("label {} does not encode any Unicode", label));
+    }
+    validate_unicode_label(&unicode_label)?;
+
+    Ok(unicode_label)
+}
+
+/// Split a domain name on '.' and the Unicode full stops UTS #46 maps to it, leaving escaped
+/// characters in place. Returns the labels and whether the name ended with a full stop.
+fn split_unicode_domain_name(domain_name: &str) -> Result<(Vec<String>, bool), String> {
+    if domain_name.is_empty() {
+        return Err("domain name is empty".into());
+    } else if domain_name == "." {
+        return Ok((Vec::new(), true));
+    }
+
+    let mut labels = Vec::new();
+    let mut label = String::new();
+    let mut absolute = false;
+    let mut escaped = false;
+
+    for ch in domain_name.chars() {
+        absolute = false;
+
+        if escaped {
+            label.push(ch);
+            escaped = false;
+            continue;
+        }
+
+        match ch {
+            '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}' => {
+                if label.is_empty() {
+                    return Err(format!("domain name {} has an empty label", domain_name));
+                }
+                labels.push(std::mem::take(&mut label));
+                absolute = true;
+            }
+            '\\' => {
+                label.push(ch);
+                escaped = true;
+            }
+            _ => label.push(ch),
+        }
+    }
+
+    if !label.is_empty() {
+        labels.push(label);
+    }
+
+    Ok((labels, absolute))
+}
+
+/// Map a label per UTS #46: remove ignored characters, narrow full width ASCII and case fold.
+fn map_label(label: &str) -> String {
+    label
+        .chars()
+        .filter(|&ch| !is_ignored(ch))
+        .map(|ch| match ch as u32 {
+            0xFF01..=0xFF5E => std::char::from_u32(ch as u32 - 0xFEE0).unwrap_or(ch),
+            _ => ch,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Returns true if ch may appear in a label given to [domain_name_to_ascii]: ASCII, code points
+/// [map_label] maps exactly as UTS #46 does, and those [is_valid_code_point] allows.
+fn is_supported_code_point(ch: char) -> bool {
+    let is_mapped_uppercase = match ch as u32 {
+        0x00C0..=0x00DE => ch != '\u{D7}',
+        // Excludes U+0130, which UTS #46 maps to "i" followed by a combining dot above.
+        0x0100..=0x017F => ch.is_uppercase() && ch != '\u{130}',
+        0x0386..=0x03AB | 0x0400..=0x042F => ch.is_uppercase(),
+        0xFF01..=0xFF5E => true,
+        _ => false,
+    };
+
+    ch.is_ascii()
+        || is_ignored(ch)
+        || is_valid_code_point(ch)
+        || (is_mapped_uppercase && map_label(&ch.to_string()).chars().all(is_valid_code_point))
+}
+
+/// Returns true if ch is in the conservative set of code points allowed in Unicode labels. Each is
+/// valid under UTS #46 and unchanged by NFC in any context. Code points that UTS #46 maps to something
+/// else, such as ligatures and Roman numerals, and combining marks with precomposed forms, are not.
+fn is_valid_code_point(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x0030..=0x0039
+            | 0x0061..=0x007A
+            | 0x00DF..=0x00F6
+            | 0x00F8..=0x00FF
+            | 0x0390
+            | 0x03AC..=0x03CE
+            | 0x0430..=0x045F
+            | 0x05D0..=0x05EA
+            | 0x0621..=0x063A
+            | 0x0641..=0x0652
+            | 0x0660..=0x0669
+            | 0x06F0..=0x06F9
+            | 0x0905..=0x0939
+            | 0x093E..=0x094D
+            | 0x0966..=0x096F
+            | 0x0E01..=0x0E32
+            | 0x0E34..=0x0E3A
+            | 0x0E40..=0x0E4E
+            | 0x0E50..=0x0E59
+            | 0x3005
+            | 0x3041..=0x3096
+            | 0x309D..=0x309E
+            | 0x30A1..=0x30FA
+            | 0x30FC..=0x30FE
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xAC00..=0xD7A3
+    ) || (matches!(ch as u32, 0x0101..=0x017E)
+        && ch.is_lowercase()
+        && !matches!(ch as u32, 0x0133 | 0x0140 | 0x0149))
+}
+
+/// Characters UTS #46 maps to nothing, such as the soft hyphen and variation selectors.
+fn is_ignored(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x00AD | 0x034F | 0x180B..=0x180D | 0x200B | 0x2060 | 0xFE00..=0xFE0F | 0xFEFF
+    )
+}
+
+fn is_combining_mark(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x0300..=0x036F
+            | 0x0483..=0x0489
+            | 0x0591..=0x05BD
+            | 0x05BF
+            | 0x05C1..=0x05C2
+            | 0x05C4..=0x05C5
+            | 0x05C7
+            | 0x0610..=0x061A
+            | 0x064B..=0x065F
+            | 0x0670
+            | 0x06D6..=0x06DC
+            | 0x06DF..=0x06E4
+            | 0x06E7..=0x06E8
+            | 0x06EA..=0x06ED
+            | 0x0E47..=0x0E4E
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x20D0..=0x20FF
+            | 0x3099..=0x309A
+            | 0xFE20..=0xFE2F
+    ) || is_virama(ch)
+}
+
+fn is_virama(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x094D
+            | 0x09CD
+            | 0x0A4D
+            | 0x0ACD
+            | 0x0B4D
+            | 0x0BCD
+            | 0x0C4D
+            | 0x0CCD
+            | 0x0D4D
+            | 0x0DCA
+            | 0x0E3A
+            | 0x1039
+            | 0x17D2
+    )
+}
+
+/// Validate a mapped Unicode label against the UTS #46 validity criteria and IDNA2008 contextual rules.
+fn validate_unicode_label(label: &str) -> Result<(), String> {
+    let chars: Vec<char> = label.chars().collect();
+
+    if chars.is_empty() {
+        return Err("label is empty".into());
+    } else if label.starts_with('-') || label.ends_with('-') {
+        return Err(format!("label {} starts or ends with a hyphen", label));
+    } else if chars.len() >= 4 && chars[2] == '-' && chars[3] == '-' {
+        return Err(format!(
+            "label {} has hyphens in the third and fourth positions",
+            label
+        ));
+    } else if is_combining_mark(chars[0]) {
+        return Err(format!("label {} starts with a combining mark", label));
+    } else if map_label(label) != label {
+        return Err(format!("label {} is not in its mapped form", label));
+    }
+
+    for (index, &ch) in chars.iter().enumerate() {
+        let allowed = match ch {
+            '-' => true,
+            // ZERO WIDTH NON-JOINER and JOINER are only allowed after a virama (RFC 5892 appendix A.1, A.2).
+            '\u{200C}' | '\u{200D}' => index > 0 && is_virama(chars[index - 1]),
+            _ => is_valid_code_point(ch),
+        };
+
+        if !allowed {
+            return Err(format!(
+                "label {} has disallowed code point U+{:04X}",
+                label, ch as u32
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The bidirectional character types (UAX #9) used by the bidi rule, approximated by Unicode block.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BidiClass {
+    L,
+    R,
+    AL,
+    AN,
+    EN,
+    ES,
+    CS,
+    ET,
+    ON,
+    BN,
+    NSM,
+}
+
+fn bidi_class(ch: char) -> BidiClass {
+    use BidiClass::*;
+
+    match ch as u32 {
+        0x0030..=0x0039 | 0x06F0..=0x06F9 => EN,
+        0x0660..=0x0669 | 0x066B..=0x066C => AN,
+        0x002B | 0x002D => ES,
+        0x002C | 0x002E | 0x002F | 0x003A => CS,
+        0x0023..=0x0025 => ET,
+        0x00AD | 0x200B..=0x200D => BN,
+        _ if is_combining_mark(ch) => NSM,
+        0x0590..=0x05FF | 0x07C0..=0x085F | 0xFB1D..=0xFB4F => R,
+        0x0600..=0x07BF | 0x0860..=0x08FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => AL,
+        _ if ch.is_alphanumeric() => L,
+        _ => ON,
+    }
+}
+
+/// Check the bidi rule of RFC 5893 section 2, which applies to every label once any label of the
+/// domain name contains right to left characters.
+fn check_bidi(labels: &[String]) -> Result<(), String> {
+    use BidiClass::*;
+
+    let is_bidi_domain_name = labels
+        .iter()
+        .flat_map(|label| label.chars())
+        .any(|ch| matches!(bidi_class(ch), R | AL | AN));
+    if !is_bidi_domain_name {
+        return Ok(());
+    }
+
+    for label in labels {
+        let classes: Vec<BidiClass> = label.chars().map(bidi_class).collect();
+        let last = classes.iter().rev().find(|&&class| class != NSM);
+        let violation = || format!("label {} violates the bidi rule (RFC 5893)", label);
+
+        match classes.first() {
+            Some(R) | Some(AL) => {
+                let allowed = classes
+                    .iter()
+                    .all(|class| matches!(class, R | AL | AN | EN | ES | CS | ET | ON | BN | NSM));
+                let ends_well = matches!(last, Some(R) | Some(AL) | Some(EN) | Some(AN));
+                let mixed_numbers = classes.contains(&EN) && classes.contains(&AN);
+
+                if !allowed || !ends_well || mixed_numbers {
+                    return Err(violation());
+                }
+            }
+            Some(L) => {
+                let allowed = classes
+                    .iter()
+                    .all(|class| matches!(class, L | EN | ES | CS | ET | ON | BN | NSM));
+                let ends_well = matches!(last, Some(L) | Some(EN));
+
+                if !allowed || !ends_well {
+                    return Err(violation());
+                }
+            }
+            _ => return Err(violation()),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_punycode() -> Result<(), String> {
+        // Sample strings from RFC 3492 section 7.1, and a couple of common examples.
+        let vectors = [
+            ("bücher", "bcher-kva"),
+            ("münchen", "mnchen-3ya"),
+            ("他们为什么不说中文", "ihqwcrb4cv8a8dqg056pqjye"),
+            ("ليهمابتكلموشعربي؟", "egbpdaj6bu4bxfgehfvwxn"),
+            ("3年B組金八先生", "3B-ww4c5e180e575a65lsy2b"),
+            (
+                "安室奈美恵-with-SUPER-MONKEYS",
+                "-with-SUPER-MONKEYS-pc58ag80a8qai00g7n9n",
+            ),
+        ];
+
+        for (unicode, encoded) in vectors.iter() {
+            assert_eq!(punycode_encode(unicode)?, *encoded);
+            assert_eq!(punycode_decode(encoded)?, *unicode);
+        }
+
+        assert!(punycode_decode("bcher-kv!").is_err());
+        assert!(punycode_decode("bcher-kv").is_err());
+        assert!(punycode_decode("99999999999").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_name_to_ascii() -> Result<(), String> {
+        assert_eq!(
+            domain_name_to_ascii("bücher.example")?,
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(
+            domain_name_to_ascii("BÜCHER.Example.")?,
+            "xn--bcher-kva.Example."
+        );
+        assert_eq!(
+            domain_name_to_ascii("例え。テスト．")?,
+            "xn--r8jz45g.xn--zckzah."
+        );
+        assert_eq!(domain_name_to_ascii("ＷＷＷ.example.")?, "www.example.");
+        assert_eq!(
+            domain_name_to_ascii("bü\u{AD}cher.example.")?,
+            "xn--bcher-kva.example."
+        );
+        assert_eq!(
+            domain_name_to_ascii("_sip._tcp.example.")?,
+            "_sip._tcp.example."
+        );
+        assert_eq!(domain_name_to_ascii(".")?, ".");
+
+        let error = domain_name_to_ascii("bü cher.example.").unwrap_err();
+        assert!(error.contains("U+0020"), "{}", error);
+        let error = domain_name_to_ascii("bü☃.example.").unwrap_err();
+        assert!(error.contains("U+2603"), "{}", error);
+        assert!(domain_name_to_ascii("-bücher.example.").is_err());
+        assert!(domain_name_to_ascii("\u{301}bücher.example.").is_err());
+        assert!(domain_name_to_ascii("xn--bcher-kv!.example.").is_err());
+        assert!(domain_name_to_ascii("xn--ascii-.example.").is_err());
+        assert!(domain_name_to_ascii(&format!("{}ü.example.", "a".repeat(60))).is_err());
+        assert!(domain_name_to_ascii("bücher..example.").is_err());
+
+        // Code points UTS #46 maps to other characters, and input not in NFC, are rejected rather
+        // than encoded as a different A-label.
+        assert_eq!(
+            domain_name_to_ascii("ПРИМЕР.испытание.")?,
+            "xn--e1afmkfd.xn--80akhbyknj4f."
+        );
+        for domain_name in [
+            "ﬁle.example.",
+            "Ⅻ.example.",
+            "u\u{308}ber.example.",
+            "\u{130}stanbul.example.",
+            "stra\u{1E9E}e.example.",
+        ]
+        .iter()
+        {
+            let error = domain_name_to_ascii(domain_name).unwrap_err();
+            assert!(error.contains("unsupported code point"), "{}", error);
+        }
+
+        // Right to left labels, which may not be mixed with left to right characters or start with a digit.
+        assert_eq!(
+            domain_name_to_ascii("שלום.example.")?,
+            "xn--9dbne9b.example."
+        );
+        let error = domain_name_to_ascii("aשלום.example.").unwrap_err();
+        assert!(error.contains("bidi"), "{}", error);
+        assert!(domain_name_to_ascii("1com.שלום.").is_err());
+        assert!(domain_name_to_ascii("شبكة١2.").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_domain_name_to_unicode() -> Result<(), String> {
+        assert_eq!(
+            domain_name_to_unicode("xn--bcher-kva.XN--zckzah.example."),
+            "bücher.テスト.example."
+        );
+        assert_eq!(
+            domain_name_to_unicode("xn--invalid-.dot\\.label.example."),
+            "xn--invalid-.dot\\.label.example."
+        );
+        assert_eq!(domain_name_to_unicode("."), ".");
+
+        let unicode = "bücher.例え.example.";
+        assert_eq!(
+            domain_name_to_unicode(&domain_name_to_ascii(unicode)?),
+            unicode
+        );
+
+        Ok(())
+    }
+}
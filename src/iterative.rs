@@ -0,0 +1,410 @@
+use crate::{
+    dns_packet::{dns_resource_record::*, *},
+    domain_name::*,
+    idna,
+    rcodes::*,
+    response_matches_query,
+    root_hints::*,
+    send_dns_query_with_options,
+    types::*,
+    QueryOptions, DNS_PORT, MAX_ALIAS_CHAIN_LENGTH,
+};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+/// The maximum number of referrals followed while looking for the servers of a single name.
+pub const MAX_REFERRALS: usize = 16;
+
+/// The maximum number of queries sent while resolving a single name, including those for the
+/// addresses of name servers.
+pub const MAX_QUERIES: usize = 64;
+
+/// The maximum nesting of lookups for the addresses of name servers without glue.
+pub const MAX_GLUELESS_DEPTH: usize = 4;
+
+/// A query sent to a name server while resolving a name.
+#[derive(Debug, Clone)]
+pub struct ServerQuery {
+    /// The address of the name server.
+    pub address: SocketAddr,
+    /// The zone the name server was asked as a server for.
+    pub zone: String,
+    /// The name queried for.
+    pub qname: String,
+    /// The type queried for.
+    pub qtype: u16,
+    /// The RCODE of the response, or why no usable response was received.
+    pub result: Result<u8, String>,
+}
+
+/// The outcome of [IterativeResolver::resolve].
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    /// The RCODE of the final response.
+    pub rcode: u8,
+    /// The CNAME records followed, across zones, then the records of the requested type.
+    pub answer: Vec<DnsResourceRecord>,
+    /// The authority section of the final response, with the SOA record for negative answers.
+    pub authority: Vec<DnsResourceRecord>,
+    /// Every query sent, in order.
+    pub servers: Vec<ServerQuery>,
+}
+
+/// What a response from a name server means for the name being resolved.
+enum ResponseKind {
+    /// The response answers the query, positively or negatively.
+    Answer,
+    /// The response refers to the name servers of a child zone closer to the name.
+    Referral(String),
+    /// The server is not authoritative for the zone it was asked for, or could not answer.
+    Lame,
+}
+
+/// Classify response to a query for qname sent to a server for zone.
+fn classify_response(response: &DnsPacket, qname: &str, zone: &str) -> ResponseKind {
+    match response.header.rcode {
+        DNS_RCODE_NAME_ERROR => return ResponseKind::Answer,
+        DNS_RCODE_NO_ERROR if !response.answer.is_empty() => return ResponseKind::Answer,
+        DNS_RCODE_NO_ERROR => {}
+        _ => return ResponseKind::Lame,
+    }
+
+    let child_zone = response
+        .authority
+        .iter()
+        .find_map(|record| match record.rdata {
+            DnsResourceRecordData::NS(_)
+                if is_subdomain_of(qname, &record.name)
+                    && is_subdomain_of(&record.name, zone)
+                    && !domain_names_equal(&record.name, zone) =>
+            {
+                Some(record.name.clone())
+            }
+            _ => None,
+        });
+
+    if let Some(child_zone) = child_zone {
+        ResponseKind::Referral(child_zone)
+    } else if response.header.aa
+        || response
+            .authority
+            .iter()
+            .any(|record| record.rrtype == DNS_TYPE_SOA)
+    {
+        ResponseKind::Answer
+    } else {
+        ResponseKind::Lame
+    }
+}
+
+/// A resolver that finds answers itself by following referrals down from the root name servers,
+/// instead of asking a recursive resolver. The root hints are primed before the first resolution and
+/// again whenever the TTL of the root NS records has passed.
+#[derive(Debug, Clone)]
+pub struct IterativeResolver {
+    root_hints: Arc<Mutex<RootHints>>,
+    port: u16,
+    options: QueryOptions,
+}
+
+impl Default for IterativeResolver {
+    fn default() -> Self {
+        IterativeResolver::with_root_hints(
+            RootHints::default(),
+            DNS_PORT as u16,
+            QueryOptions::default(),
+        )
+    }
+}
+
+impl IterativeResolver {
+    /// Create a resolver starting from the bundled root hints.
+    pub fn new() -> IterativeResolver {
+        IterativeResolver::default()
+    }
+
+    /// Create a resolver starting from root_hints, sending queries to port on every server as
+    /// controlled by options. Other ports than [DNS_PORT] are only useful for testing.
+    pub fn with_root_hints(
+        root_hints: RootHints,
+        port: u16,
+        options: QueryOptions,
+    ) -> IterativeResolver {
+        IterativeResolver {
+            root_hints: Arc::new(Mutex::new(root_hints)),
+            port,
+            options,
+        }
+    }
+
+    /// The current root hints, as primed.
+    pub fn root_hints(&self) -> RootHints {
+        self.root_hints
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// The addresses of the root servers, priming the root hints first if needed. If priming fails
+    /// the current hints are used. Priming works on a copy of the hints, so that other resolutions
+    /// are not held up while it waits for the root servers.
+    fn root_server_addresses(&self) -> Vec<IpAddr> {
+        let mut root_hints = self.root_hints();
+        if root_hints.needs_priming() {
+            let _ = root_hints.prime(self.port, &self.options);
+            *self
+                .root_hints
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = root_hints.clone();
+        }
+
+        root_hints.addresses()
+    }
+
+    /// Resolve qname and qtype, following referrals from the root servers and CNAME records across
+    /// zones. Servers that time out or are lame are skipped in favour of the other servers of a zone.
+    pub fn resolve(&self, qname: &str, qtype: u16) -> Result<Resolution, String> {
+        let qname = idna::domain_name_to_ascii(&normalize_domain_name(&String::from(qname)))?;
+        let mut servers = Vec::new();
+
+        let (rcode, answer, authority) = self.resolve_name(&qname, qtype, 0, &mut servers)?;
+
+        Ok(Resolution {
+            rcode,
+            answer,
+            authority,
+            servers,
+        })
+    }
+
+    /// Resolve qname and qtype, following CNAME records into other zones. Returns the RCODE, answer
+    /// and authority of the final response.
+    fn resolve_name(
+        &self,
+        qname: &str,
+        qtype: u16,
+        depth: usize,
+        servers: &mut Vec<ServerQuery>,
+    ) -> Result<(u8, Vec<DnsResourceRecord>, Vec<DnsResourceRecord>), String> {
+        let mut answer = Vec::new();
+        let mut name = String::from(qname);
+
+        for _ in 0..MAX_ALIAS_CHAIN_LENGTH {
+            let (mut response, zone) = self.query_authoritative(&name, qtype, depth, servers)?;
+            // Records outside the zone of the server are not trusted, so CNAME targets outside of it
+            // are resolved again from the root.
+            response
+                .answer
+                .retain(|record| is_subdomain_of(&record.name, &zone));
+            let canonical_name = response.follow_alias_chain(&name)?;
+            answer.extend(response.answer.iter().cloned());
+
+            let answered = response.answer.iter().any(|record| {
+                record.rrtype == qtype && domain_names_equal(&record.name, &canonical_name)
+            });
+            if answered
+                || response.header.rcode != DNS_RCODE_NO_ERROR
+                || domain_names_equal(&canonical_name, &name)
+            {
+                return Ok((response.header.rcode, answer, response.authority));
+            }
+
+            // The answer ended in a CNAME to a name the server is not authoritative for.
+            name = canonical_name;
+        }
+
+        Err(format!("Too many redirections while resolving {}", qname))
+    }
+
+    /// Find a server authoritative for qname, starting at the root, and return its response and the
+    /// zone it was asked for.
+    fn query_authoritative(
+        &self,
+        qname: &str,
+        qtype: u16,
+        depth: usize,
+        servers: &mut Vec<ServerQuery>,
+    ) -> Result<(DnsPacket, String), String> {
+        let mut zone = String::from(".");
+        let mut addresses = self.root_server_addresses();
+
+        for _ in 0..MAX_REFERRALS {
+            let mut referral = None;
+
+            for address in addresses.iter() {
+                if servers.len() >= MAX_QUERIES {
+                    return Err(format!("Too many queries while resolving {}", qname));
+                }
+
+                let address = SocketAddr::new(*address, self.port);
+                let response = self.query(address, qname, qtype);
+                servers.push(ServerQuery {
+                    address,
+                    zone: zone.clone(),
+                    qname: String::from(qname),
+                    qtype,
+                    result: response
+                        .as_ref()
+                        .map(|response| response.header.rcode)
+                        .map_err(String::clone),
+                });
+
+                let response = match response {
+                    Ok(response) => response,
+                    Err(_) => continue,
+                };
+                match classify_response(&response, qname, &zone) {
+                    ResponseKind::Answer => return Ok((response, zone)),
+                    ResponseKind::Referral(child_zone) => {
+                        referral = Some((response, child_zone));
+                        break;
+                    }
+                    ResponseKind::Lame => continue,
+                }
+            }
+
+            let (response, child_zone) =
+                referral.ok_or_else(|| format!("No server for {} answered for {}", zone, qname))?;
+            addresses =
+                self.name_server_addresses(&response, &zone, &child_zone, depth, servers)?;
+            zone = child_zone;
+        }
+
+        Err(format!("Too many referrals while resolving {}", qname))
+    }
+
+    /// The addresses of the name servers for child_zone in a referral from a server for zone. Glue is
+    /// only used for name servers within zone, and the other name servers are resolved separately.
+    fn name_server_addresses(
+        &self,
+        referral: &DnsPacket,
+        zone: &str,
+        child_zone: &str,
+        depth: usize,
+        servers: &mut Vec<ServerQuery>,
+    ) -> Result<Vec<IpAddr>, String> {
+        let name_servers: Vec<&String> = referral
+            .authority
+            .iter()
+            .filter_map(|record| match &record.rdata {
+                DnsResourceRecordData::NS(nsdname)
+                    if domain_names_equal(&record.name, child_zone) =>
+                {
+                    Some(nsdname)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut addresses: Vec<IpAddr> = Vec::new();
+        for rrtype in [DNS_TYPE_A, DNS_TYPE_AAAA].iter() {
+            for record in referral.additional.iter() {
+                let in_bailiwick = name_servers.iter().any(|nsdname| {
+                    domain_names_equal(nsdname, &record.name) && is_subdomain_of(nsdname, zone)
+                });
+                match record.rdata {
+                    DnsResourceRecordData::A(address) if *rrtype == DNS_TYPE_A && in_bailiwick => {
+                        addresses.push(address.into())
+                    }
+                    DnsResourceRecordData::AAAA(address)
+                        if *rrtype == DNS_TYPE_AAAA && in_bailiwick =>
+                    {
+                        addresses.push(address.into())
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if !addresses.is_empty() {
+            return Ok(addresses);
+        } else if depth >= MAX_GLUELESS_DEPTH {
+            return Err(format!(
+                "Too many name servers without glue while resolving {}",
+                child_zone
+            ));
+        }
+
+        for nsdname in name_servers {
+            if let Ok((_, answer, _)) = self.resolve_name(nsdname, DNS_TYPE_A, depth + 1, servers) {
+                addresses.extend(answer.iter().filter_map(|record| match record.rdata {
+                    DnsResourceRecordData::A(address) => Some(IpAddr::from(address)),
+                    _ => None,
+                }));
+            }
+            if !addresses.is_empty() {
+                return Ok(addresses);
+            }
+        }
+
+        Err(format!(
+            "Could not find the address of any name server for {}",
+            child_zone
+        ))
+    }
+
+    /// Send a non-recursive query for qname and qtype to address.
+    fn query(&self, address: SocketAddr, qname: &str, qtype: u16) -> Result<DnsPacket, String> {
+        let mut dns_packet = DnsPacket::new(&String::from(qname), qtype)?;
+        dns_packet.header.rd = false;
+
+        let dns_response =
+            send_dns_query_with_options(&dns_packet, &address.to_string(), &self.options)?;
+        if !response_matches_query(&dns_packet, &dns_response, false) {
+            return Err("Response does not match the query".into());
+        } else if dns_response.header.tc {
+            return Err("Response is truncated".into());
+        }
+
+        Ok(dns_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::*, zone::*};
+
+    #[test]
+    fn test_classify_response() -> Result<(), String> {
+        let master_file = "$TTL 60\n@ SOA ns host 1 2 3 4 5\n@ NS ns\nns A 192.0.2.1\n\
+                           www A 192.0.2.2\nsub NS ns.sub\nns.sub A 192.0.2.3\n";
+        let zone = Zone::new(
+            "example.com.",
+            parser::parse_master_file_str(master_file, "example.com.")?,
+        )?;
+        let server = AuthoritativeServer::new(vec![zone])?;
+        let respond = |qname: &str, qtype: u16| -> Result<DnsPacket, String> {
+            Ok(server.respond(&DnsPacket::new(&String::from(qname), qtype)?))
+        };
+
+        let kind = |response: &DnsPacket, qname: &str, zone: &str| match classify_response(
+            response, qname, zone,
+        ) {
+            ResponseKind::Answer => String::from("answer"),
+            ResponseKind::Referral(child_zone) => child_zone,
+            ResponseKind::Lame => String::from("lame"),
+        };
+
+        let response = respond("www.example.com.", DNS_TYPE_A)?;
+        assert_eq!(kind(&response, "www.example.com.", "com."), "answer");
+        let response = respond("www.example.com.", DNS_TYPE_MX)?;
+        assert_eq!(kind(&response, "www.example.com.", "com."), "answer");
+        let response = respond("missing.example.com.", DNS_TYPE_A)?;
+        assert_eq!(kind(&response, "missing.example.com.", "com."), "answer");
+        let response = respond("www.sub.example.com.", DNS_TYPE_A)?;
+        assert_eq!(
+            kind(&response, "www.sub.example.com.", "example.com."),
+            "sub.example.com."
+        );
+
+        // A referral that is not closer to the name than the zone asked for is lame.
+        assert_eq!(
+            kind(&response, "www.sub.example.com.", "sub.example.com."),
+            "lame"
+        );
+        let response = respond("www.example.net.", DNS_TYPE_A)?;
+        assert_eq!(kind(&response, "www.example.net.", "net."), "lame");
+
+        Ok(())
+    }
+}
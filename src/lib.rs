@@ -5,8 +5,9 @@
 //TODO Improve testing to check against invalid input
 
 //TODO After adding the RFCs for EDNS and DNSSEC fully, we need to implement.
-//TODO Begin using lib for a recursive resolver in a bin?
-//TODO Begin master file and authoritative scenario in a bin?
+
+/// A TTL-aware cache of DNS answers, including negative answers.
+pub mod cache;
 
 /// DNS packet structures and operations.
 pub mod dns_packet;
@@ -14,12 +15,57 @@ pub mod dns_packet;
 /// DNS Domain Name operations.
 pub mod domain_name;
 
+/// Text encodings used by the DNS presentation format.
+pub mod encoding;
+
+/// A caching forwarder answering queries through upstream recursive name servers.
+pub mod forwarder;
+
+/// Hosts file parsing and host lookups consulting it before the DNS.
+pub mod hosts;
+
+/// Internationalized domain names, Punycode and UTS #46 processing.
+pub mod idna;
+
+/// Iterative resolution of domain names starting from the root name servers.
+pub mod iterative;
+
+/// Mnemonics for TYPE, CLASS, OPCODE and RCODE values.
+pub mod mnemonics;
+
+/// Stub resolver configured like the system resolver, from resolv.conf.
+pub mod resolver;
+
+/// Root name server hints and priming.
+pub mod root_hints;
+
+/// Authoritative name server answering queries from zones over UDP and TCP.
+pub mod server;
+
+/// DNS over TCP client connections, with connection reuse and pipelining.
+pub mod tcp;
+
+/// Bounds checked reading and writing of the DNS wire format.
+pub mod wire;
+
+/// DNS zones and the master files describing them.
+pub mod zone;
+
 /// Default DNS protocol port.
 pub const DNS_PORT: u8 = 53;
 
 /// The size of a valid DNS packet header.
 pub const DNS_HEADER_SIZE: usize = 12;
 
+/// The maximum size of a DNS message carried over UDP without EDNS(0) (RFC 1035 section 4.2.1).
+pub const MAX_UDP_MESSAGE_SIZE: usize = 512;
+
+/// The UDP payload size advertised with EDNS(0), as recommended by DNS Flag Day 2020.
+pub const EDNS_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// The maximum number of CNAME and DNAME redirections followed when resolving a single domain name.
+pub const MAX_ALIAS_CHAIN_LENGTH: usize = 16;
+
 /// DNS OPCODE values.
 pub mod opcodes {
     /// A standard query (QUERY).
@@ -45,6 +91,12 @@ pub mod rcodes {
     pub const DNS_RCODE_NOT_IMPLEMENTED: u8 = 4;
     /// Refused - The name server refuses to perform the specified operation.
     pub const DNS_RCODE_REFUSED: u8 = 5;
+    /// YXDomain - Some name that ought not to exist, does exist.
+    /// Also returned when a DNAME substitution would overflow the legal size of a domain name.
+    pub const DNS_RCODE_YXDOMAIN: u8 = 6;
+    /// Bad OPT Version - The responder does not implement the EDNS version requested.
+    /// This is an extended RCODE, only representable with an OPT pseudo-RR.
+    pub const DNS_RCODE_BADVERS: u8 = 16;
 }
 
 /// CLASS values, all of which are a subset of QCLASS values.
@@ -105,10 +157,36 @@ pub mod types {
     pub const DNS_TYPE_MX: u16 = 15;
     /// Text strings.
     pub const DNS_TYPE_TXT: u16 = 16;
+    /// Responsible person.
+    pub const DNS_TYPE_RP: u16 = 17;
+    /// AFS database location.
+    pub const DNS_TYPE_AFSDB: u16 = 18;
+    /// X.25 PSDN address.
+    pub const DNS_TYPE_X25: u16 = 19;
+    /// ISDN address.
+    pub const DNS_TYPE_ISDN: u16 = 20;
+    /// Route through.
+    pub const DNS_TYPE_RT: u16 = 21;
     /// An IPv6 host address.
     pub const DNS_TYPE_AAAA: u16 = 28;
     /// Specifies location of a service for a specific protocol.
     pub const DNS_TYPE_SRV: u16 = 33;
+    /// A certificate or certificate revocation list.
+    pub const DNS_TYPE_CERT: u16 = 37;
+    /// Delegation of an entire subtree of the domain name space to another domain.
+    pub const DNS_TYPE_DNAME: u16 = 39;
+    /// The EDNS(0) pseudo-RR, carrying extended header fields and options in the additional section.
+    pub const DNS_TYPE_OPT: u16 = 41;
+    /// Lists of address prefixes.
+    pub const DNS_TYPE_APL: u16 = 42;
+    /// A public key for IPsec.
+    pub const DNS_TYPE_IPSECKEY: u16 = 45;
+    /// A 48-bit extended unique identifier (MAC address).
+    pub const DNS_TYPE_EUI48: u16 = 108;
+    /// A 64-bit extended unique identifier.
+    pub const DNS_TYPE_EUI64: u16 = 109;
+    /// A mapping from a hostname to a URI.
+    pub const DNS_TYPE_URI: u16 = 256;
 }
 
 /// QTYPE values, used in the question section of a DNS packet.
@@ -127,7 +205,7 @@ pub mod qtypes {
 
 /// Example DNS query packets in network format
 pub mod query_examples {
-    pub const BASIC_QUERY: &'static [u8] = &[
+    pub const BASIC_QUERY: &[u8] = &[
         0x24, 0xB1, //ID
         0x01, 0x80, //QR=0,OPCODE=0,AA=0,TC=0,RD=1,RA=1,Z=0,RCODE=0
         0x00, 0x01, //QDCOUNT
@@ -142,7 +220,7 @@ pub mod query_examples {
         0x00, 0x01, //QCLASS=1
     ];
 
-    pub const BASIC_QUERY_RESPONSE: &'static [u8] = &[
+    pub const BASIC_QUERY_RESPONSE: &[u8] = &[
         0x24, 0xB1, //ID
         0x81, 0x80, //QR=1,OPCODE=0,AA=0,TC=0,RD=1,RA=1,Z=0,RCODE=0
         0x00, 0x01, //QDCOUNT
@@ -162,7 +240,7 @@ pub mod query_examples {
         0xD8, 0x3A, 0xD9, 0x24, //RDATA = 216.58.217.36
     ];
 
-    pub const NAME_COMPRESSION_QUERY: &'static [u8] = &[
+    pub const NAME_COMPRESSION_QUERY: &[u8] = &[
         0x24, 0xB1, //ID
         0x01, 0x80, //QR=0,OPCODE=0,AA=0,TC=0,RD=1,RA=1,Z=0,RCODE=0
         0x00, 0x02, //QDCOUNT
@@ -182,20 +260,174 @@ pub mod query_examples {
     ];
 }
 
+/// The transport a DNS message is carried over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    /// A single UDP datagram, limited to 512 octets or the EDNS(0) UDP payload size.
+    Udp,
+    /// A TCP connection, with each message preceded by its two octet length.
+    Tcp,
+}
+
+/// How long to wait for a response to a query by default.
+pub const DEFAULT_QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Options controlling how a DNS query is sent by [send_dns_query_with_options].
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// Randomize the case of the letters in each QNAME (DNS 0x20) and require the response to echo
+    /// the question exactly. If the server answers with the question in a different case, it does not
+    /// preserve case and is queried again without randomization.
+    pub randomize_case: bool,
+    /// How long to wait for a response before giving up on the query.
+    pub timeout: std::time::Duration,
+    /// The transport the query is sent over. Queries sent over UDP are sent again over TCP if the
+    /// response is truncated.
+    pub transport: Transport,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            randomize_case: false,
+            timeout: DEFAULT_QUERY_TIMEOUT,
+            transport: Transport::Udp,
+        }
+    }
+}
+
 /// Send a DNS packet to the given destination, returns the response
 pub fn send_dns_query_to(
     dns_packet: &dns_packet::DnsPacket,
     destination: &String,
 ) -> Result<dns_packet::DnsPacket, String> {
-    let client_socket = std::net::UdpSocket::bind("0.0.0.0:0").expect("Client could not bind");
+    send_dns_query_with_options(dns_packet, destination, &QueryOptions::default())
+}
+
+/// Send a DNS packet to the given destination as controlled by options, returns the response
+pub fn send_dns_query_with_options(
+    dns_packet: &dns_packet::DnsPacket,
+    destination: &String,
+    options: &QueryOptions,
+) -> Result<dns_packet::DnsPacket, String> {
+    send_dns_query_reporting_transport(dns_packet, destination, options)
+        .map(|(dns_response, _)| dns_response)
+}
+
+/// Send a DNS packet to the given destination as controlled by options, returns the response and the
+/// transport it arrived over. A truncated response over UDP is discarded and the query sent again
+/// over TCP to the same destination (RFC 7766 section 5).
+pub fn send_dns_query_reporting_transport(
+    dns_packet: &dns_packet::DnsPacket,
+    destination: &String,
+    options: &QueryOptions,
+) -> Result<(dns_packet::DnsPacket, Transport), String> {
+    if !options.randomize_case {
+        return exchange(dns_packet, destination, options);
+    }
+
+    let mut randomized_packet = dns_packet.clone();
+    for question in randomized_packet.question.iter_mut() {
+        question.qname = domain_name::randomize_case(&question.qname);
+    }
+
+    let (dns_response, transport) = exchange(&randomized_packet, destination, options)?;
+    if response_matches_query(&randomized_packet, &dns_response, true) {
+        return Ok((dns_response, transport));
+    } else if !response_matches_query(&randomized_packet, &dns_response, false) {
+        return Err(format!(
+            "Response question {:?} does not match query question {:?}",
+            dns_response.question, randomized_packet.question
+        ));
+    }
+
+    // The server changed the case of the question, so it cannot be relied on to echo it exactly. The
+    // query is sent again under a new ID, so that the ID of the first one does not give it away.
+    let mut fallback_packet = dns_packet.clone();
+    while fallback_packet.header.id == dns_packet.header.id {
+        fallback_packet.header.id = rand::random();
+    }
+    let (mut dns_response, transport) = exchange(&fallback_packet, destination, options)?;
+    if !response_matches_query(&fallback_packet, &dns_response, false) {
+        return Err(format!(
+            "Response question {:?} does not match query question {:?}",
+            dns_response.question, dns_packet.question
+        ));
+    }
+
+    dns_response.header.id = dns_packet.header.id;
+    Ok((dns_response, transport))
+}
+
+/// Returns true if the response has the ID and question of the query, comparing names octet for octet
+/// if exact is true and otherwise ignoring ASCII case.
+fn response_matches_query(
+    query: &dns_packet::DnsPacket,
+    response: &dns_packet::DnsPacket,
+    exact: bool,
+) -> bool {
+    let names_match = if exact {
+        domain_name::domain_names_identical
+    } else {
+        domain_name::domain_names_equal
+    };
+
+    response.header.id == query.header.id
+        && response.question.len() == query.question.len()
+        && response.question.iter().zip(query.question.iter()).all(
+            |(response_question, query_question)| {
+                names_match(&response_question.qname, &query_question.qname)
+                    && response_question.qtype == query_question.qtype
+                    && response_question.qclass == query_question.qclass
+            },
+        )
+}
+
+/// Send a DNS packet to the given destination over the transport of options, falling back to TCP if
+/// the response over UDP is truncated. Returns the response and the transport it arrived over.
+fn exchange(
+    dns_packet: &dns_packet::DnsPacket,
+    destination: &String,
+    options: &QueryOptions,
+) -> Result<(dns_packet::DnsPacket, Transport), String> {
+    if options.transport == Transport::Udp {
+        let dns_response = exchange_udp(dns_packet, destination, options.timeout)?;
+        if !dns_response.header.tc {
+            return Ok((dns_response, Transport::Udp));
+        }
+    }
+
+    let dns_response = exchange_tcp(dns_packet, destination, options.timeout)?;
+    Ok((dns_response, Transport::Tcp))
+}
+
+/// Send a DNS packet over a new TCP connection to the given destination, returns the response or an
+/// error if none arrives within timeout. See [tcp::TcpConnection] to reuse the connection.
+fn exchange_tcp(
+    dns_packet: &dns_packet::DnsPacket,
+    destination: &str,
+    timeout: std::time::Duration,
+) -> Result<dns_packet::DnsPacket, String> {
+    tcp::TcpConnection::new(destination, timeout)?.query(dns_packet)
+}
+
+/// Send a DNS packet over UDP to the given destination, returns the response or an error if none
+/// arrives within timeout.
+fn exchange_udp(
+    dns_packet: &dns_packet::DnsPacket,
+    destination: &String,
+    timeout: std::time::Duration,
+) -> Result<dns_packet::DnsPacket, String> {
+    let client_socket = std::net::UdpSocket::bind("0.0.0.0:0")
+        .map_err(|error| format!("Client could not bind: {}", error))?;
 
     let serialized_dns_packet = dns_packet.serialize()?;
 
     client_socket
         .send_to(&serialized_dns_packet, destination)
-        .expect("Client could not send data");
+        .map_err(|error| format!("Client could not send data to {}: {}", destination, error))?;
 
-    match client_socket.set_read_timeout(Some(std::time::Duration::from_secs(2))) {
+    match client_socket.set_read_timeout(Some(timeout)) {
         Ok(_) => {}
         Err(_) => {
             return Err("Could not set query socket timeout".into());
@@ -203,44 +435,120 @@ pub fn send_dns_query_to(
     }
 
     let mut buf: [u8; 65535] = [0; 65535];
-    let (amt, _) = client_socket
-        .recv_from(&mut buf)
-        .expect("Client could not recieve data from google dns");
+    let (amt, _) = client_socket.recv_from(&mut buf).map_err(|error| {
+        format!(
+            "Client could not recieve data from {}: {}",
+            destination, error
+        )
+    })?;
     let buf = &buf[..amt];
 
-    let dns_response = dns_packet::DnsPacket::parse_dns_packet(&buf.into())?;
+    let dns_response = dns_packet::DnsPacket::parse_dns_packet(buf)?;
 
     Ok(dns_response)
 }
 
-/// Resolve a domain name via DNS through a Google recursive resolver
+/// Resolve a domain name via DNS through a Google recursive resolver, following CNAME and DNAME redirections.
+/// The domain name may contain Unicode labels, which are converted with IDNA. Answers are cached in a cache
+/// shared by all callers, see [shared_cache].
 pub fn resolve_domain_name(domain_name: &String) -> Result<std::net::Ipv4Addr, String> {
-    let dns_packet = dns_packet::DnsPacket::new(domain_name, types::DNS_TYPE_A)?;
+    resolve_domain_name_with_cache(domain_name, shared_cache())
+}
+
+/// The cache used by [resolve_domain_name], with the default limits.
+pub fn shared_cache() -> &'static cache::Cache {
+    static SHARED_CACHE: std::sync::OnceLock<cache::Cache> = std::sync::OnceLock::new();
+    SHARED_CACHE.get_or_init(cache::Cache::default)
+}
 
-    let dns_response = send_dns_query_to(&dns_packet, &String::from("8.8.8.8:53"))?;
+/// Resolve a domain name like [resolve_domain_name], answering from cache where it can and caching the
+/// responses of the recursive resolver, including negative ones.
+pub fn resolve_domain_name_with_cache(
+    domain_name: &String,
+    cache: &cache::Cache,
+) -> Result<std::net::Ipv4Addr, String> {
+    let mut current_name =
+        idna::domain_name_to_ascii(&domain_name::normalize_domain_name(domain_name))?;
 
-    match dns_response.header.rcode {
-        rcodes::DNS_RCODE_NO_ERROR => {}
-        _ => {
-            return Err(format!(
-                "Recursive resolver could not find {}, returned RCODE={}, Packet: {:?}",
-                domain_name, dns_response.header.rcode, dns_response
-            ));
+    for _ in 0..MAX_ALIAS_CHAIN_LENGTH {
+        match cache.get(&current_name, types::DNS_TYPE_A, classes::DNS_CLASS_IN) {
+            Some(cache::CachedAnswer::Records(records)) => {
+                if let Some(address) = records.iter().find_map(|record| match record.rdata {
+                    crate::dns_packet::dns_resource_record::DnsResourceRecordData::A(address) => {
+                        Some(address)
+                    }
+                    _ => None,
+                }) {
+                    return Ok(address);
+                }
+            }
+            Some(cache::CachedAnswer::NameError(_)) => {
+                return Err(format!(
+                    "Recursive resolver could not find {}, returned RCODE={} (cached)",
+                    current_name,
+                    rcodes::DNS_RCODE_NAME_ERROR
+                ));
+            }
+            Some(cache::CachedAnswer::NoData(_)) => {
+                return Err("DNS response had no A records (cached)".into());
+            }
+            None => {}
         }
-    }
 
-    let position = dns_response
-        .answer
-        .iter()
-        .position(|record| record.rrtype == types::DNS_TYPE_A)
-        .ok_or("DNS response had no A records")?;
+        // A cached CNAME lets the chain be followed without asking for the alias again.
+        if let Some(cache::CachedAnswer::Records(records)) =
+            cache.get(&current_name, types::DNS_TYPE_CNAME, classes::DNS_CLASS_IN)
+        {
+            if let Some(crate::dns_packet::dns_resource_record::DnsResourceRecordData::CNAME(
+                target,
+            )) = records.first().map(|record| &record.rdata)
+            {
+                current_name = target.clone();
+                continue;
+            }
+        }
 
-    match dns_response.answer[position].rdata {
-        crate::dns_packet::dns_resource_record::DnsResourceRecordData::A(address) => {
-            return Ok(address);
+        let dns_packet = dns_packet::DnsPacket::new(&current_name, types::DNS_TYPE_A)?;
+
+        let dns_response = send_dns_query_to(&dns_packet, &String::from("8.8.8.8:53"))?;
+        cache.insert_response(&dns_response)?;
+
+        match dns_response.header.rcode {
+            rcodes::DNS_RCODE_NO_ERROR => {}
+            _ => {
+                return Err(format!(
+                    "Recursive resolver could not find {}, returned RCODE={}, Packet: {:?}",
+                    current_name, dns_response.header.rcode, dns_response
+                ));
+            }
         }
-        _ => {
-            return Err("Did not match A resource record".into());
+
+        let canonical_name = dns_response.follow_alias_chain(&current_name)?;
+
+        let address = dns_response
+            .answer
+            .iter()
+            .find_map(|record| match record.rdata {
+                crate::dns_packet::dns_resource_record::DnsResourceRecordData::A(address)
+                    if domain_name::domain_names_equal(&record.name, &canonical_name) =>
+                {
+                    Some(address)
+                }
+                _ => None,
+            });
+
+        if let Some(address) = address {
+            return Ok(address);
+        } else if domain_name::domain_names_equal(&canonical_name, &current_name) {
+            return Err("DNS response had no A records".into());
         }
+
+        // The answer ended in a redirection without the final records, so ask for the new name.
+        current_name = canonical_name;
     }
+
+    Err(format!(
+        "Too many redirections while resolving {}",
+        domain_name
+    ))
 }
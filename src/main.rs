@@ -8,7 +8,7 @@ fn main() -> Result<(), String> {
 
     let address = rusty_dns::resolve_domain_name(&args[1])?;
 
-    println!("Returned IPv4 address: {}", address.to_string());
+    println!("Returned IPv4 address: {}", address);
 
     Ok(())
 }
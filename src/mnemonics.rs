@@ -0,0 +1,158 @@
+use crate::{classes::*, opcodes::*, qclasses::*, qtypes::*, rcodes::*, types::*};
+
+const TYPE_MNEMONICS: &[(u16, &str)] = &[
+    (DNS_TYPE_A, "A"),
+    (DNS_TYPE_NS, "NS"),
+    (DNS_TYPE_MD, "MD"),
+    (DNS_TYPE_MF, "MF"),
+    (DNS_TYPE_CNAME, "CNAME"),
+    (DNS_TYPE_SOA, "SOA"),
+    (DNS_TYPE_MB, "MB"),
+    (DNS_TYPE_MG, "MG"),
+    (DNS_TYPE_MR, "MR"),
+    (DNS_TYPE_NULL, "NULL"),
+    (DNS_TYPE_WKS, "WKS"),
+    (DNS_TYPE_PTR, "PTR"),
+    (DNS_TYPE_HINFO, "HINFO"),
+    (DNS_TYPE_MINFO, "MINFO"),
+    (DNS_TYPE_MX, "MX"),
+    (DNS_TYPE_TXT, "TXT"),
+    (DNS_TYPE_RP, "RP"),
+    (DNS_TYPE_AFSDB, "AFSDB"),
+    (DNS_TYPE_X25, "X25"),
+    (DNS_TYPE_ISDN, "ISDN"),
+    (DNS_TYPE_RT, "RT"),
+    (DNS_TYPE_AAAA, "AAAA"),
+    (DNS_TYPE_SRV, "SRV"),
+    (DNS_TYPE_CERT, "CERT"),
+    (DNS_TYPE_DNAME, "DNAME"),
+    (DNS_TYPE_OPT, "OPT"),
+    (DNS_TYPE_APL, "APL"),
+    (DNS_TYPE_IPSECKEY, "IPSECKEY"),
+    (DNS_TYPE_EUI48, "EUI48"),
+    (DNS_TYPE_EUI64, "EUI64"),
+    (DNS_TYPE_URI, "URI"),
+    (DNS_QTYPE_AXFR, "AXFR"),
+    (DNS_QTYPE_MAILB, "MAILB"),
+    (DNS_QTYPE_MAILA, "MAILA"),
+    (DNS_QTYPE_ANY, "ANY"),
+];
+
+const CLASS_MNEMONICS: &[(u16, &str)] = &[
+    (DNS_CLASS_IN, "IN"),
+    (DNS_CLASS_CS, "CS"),
+    (DNS_CLASS_CH, "CH"),
+    (DNS_CLASS_HS, "HS"),
+    (DNS_QCLASS_ANY, "ANY"),
+];
+
+const OPCODE_MNEMONICS: &[(u8, &str)] = &[
+    (DNS_OPCODE_QUERY, "QUERY"),
+    (DNS_OPCODE_IQUERY, "IQUERY"),
+    (DNS_OPCODE_STATUS, "STATUS"),
+];
+
+const RCODE_MNEMONICS: &[(u8, &str)] = &[
+    (DNS_RCODE_NO_ERROR, "NOERROR"),
+    (DNS_RCODE_FORMAT_ERROR, "FORMERR"),
+    (DNS_RCODE_SERVER_ERROR, "SERVFAIL"),
+    (DNS_RCODE_NAME_ERROR, "NXDOMAIN"),
+    (DNS_RCODE_NOT_IMPLEMENTED, "NOTIMP"),
+    (DNS_RCODE_REFUSED, "REFUSED"),
+    (DNS_RCODE_YXDOMAIN, "YXDOMAIN"),
+    (DNS_RCODE_BADVERS, "BADVERS"),
+];
+
+/// The mnemonic for a TYPE or QTYPE value, or TYPE followed by the value if it has none (RFC 3597).
+pub fn type_to_string(rrtype: u16) -> String {
+    match TYPE_MNEMONICS.iter().find(|(value, _)| *value == rrtype) {
+        Some((_, mnemonic)) => String::from(*mnemonic),
+        None => format!("TYPE{}", rrtype),
+    }
+}
+
+/// The mnemonic for a CLASS or QCLASS value, or CLASS followed by the value if it has none (RFC 3597).
+pub fn class_to_string(class: u16) -> String {
+    match CLASS_MNEMONICS.iter().find(|(value, _)| *value == class) {
+        Some((_, mnemonic)) => String::from(*mnemonic),
+        None => format!("CLASS{}", class),
+    }
+}
+
+/// The mnemonic for an OPCODE value, or the value itself if it has none.
+pub fn opcode_to_string(opcode: u8) -> String {
+    match OPCODE_MNEMONICS.iter().find(|(value, _)| *value == opcode) {
+        Some((_, mnemonic)) => String::from(*mnemonic),
+        None => opcode.to_string(),
+    }
+}
+
+/// The mnemonic for an RCODE value, including extended RCODEs from EDNS, or RCODE followed by the value.
+pub fn rcode_to_string(rcode: u16) -> String {
+    match RCODE_MNEMONICS
+        .iter()
+        .find(|(value, _)| *value as u16 == rcode)
+    {
+        Some((_, mnemonic)) => String::from(*mnemonic),
+        None => format!("RCODE{}", rcode),
+    }
+}
+
+/// Parse a TYPE or QTYPE mnemonic, or the generic TYPE followed by a value (RFC 3597), ignoring case.
+pub fn type_from_string(text: &str) -> Result<u16, String> {
+    from_string(text, TYPE_MNEMONICS, "TYPE").ok_or(format!("unknown type {}", text))
+}
+
+/// Parse a CLASS or QCLASS mnemonic, or the generic CLASS followed by a value (RFC 3597), ignoring case.
+pub fn class_from_string(text: &str) -> Result<u16, String> {
+    from_string(text, CLASS_MNEMONICS, "CLASS").ok_or(format!("unknown class {}", text))
+}
+
+fn from_string(text: &str, mnemonics: &[(u16, &str)], generic_prefix: &str) -> Option<u16> {
+    if let Some((value, _)) = mnemonics
+        .iter()
+        .find(|(_, mnemonic)| mnemonic.eq_ignore_ascii_case(text))
+    {
+        return Some(*value);
+    }
+
+    let prefix = text.get(..generic_prefix.len())?;
+    let digits = &text[generic_prefix.len()..];
+    if !prefix.eq_ignore_ascii_case(generic_prefix) || !digits.bytes().all(|ch| ch.is_ascii_digit())
+    {
+        return None;
+    }
+
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mnemonics() -> Result<(), String> {
+        assert_eq!(type_to_string(DNS_TYPE_AAAA), "AAAA");
+        assert_eq!(type_to_string(DNS_QTYPE_ANY), "ANY");
+        assert_eq!(type_to_string(65280), "TYPE65280");
+        assert_eq!(class_to_string(DNS_CLASS_IN), "IN");
+        assert_eq!(class_to_string(4096), "CLASS4096");
+        assert_eq!(opcode_to_string(DNS_OPCODE_QUERY), "QUERY");
+        assert_eq!(opcode_to_string(15), "15");
+        assert_eq!(rcode_to_string(DNS_RCODE_NAME_ERROR as u16), "NXDOMAIN");
+        assert_eq!(rcode_to_string(DNS_RCODE_BADVERS as u16), "BADVERS");
+        assert_eq!(rcode_to_string(4095), "RCODE4095");
+
+        assert_eq!(type_from_string("aaaa")?, DNS_TYPE_AAAA);
+        assert_eq!(type_from_string("TYPE65280")?, 65280);
+        assert_eq!(type_from_string("type1")?, DNS_TYPE_A);
+        assert!(type_from_string("TYPE65536").is_err());
+        assert!(type_from_string("TYPE+1").is_err());
+        assert!(type_from_string("BOGUS").is_err());
+        assert_eq!(class_from_string("in")?, DNS_CLASS_IN);
+        assert_eq!(class_from_string("CLASS4096")?, 4096);
+        assert!(class_from_string("A").is_err());
+
+        Ok(())
+    }
+}
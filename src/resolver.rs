@@ -0,0 +1,481 @@
+use crate::{
+    dns_packet::{dns_resource_record::*, *},
+    domain_name::*,
+    idna,
+    rcodes::*,
+    send_dns_query_with_options,
+    tcp::*,
+    types::*,
+    QueryOptions, Transport, DNS_PORT, EDNS_UDP_PAYLOAD_SIZE,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The location of the system resolver configuration.
+pub const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// The most name servers used from a configuration, later ones are ignored.
+pub const MAX_NAMESERVERS: usize = 3;
+
+/// The most domains in a search list, later ones are ignored.
+pub const MAX_SEARCH_DOMAINS: usize = 6;
+
+/// The largest ndots option accepted, larger values are lowered to it.
+pub const MAX_NDOTS: u8 = 15;
+
+/// The largest timeout option accepted in seconds, larger values are lowered to it.
+pub const MAX_TIMEOUT_SECS: u64 = 30;
+
+/// The largest attempts option accepted, larger values are lowered to it.
+pub const MAX_ATTEMPTS: u8 = 5;
+
+/// The configuration of a stub resolver, in the terms of resolv.conf(5).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolverConfig {
+    /// The recursive name servers to query, in order.
+    pub nameservers: Vec<SocketAddr>,
+    /// The domains appended to relative names when looking them up.
+    pub search: Vec<String>,
+    /// Relative names with at least this many dots are tried as absolute names before the search list.
+    pub ndots: u8,
+    /// How long to wait for a response from a single name server.
+    pub timeout: Duration,
+    /// How many times to try every name server before giving up.
+    pub attempts: u8,
+    /// Spread queries over the name servers, instead of always starting with the first.
+    pub rotate: bool,
+    /// Include an EDNS(0) OPT record in queries.
+    pub edns0: bool,
+    /// Query over TCP instead of UDP.
+    pub use_vc: bool,
+}
+
+/// The configuration used when resolv.conf is empty, a name server on the local host.
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        ResolverConfig {
+            nameservers: vec![SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::LOCALHOST),
+                DNS_PORT as u16,
+            )],
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+            edns0: false,
+            use_vc: false,
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Parse a configuration in the format of resolv.conf. Lines starting with ';' or '#' are comments,
+    /// and unknown keywords and options are ignored. The last domain or search line sets the search list.
+    /// As in glibc, invalid entries are skipped rather than failing the whole file: name servers with
+    /// an invalid address, domain and search lines without a valid domain, and options with an invalid
+    /// value. The zone ID of an IPv6 name server address, as in "fe80::1%eth0", is dropped.
+    pub fn parse(text: &str) -> ResolverConfig {
+        let mut config = ResolverConfig {
+            nameservers: Vec::new(),
+            ..ResolverConfig::default()
+        };
+
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let keyword = match fields.next() {
+                Some(keyword) if !keyword.starts_with(';') && !keyword.starts_with('#') => keyword,
+                _ => continue,
+            };
+            let arguments: Vec<&str> = fields
+                .take_while(|field| !field.starts_with(';') && !field.starts_with('#'))
+                .collect();
+
+            match keyword {
+                "nameserver" => {
+                    let address = match arguments.first().and_then(|text| parse_address(text)) {
+                        Some(address) => address,
+                        None => continue,
+                    };
+                    if config.nameservers.len() < MAX_NAMESERVERS {
+                        config
+                            .nameservers
+                            .push(SocketAddr::new(address, DNS_PORT as u16));
+                    }
+                }
+                "domain" | "search" => {
+                    // The domain line names a single domain, any further names are ignored.
+                    let count = if keyword == "domain" {
+                        1
+                    } else {
+                        arguments.len()
+                    };
+                    let search: Vec<String> = arguments
+                        .iter()
+                        .take(count)
+                        .filter_map(|domain| {
+                            let domain = normalize_domain_name(&domain.to_string());
+                            domain_name_to_labels(&domain).ok()?;
+                            Some(domain)
+                        })
+                        .take(MAX_SEARCH_DOMAINS)
+                        .collect();
+                    if !search.is_empty() {
+                        config.search = search;
+                    }
+                }
+                "options" => {
+                    for option in arguments {
+                        config.apply_option(option);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if config.nameservers.is_empty() {
+            config.nameservers = ResolverConfig::default().nameservers;
+        }
+
+        config
+    }
+
+    /// Read a configuration from a file in the format of resolv.conf.
+    pub fn from_file(path: &Path) -> Result<ResolverConfig, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+
+        Ok(ResolverConfig::parse(&text))
+    }
+
+    /// Read the system configuration from [RESOLV_CONF_PATH].
+    pub fn system() -> Result<ResolverConfig, String> {
+        ResolverConfig::from_file(Path::new(RESOLV_CONF_PATH))
+    }
+
+    /// Apply an option of an options line. Unknown options and options without a valid number are
+    /// ignored.
+    fn apply_option(&mut self, option: &str) {
+        let (name, value) = match option.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+        let number = value.and_then(|value| value.parse::<u64>().ok());
+
+        match (name, number) {
+            ("ndots", Some(number)) => self.ndots = number.min(MAX_NDOTS as u64) as u8,
+            ("timeout", Some(number)) => {
+                self.timeout = Duration::from_secs(number.clamp(1, MAX_TIMEOUT_SECS))
+            }
+            ("attempts", Some(number)) => {
+                self.attempts = number.clamp(1, MAX_ATTEMPTS as u64) as u8
+            }
+            ("rotate", _) => self.rotate = true,
+            ("edns0", _) => self.edns0 = true,
+            ("use-vc", _) => self.use_vc = true,
+            _ => {}
+        }
+    }
+
+    /// The absolute names to try in turn when looking up domain_name. Absolute names are used as they
+    /// are. Relative names are tried with each search domain appended, and as an absolute name first
+    /// if they have at least ndots dots and last otherwise.
+    pub fn search_names(&self, domain_name: &str) -> Result<Vec<String>, String> {
+        let (labels, absolute) = split_domain_name(domain_name)?;
+        if absolute {
+            return Ok(vec![String::from(domain_name)]);
+        }
+
+        let mut names = self
+            .search
+            .iter()
+            .map(|domain| absolute_domain_name(domain_name, domain))
+            .collect::<Result<Vec<String>, String>>()?;
+        let name = absolute_domain_name(domain_name, ".")?;
+        if labels.len() > self.ndots as usize {
+            names.insert(0, name);
+        } else {
+            names.push(name);
+        }
+
+        Ok(names)
+    }
+
+    /// The options each query to a name server is sent with.
+    pub fn query_options(&self) -> QueryOptions {
+        QueryOptions {
+            timeout: self.timeout,
+            transport: if self.use_vc {
+                Transport::Tcp
+            } else {
+                Transport::Udp
+            },
+            ..QueryOptions::default()
+        }
+    }
+}
+
+/// A stub resolver sending recursive queries to the name servers of a [ResolverConfig]. With use_vc,
+/// the TCP connection to each name server is reused for later queries.
+#[derive(Debug)]
+pub struct Resolver {
+    config: ResolverConfig,
+    next_nameserver: AtomicUsize,
+    connections: Mutex<HashMap<SocketAddr, TcpConnection>>,
+}
+
+impl Resolver {
+    /// Create a resolver with config.
+    pub fn new(config: ResolverConfig) -> Resolver {
+        Resolver {
+            config,
+            next_nameserver: AtomicUsize::new(0),
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a resolver with the system configuration, see [ResolverConfig::system].
+    pub fn system() -> Result<Resolver, String> {
+        Ok(Resolver::new(ResolverConfig::system()?))
+    }
+
+    /// The configuration of the resolver.
+    pub fn config(&self) -> &ResolverConfig {
+        &self.config
+    }
+
+    /// Send a query for the absolute domain_name and rrtype, trying each name server in turn for the
+    /// configured number of attempts. A name server answering SERVFAIL, NOTIMP or REFUSED is passed
+    /// over, and its response returned only if no other name server answers.
+    pub fn query(&self, domain_name: &str, rrtype: u16) -> Result<DnsPacket, String> {
+        let mut query = DnsPacket::new(&String::from(domain_name), rrtype)?;
+        if self.config.edns0 {
+            query.additional.push(DnsResourceRecord::new(
+                String::from("."),
+                DNS_TYPE_OPT,
+                EDNS_UDP_PAYLOAD_SIZE,
+                0,
+                0,
+                DnsResourceRecordData::OPT(Vec::new()),
+            )?);
+            query.update_counts();
+        }
+
+        let nameservers = &self.config.nameservers;
+        if nameservers.is_empty() {
+            return Err("resolver has no name servers".into());
+        }
+        let first = if self.config.rotate {
+            self.next_nameserver.fetch_add(1, Ordering::Relaxed)
+        } else {
+            0
+        };
+
+        let options = self.config.query_options();
+        let mut result = Err(String::from("resolver made no attempts"));
+        for _ in 0..self.config.attempts.max(1) {
+            for index in 0..nameservers.len() {
+                let nameserver = nameservers[(first + index) % nameservers.len()];
+                match self.send(&query, nameserver, &options) {
+                    Ok(response)
+                        if !matches!(
+                            response.header.rcode,
+                            DNS_RCODE_SERVER_ERROR | DNS_RCODE_NOT_IMPLEMENTED | DNS_RCODE_REFUSED
+                        ) =>
+                    {
+                        return Ok(response)
+                    }
+                    Ok(response) => result = Ok(response),
+                    Err(error) if result.is_err() => {
+                        result = Err(format!("{}: {}", nameserver, error))
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Send query to nameserver, over a reused TCP connection if the transport of options is TCP.
+    fn send(
+        &self,
+        query: &DnsPacket,
+        nameserver: SocketAddr,
+        options: &QueryOptions,
+    ) -> Result<DnsPacket, String> {
+        if options.transport != Transport::Tcp {
+            return send_dns_query_with_options(query, &nameserver.to_string(), options);
+        }
+
+        // The connection is taken out while in use, so that other queries open their own.
+        let connection = self
+            .connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&nameserver);
+        let mut connection = match connection {
+            Some(connection) => connection,
+            None => TcpConnection::new(&nameserver.to_string(), options.timeout)?,
+        };
+
+        let response = connection.query(query);
+        self.connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(nameserver, connection);
+        response
+    }
+
+    /// Look up domain_name and rrtype, trying each of its [ResolverConfig::search_names] until one has
+    /// records of rrtype. If none does, returns the first response saying a name exists without
+    /// records of rrtype, or else the last response. The domain name may contain Unicode labels,
+    /// which are converted with IDNA.
+    pub fn lookup(&self, domain_name: &str, rrtype: u16) -> Result<DnsPacket, String> {
+        let domain_name = idna::domain_name_to_ascii(domain_name)?;
+
+        let mut no_data = None;
+        let mut result = Err(format!("no names to look up for {}", domain_name));
+        for name in self.config.search_names(&domain_name)? {
+            result = self.query(&name, rrtype);
+            let response = match &result {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+
+            let canonical_name = response.follow_alias_chain(&name)?;
+            let answered = response.answer.iter().any(|record| {
+                record.rrtype == rrtype && domain_names_equal(&record.name, &canonical_name)
+            });
+            if response.header.rcode == DNS_RCODE_NO_ERROR {
+                if answered {
+                    return result;
+                } else if no_data.is_none() {
+                    no_data = Some(response.clone());
+                }
+            }
+        }
+
+        match no_data {
+            Some(response) => Ok(response),
+            None => result,
+        }
+    }
+}
+
+/// Parse the address of a name server, dropping the zone ID of an IPv6 address.
+fn parse_address(text: &str) -> Option<IpAddr> {
+    match text.split_once('%') {
+        Some((address, _)) => address.parse::<Ipv6Addr>().ok().map(IpAddr::V6),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolver_config() -> Result<(), String> {
+        let config = ResolverConfig::parse(
+            "# generated by a DHCP client\n\
+             nameserver 192.0.2.53\n\
+             nameserver 2001:db8::53 ; secondary\n\
+             domain ignored.example\n\
+             search corp.example.com example.com.\n\
+             sortlist 192.0.2.0/255.255.255.0\n\
+             options ndots:2 timeout:40 attempts:0\n\
+             options rotate edns0 use-vc inet6\n\
+             nameserver 192.0.2.54\n\
+             nameserver 192.0.2.55\n",
+        );
+        assert_eq!(
+            config,
+            ResolverConfig {
+                nameservers: vec![
+                    "192.0.2.53:53".parse().unwrap(),
+                    "[2001:db8::53]:53".parse().unwrap(),
+                    "192.0.2.54:53".parse().unwrap(),
+                ],
+                search: vec!["corp.example.com.".into(), "example.com.".into()],
+                ndots: 2,
+                timeout: Duration::from_secs(30),
+                attempts: 1,
+                rotate: true,
+                edns0: true,
+                use_vc: true,
+            }
+        );
+        assert_eq!(config.query_options().transport, Transport::Tcp);
+
+        assert_eq!(ResolverConfig::parse(""), ResolverConfig::default());
+        assert_eq!(
+            ResolverConfig::parse("search a.example\ndomain b.example\n").search,
+            ["b.example."]
+        );
+
+        // Invalid entries are skipped, keeping the rest of the configuration.
+        let config = ResolverConfig::parse(
+            "nameserver localhost\n\
+             nameserver\n\
+             nameserver fe80::1%eth0\n\
+             nameserver 192.0.2.1%eth0\n\
+             nameserver 192.0.2.53 192.0.2.54\n\
+             search a.example\n\
+             domain b.example c.example\n\
+             search d..example\n\
+             options ndots timeout:x attempts:3\n",
+        );
+        assert_eq!(
+            config.nameservers,
+            [
+                "[fe80::1]:53".parse().unwrap(),
+                "192.0.2.53:53".parse().unwrap()
+            ]
+        );
+        assert_eq!(config.search, ["b.example."]);
+        assert_eq!(config.ndots, 1);
+        assert_eq!(config.timeout, Duration::from_secs(5));
+        assert_eq!(config.attempts, 3);
+
+        let path = std::env::temp_dir().join("rusty_dns_test_resolv.conf");
+        std::fs::write(&path, "nameserver 192.0.2.1\n").map_err(|error| error.to_string())?;
+        let config = ResolverConfig::from_file(&path);
+        std::fs::remove_file(&path).map_err(|error| error.to_string())?;
+        assert_eq!(config?.nameservers, ["192.0.2.1:53".parse().unwrap()]);
+        assert!(ResolverConfig::from_file(Path::new("/nonexistent/resolv.conf")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_names() -> Result<(), String> {
+        let config = ResolverConfig {
+            search: vec!["corp.example.com.".into(), "example.com.".into()],
+            ..ResolverConfig::default()
+        };
+        assert_eq!(
+            config.search_names("www")?,
+            ["www.corp.example.com.", "www.example.com.", "www."]
+        );
+        assert_eq!(
+            config.search_names("www.test")?,
+            [
+                "www.test.",
+                "www.test.corp.example.com.",
+                "www.test.example.com."
+            ]
+        );
+        assert_eq!(config.search_names("www.")?, ["www."]);
+
+        let config = ResolverConfig { ndots: 0, ..config };
+        assert_eq!(config.search_names("www")?[0], "www.");
+        assert!(config.search_names("").is_err());
+
+        Ok(())
+    }
+}
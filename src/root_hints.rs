@@ -0,0 +1,354 @@
+use crate::{
+    classes::*,
+    dns_packet::{dns_resource_record::*, *},
+    domain_name::*,
+    rcodes::*,
+    response_matches_query, send_dns_query_with_options,
+    types::*,
+    zone::parser::*,
+    QueryOptions,
+};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The root hints file published by IANA, as of the 2023 renumbering of b.root-servers.net.
+pub const NAMED_ROOT: &str = r#";       This file holds the information on root name servers needed to
+;       initialize cache of Internet domain name servers
+;       (e.g. reference this file in the "cache  .  <file>"
+;       configuration file of BIND domain name servers).
+;
+;       This file is made available by InterNIC
+;       under anonymous FTP as
+;           file                /domain/named.cache
+;           on server           FTP.INTERNIC.NET
+;       -OR-                    RS.INTERNIC.NET
+;
+;       related version of root zone:     2023120201
+;
+; FORMERLY NS.INTERNIC.NET
+;
+.                        3600000      NS    A.ROOT-SERVERS.NET.
+A.ROOT-SERVERS.NET.      3600000      A     198.41.0.4
+A.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:ba3e::2:30
+;
+; FORMERLY NS1.ISI.EDU
+;
+.                        3600000      NS    B.ROOT-SERVERS.NET.
+B.ROOT-SERVERS.NET.      3600000      A     170.247.170.2
+B.ROOT-SERVERS.NET.      3600000      AAAA  2801:1b8:10::b
+;
+; FORMERLY C.PSI.NET
+;
+.                        3600000      NS    C.ROOT-SERVERS.NET.
+C.ROOT-SERVERS.NET.      3600000      A     192.33.4.12
+C.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:2::c
+;
+; FORMERLY TERP.UMD.EDU
+;
+.                        3600000      NS    D.ROOT-SERVERS.NET.
+D.ROOT-SERVERS.NET.      3600000      A     199.7.91.13
+D.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:2d::d
+;
+; FORMERLY NS.NASA.GOV
+;
+.                        3600000      NS    E.ROOT-SERVERS.NET.
+E.ROOT-SERVERS.NET.      3600000      A     192.203.230.10
+E.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:a8::e
+;
+; FORMERLY NS.ISC.ORG
+;
+.                        3600000      NS    F.ROOT-SERVERS.NET.
+F.ROOT-SERVERS.NET.      3600000      A     192.5.5.241
+F.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:2f::f
+;
+; FORMERLY NS.NIC.DDN.MIL
+;
+.                        3600000      NS    G.ROOT-SERVERS.NET.
+G.ROOT-SERVERS.NET.      3600000      A     192.112.36.4
+G.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:12::d0d
+;
+; FORMERLY AOS.ARL.ARMY.MIL
+;
+.                        3600000      NS    H.ROOT-SERVERS.NET.
+H.ROOT-SERVERS.NET.      3600000      A     198.97.190.53
+H.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:1::53
+;
+; FORMERLY NIC.NORDU.NET
+;
+.                        3600000      NS    I.ROOT-SERVERS.NET.
+I.ROOT-SERVERS.NET.      3600000      A     192.36.148.17
+I.ROOT-SERVERS.NET.      3600000      AAAA  2001:7fe::53
+;
+; OPERATED BY VERISIGN, INC.
+;
+.                        3600000      NS    J.ROOT-SERVERS.NET.
+J.ROOT-SERVERS.NET.      3600000      A     192.58.128.30
+J.ROOT-SERVERS.NET.      3600000      AAAA  2001:503:c27::2:30
+;
+; OPERATED BY RIPE NCC
+;
+.                        3600000      NS    K.ROOT-SERVERS.NET.
+K.ROOT-SERVERS.NET.      3600000      A     193.0.14.129
+K.ROOT-SERVERS.NET.      3600000      AAAA  2001:7fd::1
+;
+; OPERATED BY ICANN
+;
+.                        3600000      NS    L.ROOT-SERVERS.NET.
+L.ROOT-SERVERS.NET.      3600000      A     199.7.83.42
+L.ROOT-SERVERS.NET.      3600000      AAAA  2001:500:9f::42
+;
+; OPERATED BY WIDE
+;
+.                        3600000      NS    M.ROOT-SERVERS.NET.
+M.ROOT-SERVERS.NET.      3600000      A     202.12.27.33
+M.ROOT-SERVERS.NET.      3600000      AAAA  2001:dc3::35
+; END OF FILE
+"#;
+
+/// How long to keep using the hints after priming fails before priming again.
+pub const PRIMING_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A root name server and its addresses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootServer {
+    /// The name of the server, such as "A.ROOT-SERVERS.NET.".
+    pub name: String,
+    /// The IPv4 and IPv6 addresses of the server.
+    pub addresses: Vec<IpAddr>,
+}
+
+/// The root name servers an iterative resolver starts from. Hints from a file are only a starting
+/// point, which priming (RFC 8109) replaces with the current servers as published by the root zone.
+#[derive(Debug, Clone)]
+pub struct RootHints {
+    servers: Vec<RootServer>,
+    ttl: u32,
+    primed_at: Option<Instant>,
+    priming_failed_at: Option<Instant>,
+}
+
+/// The root hints bundled with the library, from [NAMED_ROOT].
+impl Default for RootHints {
+    fn default() -> Self {
+        RootHints::parse(NAMED_ROOT).expect("bundled root hints are valid")
+    }
+}
+
+impl RootHints {
+    /// Build root hints from the NS records of the root and the A and AAAA records of the servers
+    /// they name. Servers without any address are left out.
+    pub fn from_resource_records(
+        resource_records: &[DnsResourceRecord],
+    ) -> Result<RootHints, String> {
+        let mut servers: Vec<RootServer> = Vec::new();
+        let mut ttl = u32::MAX;
+
+        for record in resource_records {
+            match &record.rdata {
+                DnsResourceRecordData::NS(nsdname) if domain_names_equal(&record.name, ".") => {
+                    ttl = ttl.min(record.ttl);
+                    if !servers
+                        .iter()
+                        .any(|server| domain_names_equal(&server.name, nsdname))
+                    {
+                        servers.push(RootServer {
+                            name: nsdname.clone(),
+                            addresses: Vec::new(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // IPv4 addresses come first, as they are reachable from more networks.
+        for rrtype in [DNS_TYPE_A, DNS_TYPE_AAAA].iter() {
+            for record in resource_records
+                .iter()
+                .filter(|record| record.rrtype == *rrtype)
+            {
+                let server = servers
+                    .iter_mut()
+                    .find(|server| domain_names_equal(&server.name, &record.name));
+                match (server, &record.rdata) {
+                    (Some(server), DnsResourceRecordData::A(address)) => {
+                        server.addresses.push((*address).into())
+                    }
+                    (Some(server), DnsResourceRecordData::AAAA(address)) => {
+                        server.addresses.push((*address).into())
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        servers.retain(|server| !server.addresses.is_empty());
+        if servers.is_empty() {
+            return Err("root hints have no name servers with addresses".into());
+        }
+
+        Ok(RootHints {
+            servers,
+            ttl,
+            primed_at: None,
+            priming_failed_at: None,
+        })
+    }
+
+    /// Parse root hints in the master file format of named.root.
+    pub fn parse(text: &str) -> Result<RootHints, String> {
+        RootHints::from_resource_records(&parse_master_file_str(text, ".")?)
+    }
+
+    /// Read root hints from a file in the format of named.root.
+    pub fn from_file(path: &Path) -> Result<RootHints, String> {
+        RootHints::from_resource_records(&parse_master_file(path, ".")?)
+    }
+
+    /// The root name servers.
+    pub fn servers(&self) -> &[RootServer] {
+        &self.servers
+    }
+
+    /// The addresses of all root name servers, IPv4 addresses first.
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        let addresses = self
+            .servers
+            .iter()
+            .flat_map(|server| server.addresses.iter().cloned());
+        let (mut ipv4, ipv6): (Vec<IpAddr>, Vec<IpAddr>) =
+            addresses.partition(|address| address.is_ipv4());
+        ipv4.extend(ipv6);
+
+        ipv4
+    }
+
+    /// The TTL of the root NS records the hints were built from.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// Returns true if the hints have not been primed, or the TTL of the primed records has passed.
+    /// After priming fails, returns false until [PRIMING_RETRY_INTERVAL] has passed.
+    pub fn needs_priming(&self) -> bool {
+        if let Some(failed_at) = self.priming_failed_at {
+            if failed_at.elapsed() < PRIMING_RETRY_INTERVAL {
+                return false;
+            }
+        }
+
+        match self.primed_at {
+            Some(primed_at) => primed_at.elapsed().as_secs() >= self.ttl as u64,
+            None => true,
+        }
+    }
+
+    /// Prime the hints as in RFC 8109, asking each hinted server in turn for the NS records of the
+    /// root until one answers authoritatively, then replacing the hints with that answer and its
+    /// glue. Servers without glue keep their hinted addresses. Queries go to port on each server. If
+    /// no server answers, the hints are kept and the failure is recorded, see [RootHints::needs_priming].
+    pub fn prime(&mut self, port: u16, options: &QueryOptions) -> Result<(), String> {
+        let mut query = DnsPacket::new(&String::from("."), DNS_TYPE_NS)?;
+        query.header.rd = false;
+
+        for address in self.addresses() {
+            let destination = SocketAddr::new(address, port).to_string();
+            let response = match send_dns_query_with_options(&query, &destination, options) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+            if !response_matches_query(&query, &response, false)
+                || !response.header.aa
+                || response.header.rcode != DNS_RCODE_NO_ERROR
+            {
+                continue;
+            }
+
+            let mut resource_records = response.answer.clone();
+            for server in self.servers.iter() {
+                let has_glue = response
+                    .additional
+                    .iter()
+                    .any(|record| domain_names_equal(&record.name, &server.name));
+                if has_glue {
+                    continue;
+                }
+
+                // Without glue, keep the hinted addresses of a server that is still listed.
+                for address in server.addresses.iter() {
+                    let rdata = match address {
+                        IpAddr::V4(address) => DnsResourceRecordData::A(*address),
+                        IpAddr::V6(address) => DnsResourceRecordData::AAAA(*address),
+                    };
+                    resource_records.push(DnsResourceRecord::new(
+                        server.name.clone(),
+                        rdata.rrtype(),
+                        DNS_CLASS_IN,
+                        0,
+                        0,
+                        rdata,
+                    )?);
+                }
+            }
+            resource_records.extend(response.additional.iter().cloned());
+
+            if let Ok(mut primed) = RootHints::from_resource_records(&resource_records) {
+                primed.primed_at = Some(Instant::now());
+                *self = primed;
+                return Ok(());
+            }
+        }
+
+        self.priming_failed_at = Some(Instant::now());
+        Err("No root server answered the priming query".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_hints() -> Result<(), String> {
+        let root_hints = RootHints::default();
+        assert_eq!(root_hints.servers().len(), 13);
+        assert_eq!(root_hints.ttl(), 3600000);
+        assert!(root_hints.needs_priming());
+        assert_eq!(
+            root_hints.servers()[0],
+            RootServer {
+                name: "A.ROOT-SERVERS.NET.".into(),
+                addresses: vec![
+                    "198.41.0.4".parse().unwrap(),
+                    "2001:503:ba3e::2:30".parse().unwrap()
+                ],
+            }
+        );
+
+        let addresses = root_hints.addresses();
+        assert_eq!(addresses.len(), 26);
+        assert!(addresses[..13].iter().all(|address| address.is_ipv4()));
+        assert!(addresses[13..].iter().all(|address| address.is_ipv6()));
+
+        let root_hints = RootHints::parse(
+            ". 60 NS a.test.\n. 30 NS b.test.\n. 60 NS c.test.\n\
+             b.test. 60 AAAA ::1\nA.TEST. 60 A 192.0.2.1\n",
+        )?;
+        let names: Vec<&str> = root_hints
+            .servers()
+            .iter()
+            .map(|server| server.name.as_str())
+            .collect();
+        assert_eq!(names, ["a.test.", "b.test."]);
+        assert_eq!(root_hints.ttl(), 30);
+
+        assert_eq!(
+            RootHints::parse(". 60 NS a.test.\n").unwrap_err(),
+            "root hints have no name servers with addresses"
+        );
+        assert!(RootHints::parse(". 60 NS\n").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,600 @@
+use crate::{
+    dns_packet::{dns_header::*, dns_question::*, dns_resource_record::*, *},
+    domain_name::*,
+    opcodes::*,
+    qclasses::*,
+    qtypes::*,
+    rcodes::*,
+    types::*,
+    zone::*,
+    Transport, EDNS_UDP_PAYLOAD_SIZE, MAX_UDP_MESSAGE_SIZE,
+};
+use std::collections::VecDeque;
+use std::io::{BufRead, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long a TCP connection may stay idle before the server closes it (RFC 7766 section 6.2.3).
+pub const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long writing a response on a TCP connection may block before the server closes it, so that a
+/// client that never reads cannot hold the connection open.
+pub const TCP_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The most TCP connections served at once, further ones wait to be accepted until one is closed.
+pub const MAX_CONCURRENT_TCP_CONNECTIONS: usize = 64;
+
+/// The most UDP queries answered at once, further ones wait for the oldest to be answered.
+const MAX_CONCURRENT_UDP_QUERIES: usize = 64;
+
+/// How often blocked listeners check whether the server is shutting down.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Answers the DNS queries of a server started by [spawn_server].
+pub trait QueryHandler: Send + Sync + 'static {
+    /// Build the response to query.
+    fn respond(&self, query: &DnsPacket) -> DnsPacket;
+
+    /// Handle a query message received over transport, returning the response message to send back.
+    /// Returns None for messages that must not be answered, such as responses or messages too short to
+    /// have a header. Responses over UDP that do not fit the client's limit are truncated, see
+    /// [DnsPacket::serialize_with_limit].
+    fn handle_message(&self, message: &[u8], transport: Transport) -> Option<Vec<u8>> {
+        let header = DnsHeader::parse(message).ok()?;
+        if header.qr {
+            return None;
+        }
+
+        let query = DnsPacket::parse_dns_packet(message);
+        let response = match &query {
+            Ok(query) => self.respond(query),
+            Err(_) => DnsPacket {
+                header: DnsHeader {
+                    qr: true,
+                    aa: false,
+                    tc: false,
+                    ra: false,
+                    z: 0,
+                    rcode: DNS_RCODE_FORMAT_ERROR,
+                    qdcount: 0,
+                    ancount: 0,
+                    nscount: 0,
+                    arcount: 0,
+                    ..header
+                },
+                question: Vec::new(),
+                answer: Vec::new(),
+                authority: Vec::new(),
+                additional: Vec::new(),
+            },
+        };
+
+        // RFC 6891 section 6.2.5, payload sizes below 512 are treated as 512.
+        let limit = match transport {
+            Transport::Udp => query
+                .ok()
+                .and_then(|query| query.opt().map(|opt| opt.class as usize))
+                .unwrap_or(MAX_UDP_MESSAGE_SIZE)
+                .max(MAX_UDP_MESSAGE_SIZE),
+            Transport::Tcp => u16::MAX as usize,
+        };
+
+        response.serialize_with_limit(limit).ok()
+    }
+}
+
+/// A shared handler answers like the handler itself, so that it can still be used while it serves.
+impl<H: QueryHandler> QueryHandler for Arc<H> {
+    fn respond(&self, query: &DnsPacket) -> DnsPacket {
+        self.as_ref().respond(query)
+    }
+}
+
+/// Start the response to query, with the header and question of the query and an OPT record if it has
+/// one (RFC 6891 section 6.1.3). Only EDNS version 0 is supported, so queries with other versions get
+/// a complete BADVERS response.
+pub fn start_response(query: &DnsPacket) -> DnsPacket {
+    let mut response = DnsPacket {
+        header: DnsHeader {
+            qr: true,
+            aa: false,
+            tc: false,
+            ra: false,
+            z: 0,
+            rcode: DNS_RCODE_NO_ERROR,
+            ..query.header.clone()
+        },
+        question: query.question.clone(),
+        answer: Vec::new(),
+        authority: Vec::new(),
+        additional: Vec::new(),
+    };
+
+    if let Some(opt) = query.opt() {
+        let mut ttl = 0;
+        if (opt.ttl >> 16) & 0xFF != 0 {
+            ttl = ((DNS_RCODE_BADVERS >> 4) as u32) << 24;
+        }
+        if let Ok(opt) = DnsResourceRecord::new(
+            String::from("."),
+            DNS_TYPE_OPT,
+            EDNS_UDP_PAYLOAD_SIZE,
+            ttl,
+            0,
+            DnsResourceRecordData::OPT(Vec::new()),
+        ) {
+            response.additional.push(opt);
+        }
+
+        if ttl != 0 {
+            response.header.rcode = DNS_RCODE_BADVERS & 0xF;
+        }
+    }
+
+    response.update_counts();
+    response
+}
+
+/// Serve the queries handler answers on UDP and TCP at address, such as "0.0.0.0:53", until the returned
+/// handle is shut down. The TCP listener uses the same port as the UDP socket, so port 0 picks one for
+/// both. UDP queries are answered concurrently, as are the queries on separate TCP connections, up to
+/// [MAX_CONCURRENT_TCP_CONNECTIONS] of them.
+pub fn spawn_server<H: QueryHandler>(handler: H, address: &str) -> Result<ServerHandle, String> {
+    let udp_socket = UdpSocket::bind(address).map_err(|error| format!("{}: {}", address, error))?;
+    let address = udp_socket.local_addr().map_err(|error| error.to_string())?;
+    let tcp_listener =
+        TcpListener::bind(address).map_err(|error| format!("{}: {}", address, error))?;
+
+    udp_socket
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(|error| error.to_string())?;
+    tcp_listener
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+
+    let handler = Arc::new(handler);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let mut threads = Vec::new();
+    {
+        let (handler, shutdown) = (handler.clone(), shutdown.clone());
+        threads.push(std::thread::spawn(move || {
+            serve_udp(&handler, &udp_socket, &shutdown)
+        }));
+    }
+    {
+        let shutdown = shutdown.clone();
+        threads.push(std::thread::spawn(move || {
+            serve_tcp(&handler, &tcp_listener, &shutdown)
+        }));
+    }
+
+    Ok(ServerHandle {
+        address,
+        shutdown,
+        threads,
+    })
+}
+
+/// An authoritative name server for a set of zones.
+#[derive(Debug, Clone)]
+pub struct AuthoritativeServer {
+    zones: Vec<Zone>,
+}
+
+impl AuthoritativeServer {
+    /// Create a server for zones, which must not contain two zones of the same class and origin.
+    pub fn new(zones: Vec<Zone>) -> Result<AuthoritativeServer, String> {
+        for (index, zone) in zones.iter().enumerate() {
+            if zones[..index].iter().any(|other| {
+                other.class() == zone.class() && domain_names_equal(other.origin(), zone.origin())
+            }) {
+                return Err(format!("zone {} is given more than once", zone.origin()));
+            }
+        }
+
+        Ok(AuthoritativeServer { zones })
+    }
+
+    /// The zones the server is authoritative for.
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// The zone with the longest origin containing qname, if any.
+    fn find_zone(&self, qname: &str, qclass: u16) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| qclass == DNS_QCLASS_ANY || zone.class() == qclass)
+            .filter(|zone| is_subdomain_of(qname, zone.origin()))
+            .max_by_key(|zone| domain_name_wire_length(zone.origin()))
+    }
+
+    /// Fill in the sections of response with the answer from the matching zone, returning the RCODE.
+    fn answer(&self, question: &DnsQuestion, response: &mut DnsPacket) -> u8 {
+        let (qname, qtype) = (question.qname.as_str(), question.qtype);
+        if matches!(qtype, DNS_QTYPE_AXFR | DNS_QTYPE_MAILA | DNS_QTYPE_MAILB) {
+            return DNS_RCODE_NOT_IMPLEMENTED;
+        }
+
+        let zone = match self.find_zone(qname, question.qclass) {
+            Some(zone) => zone,
+            None => return DNS_RCODE_REFUSED,
+        };
+        let result = match zone.lookup(qname, qtype) {
+            Ok(result) => result,
+            Err(_) => return DNS_RCODE_SERVER_ERROR,
+        };
+
+        // A referral is not authoritative data, RFC 1034 section 4.3.2.
+        response.header.aa = result.outcome != LookupOutcome::Referral;
+        response.answer = result.answer.clone();
+        response.authority = result.authority.clone();
+        let opt = std::mem::take(&mut response.additional);
+        response.additional = result.additional.clone();
+        response.additional.extend(opt);
+
+        result.rcode()
+    }
+
+    /// Serve queries on UDP and TCP at address until the returned handle is shut down, see
+    /// [spawn_server].
+    pub fn spawn(self, address: &str) -> Result<ServerHandle, String> {
+        spawn_server(self, address)
+    }
+}
+
+impl QueryHandler for AuthoritativeServer {
+    /// Queries for names outside of the server's zones are REFUSED, opcodes other than QUERY are
+    /// NOTIMP, and queries without exactly one question are FORMERR.
+    fn respond(&self, query: &DnsPacket) -> DnsPacket {
+        let mut response = start_response(query);
+        if response.extended_rcode() == DNS_RCODE_BADVERS as u16 {
+            return response;
+        }
+
+        response.header.rcode = match (query.header.opcode, query.question.as_slice()) {
+            (DNS_OPCODE_QUERY, [question]) => self.answer(question, &mut response),
+            (DNS_OPCODE_QUERY, _) => DNS_RCODE_FORMAT_ERROR,
+            _ => DNS_RCODE_NOT_IMPLEMENTED,
+        };
+
+        response.update_counts();
+        response
+    }
+}
+
+/// A running server started by [spawn_server].
+#[derive(Debug)]
+pub struct ServerHandle {
+    address: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// The address the server listens on, for both UDP and TCP.
+    pub fn local_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Stop accepting queries, finish the ones in progress and close all connections.
+    pub fn shutdown(self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.wait();
+    }
+
+    /// Wait until the server has been shut down.
+    pub fn wait(self) {
+        for thread in self.threads {
+            let _ = thread.join();
+        }
+    }
+
+    /// Serve until a line of input reads "quit", then shut down. If input ends first, such as standard
+    /// input without a terminal, keep serving. Such a server cannot then be shut down gracefully, only
+    /// stopped by ending the process, which drops the queries in progress.
+    pub fn serve_until_quit<R: BufRead>(self, input: R) {
+        for line in input.lines() {
+            match line {
+                Ok(line) if line.trim() == "quit" => {
+                    self.shutdown();
+                    return;
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+
+        self.wait();
+    }
+}
+
+fn serve_udp<H: QueryHandler>(handler: &Arc<H>, socket: &UdpSocket, shutdown: &AtomicBool) {
+    let mut buf = [0; 65535];
+    let mut workers: VecDeque<JoinHandle<()>> = VecDeque::new();
+
+    while !shutdown.load(Ordering::SeqCst) {
+        let (amt, source) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(_) => continue,
+        };
+        let socket = match socket.try_clone() {
+            Ok(socket) => socket,
+            Err(_) => continue,
+        };
+
+        // Wait for the oldest query to be answered rather than have too many in progress.
+        workers.retain(|worker| !worker.is_finished());
+        if workers.len() >= MAX_CONCURRENT_UDP_QUERIES {
+            if let Some(worker) = workers.pop_front() {
+                let _ = worker.join();
+            }
+        }
+
+        let (handler, message) = (handler.clone(), buf[..amt].to_vec());
+        workers.push_back(std::thread::spawn(move || {
+            if let Some(response) = handler.handle_message(&message, Transport::Udp) {
+                let _ = socket.send_to(&response, source);
+            }
+        }));
+    }
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+fn serve_tcp<H: QueryHandler>(
+    handler: &Arc<H>,
+    listener: &TcpListener,
+    shutdown: &Arc<AtomicBool>,
+) {
+    let mut connections: Vec<JoinHandle<()>> = Vec::new();
+
+    while !shutdown.load(Ordering::SeqCst) {
+        connections.retain(|connection| !connection.is_finished());
+        if connections.len() >= MAX_CONCURRENT_TCP_CONNECTIONS {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let (handler, shutdown) = (handler.clone(), shutdown.clone());
+                connections.push(std::thread::spawn(move || {
+                    serve_tcp_connection(handler.as_ref(), stream, &shutdown)
+                }));
+            }
+            Err(ref error) if error.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL)
+            }
+            Err(_) => {}
+        }
+    }
+
+    for connection in connections {
+        let _ = connection.join();
+    }
+}
+
+/// Answer the queries on a TCP connection, each preceded by its two octet length (RFC 1035 section
+/// 4.2.2). Queries may be pipelined and arrive in any number of reads.
+fn serve_tcp_connection<H: QueryHandler>(
+    handler: &H,
+    mut stream: TcpStream,
+    shutdown: &AtomicBool,
+) {
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err()
+        || stream.set_write_timeout(Some(TCP_WRITE_TIMEOUT)).is_err()
+    {
+        return;
+    }
+
+    let mut received: Vec<u8> = Vec::new();
+    let mut buf = [0; 4096];
+    let mut idle = Duration::from_secs(0);
+
+    while !shutdown.load(Ordering::SeqCst) && idle < TCP_IDLE_TIMEOUT {
+        match stream.read(&mut buf) {
+            Ok(0) => return,
+            Ok(amt) => {
+                received.extend_from_slice(&buf[..amt]);
+                idle = Duration::from_secs(0);
+            }
+            Err(ref error)
+                if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                idle += POLL_INTERVAL;
+                continue;
+            }
+            Err(_) => return,
+        }
+
+        while received.len() >= 2 {
+            let length = u16::from_be_bytes([received[0], received[1]]) as usize;
+            if received.len() < 2 + length {
+                break;
+            }
+
+            let message: Vec<u8> = received.drain(..2 + length).skip(2).collect();
+            if let Some(response) = handler.handle_message(&message, Transport::Tcp) {
+                let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+                framed.extend_from_slice(&response);
+                if stream.write_all(&framed).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::parser::*;
+
+    fn test_server() -> Result<AuthoritativeServer, String> {
+        let parent = "$TTL 60\n@ SOA ns1 host 1 2 3 4 5\n@ NS ns1\nns1 A 192.0.2.1\n\
+                      www A 192.0.2.2\nsub NS ns1.sub\nns1.sub A 192.0.2.3\n";
+        let child = "$TTL 60\n@ SOA ns1 host 1 2 3 4 5\n@ NS ns1\nns1 A 192.0.2.3\n\
+                     www A 192.0.2.4\n";
+        let mut big = String::from("$TTL 60\n@ SOA ns1 host 1 2 3 4 5\n");
+        for index in 0..40 {
+            big.push_str(&format!("many A 192.0.2.{}\n", index));
+        }
+
+        AuthoritativeServer::new(vec![
+            Zone::new(
+                "example.com.",
+                parse_master_file_str(parent, "example.com.")?,
+            )?,
+            Zone::new(
+                "sub.example.com.",
+                parse_master_file_str(child, "sub.example.com.")?,
+            )?,
+            Zone::new("big.example.", parse_master_file_str(&big, "big.example.")?)?,
+        ])
+    }
+
+    fn query(qname: &str, qtype: u16) -> Result<DnsPacket, String> {
+        DnsPacket::new(&String::from(qname), qtype)
+    }
+
+    #[test]
+    fn test_respond() -> Result<(), String> {
+        let server = test_server()?;
+
+        let request = query("www.example.com.", DNS_TYPE_A)?;
+        let response = server.respond(&request);
+        assert_eq!(response.header.id, request.header.id);
+        assert!(response.header.qr && response.header.aa && response.header.rd);
+        assert_eq!(response.header.rcode, DNS_RCODE_NO_ERROR);
+        assert_eq!(response.header.ancount, 1);
+        assert_eq!(
+            response.answer[0].to_string(),
+            "www.example.com. 60 IN A 192.0.2.2"
+        );
+
+        // The longest matching zone answers.
+        let response = server.respond(&query("www.sub.example.com.", DNS_TYPE_A)?);
+        assert!(response.header.aa);
+        assert_eq!(
+            response.answer[0].to_string(),
+            "www.sub.example.com. 60 IN A 192.0.2.4"
+        );
+
+        let response = server.respond(&query("missing.example.com.", DNS_TYPE_A)?);
+        assert!(response.header.aa);
+        assert_eq!(response.header.rcode, DNS_RCODE_NAME_ERROR);
+        assert_eq!(response.header.nscount, 1);
+
+        let response = server.respond(&query("www.example.net.", DNS_TYPE_A)?);
+        assert!(!response.header.aa);
+        assert_eq!(response.header.rcode, DNS_RCODE_REFUSED);
+
+        let mut request = query("www.example.com.", DNS_TYPE_A)?;
+        request.header.opcode = DNS_OPCODE_STATUS;
+        assert_eq!(
+            server.respond(&request).header.rcode,
+            DNS_RCODE_NOT_IMPLEMENTED
+        );
+
+        let mut request = query("www.example.com.", DNS_TYPE_A)?;
+        request.question.push(request.question[0].clone());
+        assert_eq!(
+            server.respond(&request).header.rcode,
+            DNS_RCODE_FORMAT_ERROR
+        );
+
+        let request = query("example.com.", DNS_QTYPE_AXFR)?;
+        assert_eq!(
+            server.respond(&request).header.rcode,
+            DNS_RCODE_NOT_IMPLEMENTED
+        );
+
+        // EDNS(0) queries get an OPT record back, and other versions BADVERS.
+        let mut request = query("www.example.com.", DNS_TYPE_A)?;
+        request.additional.push(DnsResourceRecord::new(
+            String::from("."),
+            DNS_TYPE_OPT,
+            4096,
+            1 << 16,
+            0,
+            DnsResourceRecordData::OPT(Vec::new()),
+        )?);
+        request.update_counts();
+        let response = server.respond(&request);
+        assert_eq!(response.extended_rcode(), DNS_RCODE_BADVERS as u16);
+        assert!(response.answer.is_empty());
+        request.additional[0].ttl = 0;
+        let response = server.respond(&request);
+        assert_eq!(response.extended_rcode(), DNS_RCODE_NO_ERROR as u16);
+        assert_eq!(
+            response.opt().map(|opt| opt.class),
+            Some(EDNS_UDP_PAYLOAD_SIZE)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_handle_message() -> Result<(), String> {
+        let server = test_server()?;
+
+        // A referral to the child zone is not authoritative.
+        let server = AuthoritativeServer::new(vec![server.zones()[0].clone()])?;
+        let message = query("www.sub.example.com.", DNS_TYPE_A)?.serialize()?;
+        let response = server
+            .handle_message(&message, Transport::Udp)
+            .ok_or("no response")?;
+        let response = DnsPacket::parse_dns_packet(&response)?;
+        assert!(!response.header.aa);
+        assert_eq!(response.authority.len(), 1);
+        assert_eq!(response.additional.len(), 1);
+
+        // Responses to responses and to messages without a header are not sent.
+        let mut request = query("www.example.com.", DNS_TYPE_A)?;
+        request.header.qr = true;
+        assert!(server
+            .handle_message(&request.serialize()?, Transport::Udp)
+            .is_none());
+        assert!(server.handle_message(&[0; 11], Transport::Udp).is_none());
+
+        // Messages that cannot be parsed are FORMERR, keeping the ID.
+        let mut message = query("www.example.com.", DNS_TYPE_A)?.serialize()?;
+        message.truncate(20);
+        let response = server
+            .handle_message(&message, Transport::Udp)
+            .ok_or("no response")?;
+        let response = DnsPacket::parse_dns_packet(&response)?;
+        assert_eq!(response.header.rcode, DNS_RCODE_FORMAT_ERROR);
+        assert_eq!(
+            response.header.id,
+            u16::from_be_bytes([message[0], message[1]])
+        );
+        assert!(response.question.is_empty());
+
+        // Responses too large for UDP are truncated, but not over TCP.
+        let server = test_server()?;
+        let message = query("many.big.example.", DNS_TYPE_A)?.serialize()?;
+        let response = server
+            .handle_message(&message, Transport::Udp)
+            .ok_or("no response")?;
+        assert!(response.len() <= MAX_UDP_MESSAGE_SIZE);
+        let response = DnsPacket::parse_dns_packet(&response)?;
+        assert!(response.header.tc);
+        assert!(response.answer.is_empty());
+        let response = server
+            .handle_message(&message, Transport::Tcp)
+            .ok_or("no response")?;
+        let response = DnsPacket::parse_dns_packet(&response)?;
+        assert!(!response.header.tc);
+        assert_eq!(response.answer.len(), 40);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    dns_packet::{dns_header::*, *},
+    response_matches_query,
+};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// How long a client keeps an unused connection open. This is below the idle timeout of 10 seconds
+/// RFC 7766 section 6.2.3 suggests for servers, so the client usually closes first.
+pub const TCP_CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A client connection to a DNS server over TCP, each message preceded by its two octet length
+/// (RFC 1035 section 4.2.2). The connection is opened when first needed and reused for later queries
+/// until it has been idle for the idle timeout. Queries may be pipelined, sending several before
+/// receiving their responses, which the server may send in any order (RFC 7766 section 6.2.1.1).
+#[derive(Debug)]
+pub struct TcpConnection {
+    destination: SocketAddr,
+    timeout: Duration,
+    idle_timeout: Duration,
+    stream: Option<TcpStream>,
+    last_used: Instant,
+    /// Received octets not yet making up a whole message.
+    received: Vec<u8>,
+    /// IDs of queries sent without their response having been received.
+    outstanding: HashSet<u16>,
+    /// Responses, or the errors parsing them, received while waiting for the response to another
+    /// query.
+    responses: HashMap<u16, Result<DnsPacket, String>>,
+}
+
+impl TcpConnection {
+    /// Create a connection to destination, such as "192.0.2.1:53", waiting up to timeout for each
+    /// response. Nothing is sent until the first query.
+    pub fn new(destination: &str, timeout: Duration) -> Result<TcpConnection, String> {
+        let destination = destination
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or(format!("Could not resolve destination {}", destination))?;
+
+        Ok(TcpConnection {
+            destination,
+            timeout,
+            idle_timeout: TCP_CLIENT_IDLE_TIMEOUT,
+            stream: None,
+            last_used: Instant::now(),
+            received: Vec::new(),
+            outstanding: HashSet::new(),
+            responses: HashMap::new(),
+        })
+    }
+
+    /// Set how long the connection may be unused before it is closed, instead of
+    /// [TCP_CLIENT_IDLE_TIMEOUT].
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = idle_timeout;
+    }
+
+    /// The address of the server.
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// Returns true if the connection is open.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Close the connection, forgetting any outstanding queries. The next query opens it again.
+    pub fn close(&mut self) {
+        self.stream = None;
+        self.received.clear();
+        self.outstanding.clear();
+        self.responses.clear();
+    }
+
+    /// Send query and wait for its response. If a reused connection turns out to have been closed by
+    /// the server, the query is sent again once on a new connection.
+    pub fn query(&mut self, query: &DnsPacket) -> Result<DnsPacket, String> {
+        let reused = self.is_connected() && !self.idle();
+
+        let response = match self.send(query).and_then(|_| self.receive(query.header.id)) {
+            Err(_) if reused && !self.is_connected() => {
+                self.send(query)?;
+                self.receive(query.header.id)?
+            }
+            result => result?,
+        };
+
+        if !response_matches_query(query, &response, false) {
+            return Err(format!(
+                "Response question {:?} does not match query question {:?}",
+                response.question, query.question
+            ));
+        }
+
+        Ok(response)
+    }
+
+    /// Send query without waiting for its response, which [TcpConnection::receive] returns. The ID of
+    /// query must differ from those of the other outstanding queries.
+    pub fn send(&mut self, query: &DnsPacket) -> Result<(), String> {
+        let id = query.header.id;
+        if self.outstanding.contains(&id) {
+            return Err(format!("a query with ID {} is already outstanding", id));
+        }
+
+        let message = query.serialize()?;
+        let length = u16::try_from(message.len())
+            .map_err(|_| String::from("DNS packet is too long for TCP"))?;
+        let mut framed = length.to_be_bytes().to_vec();
+        framed.extend_from_slice(&message);
+
+        let destination = self.destination;
+        let stream = self.stream()?;
+        let written = stream.write_all(&framed);
+        if let Err(error) = written {
+            self.close();
+            return Err(format!(
+                "Client could not send data to {}: {}",
+                destination, error
+            ));
+        }
+
+        self.outstanding.insert(id);
+        self.last_used = Instant::now();
+        Ok(())
+    }
+
+    /// Wait for the response to the outstanding query with ID id. Responses to other outstanding
+    /// queries that arrive first are kept for them, and responses to no outstanding query dropped. A
+    /// response that cannot be parsed is an error for the query with its ID. On timeout the query is
+    /// no longer outstanding, and a late response to it is dropped.
+    pub fn receive(&mut self, id: u16) -> Result<DnsPacket, String> {
+        if let Some(response) = self.responses.remove(&id) {
+            return response;
+        } else if !self.outstanding.contains(&id) {
+            return Err(format!("no query with ID {} is outstanding", id));
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            while let Some(message) = self.next_message() {
+                let response_id = match DnsHeader::parse(&message) {
+                    Ok(header) => header.id,
+                    Err(_) => continue,
+                };
+                let response = DnsPacket::parse_dns_packet(&message).map_err(|error| {
+                    format!(
+                        "Could not parse response from {}: {}",
+                        self.destination, error
+                    )
+                });
+                if !self.outstanding.remove(&response_id) {
+                    continue;
+                } else if response_id == id {
+                    self.last_used = Instant::now();
+                    return response;
+                }
+                self.responses.insert(response_id, response);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                self.outstanding.remove(&id);
+                return Err(format!(
+                    "Client timed out waiting for a response from {}",
+                    self.destination
+                ));
+            }
+            self.read(remaining)?;
+        }
+    }
+
+    fn idle(&self) -> bool {
+        self.outstanding.is_empty() && self.last_used.elapsed() >= self.idle_timeout
+    }
+
+    /// The open stream, closing an idle one and opening a new one if needed.
+    fn stream(&mut self) -> Result<&mut TcpStream, String> {
+        if self.is_connected() && self.idle() {
+            self.close();
+        }
+
+        if self.stream.is_none() {
+            let stream =
+                TcpStream::connect_timeout(&self.destination, self.timeout).map_err(|error| {
+                    format!(
+                        "Client could not connect to {}: {}",
+                        self.destination, error
+                    )
+                })?;
+            stream
+                .set_write_timeout(Some(self.timeout))
+                .and_then(|_| stream.set_nodelay(true))
+                .map_err(|_| String::from("Could not set query socket timeout"))?;
+            self.stream = Some(stream);
+        }
+
+        self.stream
+            .as_mut()
+            .ok_or_else(|| String::from("connection is closed"))
+    }
+
+    /// Read whatever arrives within timeout into the receive buffer. Closes the connection if the
+    /// server closed or reset it.
+    fn read(&mut self, timeout: Duration) -> Result<(), String> {
+        let destination = self.destination;
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return Err(format!("connection to {} is closed", destination)),
+        };
+
+        let mut buf = [0; 4096];
+        let result = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.read(&mut buf));
+        match result {
+            Ok(0) => {
+                self.close();
+                Err(format!("{} closed the connection", destination))
+            }
+            Ok(amt) => {
+                self.received.extend_from_slice(&buf[..amt]);
+                Ok(())
+            }
+            Err(ref error)
+                if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                Ok(())
+            }
+            Err(ref error) if error.kind() == ErrorKind::Interrupted => Ok(()),
+            Err(error) => {
+                self.close();
+                Err(format!(
+                    "Client could not recieve data from {}: {}",
+                    destination, error
+                ))
+            }
+        }
+    }
+
+    /// Take the next whole message from the receive buffer.
+    fn next_message(&mut self) -> Option<Vec<u8>> {
+        if self.received.len() < 2 {
+            return None;
+        }
+
+        let length = u16::from_be_bytes([self.received[0], self.received[1]]) as usize;
+        if self.received.len() < 2 + length {
+            return None;
+        }
+
+        Some(self.received.drain(..2 + length).skip(2).collect())
+    }
+}
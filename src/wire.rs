@@ -0,0 +1,397 @@
+use crate::domain_name::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether writers created with [WireWriter::new] use message compression.
+static MESSAGE_COMPRESSION: AtomicBool = AtomicBool::new(true);
+
+/// Turn message compression on or off for every message serialized afterwards with
+/// [WireWriter::new] or [crate::dns_packet::DnsPacket::serialize]. It is on by default. The setting
+/// is shared by the whole process, so it affects messages serialized on every thread.
+pub fn set_message_compression(enabled: bool) {
+    MESSAGE_COMPRESSION.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if message compression is on, see [set_message_compression].
+pub fn message_compression() -> bool {
+    MESSAGE_COMPRESSION.load(Ordering::Relaxed)
+}
+
+/// A bounds checked cursor for reading DNS wire format data out of a DNS message.
+///
+/// The reader always holds the entire message so compression pointers can be followed,
+/// but never reads past its limit. Every read either returns the data or an error, never panics.
+#[derive(Debug, Clone)]
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+    position: usize,
+    limit: usize,
+}
+
+impl<'a> WireReader<'a> {
+    /// Create a reader over an entire DNS message, starting at its first byte.
+    pub fn new(buf: &'a [u8]) -> WireReader<'a> {
+        WireReader {
+            buf,
+            position: 0,
+            limit: buf.len(),
+        }
+    }
+
+    /// Create a reader over a DNS message, reading from start up to, but not including, limit.
+    pub fn with_bounds(
+        buf: &'a [u8],
+        start: usize,
+        limit: usize,
+    ) -> Result<WireReader<'a>, String> {
+        if start > limit || limit > buf.len() {
+            return Err(format!(
+                "start={} limit={} are outside of buf.len()={}",
+                start,
+                limit,
+                buf.len()
+            ));
+        }
+
+        Ok(WireReader {
+            buf,
+            position: start,
+            limit,
+        })
+    }
+
+    /// The offset of the next byte to be read, from the start of the message.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of bytes left before the limit of this reader.
+    pub fn remaining(&self) -> usize {
+        self.limit - self.position
+    }
+
+    /// Returns true if every byte up to the limit of this reader has been read.
+    pub fn is_empty(&self) -> bool {
+        self.position == self.limit
+    }
+
+    /// Read a single octet.
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    /// Read a big-endian 16 bit value.
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.read_bytes(2)?;
+        Ok((bytes[0] as u16) << 8 | bytes[1] as u16)
+    }
+
+    /// Read a big-endian 32 bit value.
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.read_bytes(4)?;
+        Ok((bytes[0] as u32) << 24
+            | (bytes[1] as u32) << 16
+            | (bytes[2] as u32) << 8
+            | bytes[3] as u32)
+    }
+
+    /// Read exactly len bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.remaining() {
+            return Err(format!(
+                "cannot read {} bytes at offset {}, only {} remain",
+                len,
+                self.position,
+                self.remaining()
+            ));
+        }
+
+        let bytes = &self.buf[self.position..self.position + len];
+        self.position += len;
+
+        Ok(bytes)
+    }
+
+    /// Read every byte left before the limit of this reader.
+    pub fn read_remaining(&mut self) -> &'a [u8] {
+        let bytes = &self.buf[self.position..self.limit];
+        self.position = self.limit;
+
+        bytes
+    }
+
+    /// Read a domain name, following any compression pointers into earlier parts of the message.
+    pub fn read_name(&mut self) -> Result<String, String> {
+        let (domain_name, end) = parse_domain_name(self.buf, self.position, self.limit)?;
+        if end > self.limit {
+            return Err(format!(
+                "domain name at offset {} runs past limit {}",
+                self.position, self.limit
+            ));
+        }
+        self.position = end;
+
+        Ok(domain_name)
+    }
+
+    /// Read a character-string, a length octet followed by that many octets.
+    pub fn read_character_string(&mut self) -> Result<Vec<u8>, String> {
+        let length = self.read_u8()? as usize;
+        Ok(Vec::from(self.read_bytes(length)?))
+    }
+
+    /// Split off a reader for the next len bytes, such as the RDATA of a resource record.
+    /// This reader is advanced past those bytes.
+    pub fn sub_reader(&mut self, len: usize) -> Result<WireReader<'a>, String> {
+        if len > self.remaining() {
+            return Err(format!(
+                "cannot limit reader to {} bytes at offset {}, only {} remain",
+                len,
+                self.position,
+                self.remaining()
+            ));
+        }
+
+        let sub_reader = WireReader {
+            buf: self.buf,
+            position: self.position,
+            limit: self.position + len,
+        };
+        self.position += len;
+
+        Ok(sub_reader)
+    }
+
+    /// Returns an error if any bytes before the limit of this reader were left unread.
+    pub fn finish(&self) -> Result<(), String> {
+        if !self.is_empty() {
+            return Err(format!(
+                "{} unexpected trailing bytes at offset {}",
+                self.remaining(),
+                self.position
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Run write with a writer continuing buf, whose names were written at domain_name_offsets, and leave
+/// what it wrote in them. This backs the serialize methods that take a buffer and offsets.
+pub fn write_with_parts<F>(
+    buf: &mut Vec<u8>,
+    domain_name_offsets: &mut HashMap<String, u16>,
+    write: F,
+) -> Result<(), String>
+where
+    F: FnOnce(&mut WireWriter) -> Result<(), String>,
+{
+    let mut writer =
+        WireWriter::from_parts(std::mem::take(buf), std::mem::take(domain_name_offsets));
+    let result = write(&mut writer);
+    let (written, offsets) = writer.into_parts();
+    *buf = written;
+    *domain_name_offsets = offsets;
+
+    result
+}
+
+/// A writer building a DNS message in wire format, tracking domain names for message compression.
+#[derive(Debug, Default)]
+pub struct WireWriter {
+    buf: Vec<u8>,
+    domain_name_offsets: HashMap<String, u16>,
+    uncompressed: bool,
+}
+
+impl WireWriter {
+    /// Create a writer that uses message compression wherever it is allowed, unless it has been turned
+    /// off with [set_message_compression].
+    pub fn new() -> WireWriter {
+        WireWriter::with_compression(message_compression())
+    }
+
+    /// Create a writer that only uses message compression if compress is true.
+    pub fn with_compression(compress: bool) -> WireWriter {
+        WireWriter {
+            uncompressed: !compress,
+            ..WireWriter::default()
+        }
+    }
+
+    /// Create a writer continuing buf, whose names were written at domain_name_offsets.
+    pub fn from_parts(buf: Vec<u8>, domain_name_offsets: HashMap<String, u16>) -> WireWriter {
+        WireWriter {
+            buf,
+            domain_name_offsets,
+            uncompressed: !message_compression(),
+        }
+    }
+
+    /// Consume the writer, returning the bytes written and the offsets of the names written.
+    pub fn into_parts(self) -> (Vec<u8>, HashMap<String, u16>) {
+        (self.buf, self.domain_name_offsets)
+    }
+
+    /// The number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns true if nothing has been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// The bytes written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Consume the writer, returning the bytes written.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Discard everything written after the first len bytes, along with the names written there.
+    pub fn truncate(&mut self, len: usize) {
+        self.buf.truncate(len);
+        self.domain_name_offsets
+            .retain(|_, offset| (*offset as usize) < len);
+    }
+
+    /// Write a single octet.
+    pub fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    /// Write a big-endian 16 bit value.
+    pub fn write_u16(&mut self, value: u16) {
+        self.buf.push(((value >> 8) & 0xFF) as u8);
+        self.buf.push((value & 0xFF) as u8);
+    }
+
+    /// Write a big-endian 32 bit value.
+    pub fn write_u32(&mut self, value: u32) {
+        self.buf.push(((value >> 24) & 0xFF) as u8);
+        self.buf.push(((value >> 16) & 0xFF) as u8);
+        self.buf.push(((value >> 8) & 0xFF) as u8);
+        self.buf.push((value & 0xFF) as u8);
+    }
+
+    /// Write bytes as they are.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Overwrite a big-endian 16 bit value that was already written at index, such as a length placeholder.
+    pub fn set_u16(&mut self, index: usize, value: u16) -> Result<(), String> {
+        if index + 2 > self.buf.len() {
+            return Err(format!(
+                "cannot set u16 at index {}, only {} bytes written",
+                index,
+                self.buf.len()
+            ));
+        }
+
+        self.buf[index] = ((value >> 8) & 0xFF) as u8;
+        self.buf[index + 1] = (value & 0xFF) as u8;
+
+        Ok(())
+    }
+
+    /// Write a domain name. It is compressed against earlier names only if compress is true
+    /// and this writer was not created without compression.
+    pub fn write_name(&mut self, domain_name: &str, compress: bool) -> Result<(), String> {
+        serialize_domain_name_with_compression(
+            domain_name,
+            &mut self.buf,
+            &mut self.domain_name_offsets,
+            compress && !self.uncompressed,
+        )
+    }
+
+    /// Write a character-string, a length octet followed by the octets themselves.
+    pub fn write_character_string(&mut self, character_string: &[u8]) -> Result<(), String> {
+        crate::dns_packet::dns_resource_record::serialize_character_string(
+            character_string,
+            &mut self.buf,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_examples::*;
+
+    #[test]
+    fn test_wire_reader() -> Result<(), String> {
+        let buf = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE];
+        let mut reader = WireReader::new(&buf);
+
+        assert_eq!(reader.read_u16()?, 0x1234);
+        assert_eq!(reader.read_u32()?, 0x56789ABC);
+        assert_eq!(reader.remaining(), 1);
+        assert!(reader.read_u16().is_err());
+        assert_eq!(reader.read_u8()?, 0xDE);
+        assert!(reader.is_empty());
+        assert!(reader.read_u8().is_err());
+
+        let mut reader = WireReader::new(&buf);
+        let mut sub_reader = reader.sub_reader(3)?;
+        assert_eq!(reader.position(), 3);
+        assert_eq!(sub_reader.read_u16()?, 0x1234);
+        assert!(sub_reader.finish().is_err());
+        assert!(sub_reader.read_u16().is_err());
+        assert_eq!(sub_reader.read_remaining(), &[0x56]);
+        assert!(sub_reader.finish().is_ok());
+        assert!(reader.sub_reader(5).is_err());
+
+        let mut reader = WireReader::with_bounds(NAME_COMPRESSION_QUERY, 32, 42)?;
+        assert_eq!(reader.read_name()?, "pointer.www.google.com.");
+        assert!(reader.is_empty());
+
+        // The name itself fits, but not within the limit given.
+        let mut reader = WireReader::with_bounds(BASIC_QUERY, 12, 20)?;
+        assert!(reader.read_name().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wire_writer() -> Result<(), String> {
+        let mut writer = WireWriter::new();
+
+        writer.write_u16(0x1234);
+        writer.write_u32(0x56789ABC);
+        writer.write_name(&String::from("www.google.com."), true)?;
+        writer.write_name(&String::from("pointer.www.google.com."), true)?;
+        writer.write_name(&String::from("google.com."), false)?;
+        writer.set_u16(0, 0xFFFF)?;
+        assert!(writer.set_u16(writer.len() - 1, 0).is_err());
+
+        let correct: &[u8] = &[
+            0xFF, 0xFF, 0x56, 0x78, 0x9A, 0xBC, // values
+            0x03, 0x77, 0x77, 0x77, 0x06, 0x67, 0x6F, 0x6F, 0x67, 0x6C, 0x65, 0x03, 0x63, 0x6F,
+            0x6D, 0x00, // www.google.com.
+            0x07, 0x70, 0x6F, 0x69, 0x6E, 0x74, 0x65, 0x72, 0xC0,
+            0x06, // pointer.www.google.com.
+            0x06, 0x67, 0x6F, 0x6F, 0x67, 0x6C, 0x65, 0x03, 0x63, 0x6F, 0x6D,
+            0x00, // google.com. uncompressed
+        ];
+        assert_eq!(writer.as_bytes(), correct);
+
+        // Names in discarded bytes are no longer compression targets.
+        writer.truncate(6);
+        writer.write_name(&String::from("pointer.www.google.com."), true)?;
+        assert_eq!(writer.len(), 6 + 24);
+
+        let mut writer = WireWriter::with_compression(false);
+        writer.write_name(&String::from("google.com."), true)?;
+        writer.write_name(&String::from("google.com."), true)?;
+        assert_eq!(writer.len(), 24);
+
+        Ok(())
+    }
+}
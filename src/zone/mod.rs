@@ -0,0 +1,509 @@
+/// Parsing of RFC 1035 master files into resource records.
+pub mod parser;
+/// Writing of resource records as RFC 1035 master files.
+pub mod writer;
+
+use crate::{
+    dns_packet::dns_resource_record::*, domain_name::*, qtypes::*, rcodes::*, types::*,
+    MAX_ALIAS_CHAIN_LENGTH,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// How a name was found, or not, in a zone by [Zone::lookup].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LookupOutcome {
+    /// The answer section holds the records of the requested type, or ends in a CNAME leaving the zone.
+    Answer,
+    /// The name is at or below a delegation point. The authority section holds the NS records of the
+    /// child zone, and the additional section any glue for them.
+    Referral,
+    /// The name exists but has no records of the requested type. The authority section holds the SOA.
+    NoData,
+    /// The name does not exist. The authority section holds the SOA.
+    NameError,
+}
+
+/// The result of [Zone::lookup], with the records for each section of a response.
+#[derive(Debug, Clone)]
+pub struct LookupResult {
+    /// How the name was found.
+    pub outcome: LookupOutcome,
+    /// CNAME records followed within the zone, then the records of the requested type.
+    pub answer: Vec<DnsResourceRecord>,
+    /// The SOA record for negative answers, or the NS records of a referral.
+    pub authority: Vec<DnsResourceRecord>,
+    /// Address records in the zone for the name servers of a referral or an NS answer.
+    pub additional: Vec<DnsResourceRecord>,
+}
+
+impl LookupResult {
+    /// The RCODE of a response carrying this result, NXDOMAIN for a name error and otherwise NOERROR.
+    pub fn rcode(&self) -> u8 {
+        match self.outcome {
+            LookupOutcome::NameError => DNS_RCODE_NAME_ERROR,
+            _ => DNS_RCODE_NO_ERROR,
+        }
+    }
+}
+
+/// A node of the zone's tree, holding its RRsets by type. Empty non-terminals have no RRsets.
+type Node = BTreeMap<u16, Vec<DnsResourceRecord>>;
+
+/// The key of a node, the labels of its name with ASCII letters lowercased.
+fn node_key(domain_name: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut labels = domain_name_to_labels(domain_name)?;
+    for label in labels.iter_mut() {
+        label.make_ascii_lowercase();
+    }
+
+    Ok(labels)
+}
+
+/// An authoritative zone held in memory, answering queries as described in RFC 1034 section 4.3.2.
+#[derive(Debug, Clone)]
+pub struct Zone {
+    origin: String,
+    class: u16,
+    soa: DnsResourceRecord,
+    nodes: HashMap<Vec<Vec<u8>>, Node>,
+}
+
+impl Zone {
+    /// Create the zone at origin from its resource records, which must include exactly one SOA record
+    /// at the origin, all be at or below the origin, share the class of the SOA record, and not have
+    /// other data alongside a CNAME record.
+    pub fn new(origin: &str, resource_records: Vec<DnsResourceRecord>) -> Result<Zone, String> {
+        let origin_key = node_key(origin)?;
+
+        let mut soa_records = resource_records
+            .iter()
+            .filter(|resource_record| resource_record.rrtype == DNS_TYPE_SOA);
+        let soa = match (soa_records.next(), soa_records.next()) {
+            (Some(soa), None) if domain_names_equal(&soa.name, origin) => soa.clone(),
+            (Some(soa), None) => {
+                return Err(format!("SOA record {} is not at the origin", soa.name));
+            }
+            (None, _) => return Err(format!("zone {} has no SOA record", origin)),
+            (Some(_), Some(_)) => return Err(format!("zone {} has several SOA records", origin)),
+        };
+
+        let mut nodes: HashMap<Vec<Vec<u8>>, Node> = HashMap::new();
+        for resource_record in resource_records {
+            let key = node_key(&resource_record.name)?;
+            if !key.ends_with(&origin_key) {
+                return Err(format!(
+                    "{} is outside of zone {}",
+                    resource_record.name, origin
+                ));
+            } else if resource_record.class != soa.class {
+                return Err(format!(
+                    "{} has a different class than zone {}",
+                    resource_record.name, origin
+                ));
+            } else if resource_record.rrtype == DNS_TYPE_OPT {
+                return Err("OPT pseudo-RRs cannot be part of a zone".into());
+            }
+
+            // Every ancestor up to the origin exists, as an empty non-terminal if it has no records.
+            for depth in 1..=key.len() - origin_key.len() {
+                nodes.entry(key[depth..].to_vec()).or_default();
+            }
+            nodes
+                .entry(key)
+                .or_default()
+                .entry(resource_record.rrtype)
+                .or_default()
+                .push(resource_record);
+        }
+
+        for node in nodes.values() {
+            match node.get(&DNS_TYPE_CNAME) {
+                Some(cnames) if cnames.len() > 1 || node.len() > 1 => {
+                    return Err(format!(
+                        "{} has other data alongside its CNAME record",
+                        cnames[0].name
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Zone {
+            origin: String::from(origin),
+            class: soa.class,
+            soa,
+            nodes,
+        })
+    }
+
+    /// Load the zone at origin from the master file at path.
+    pub fn from_master_file(path: &Path, origin: &str) -> Result<Zone, String> {
+        Zone::new(origin, parser::parse_master_file(path, origin)?)
+    }
+
+    /// The origin of the zone.
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// The class of the zone.
+    pub fn class(&self) -> u16 {
+        self.class
+    }
+
+    /// The SOA record of the zone.
+    pub fn soa(&self) -> &DnsResourceRecord {
+        &self.soa
+    }
+
+    /// The records of the given type at domain_name, including any glue or data below delegation points.
+    pub fn rrset(&self, domain_name: &str, rrtype: u16) -> Option<&[DnsResourceRecord]> {
+        let node = self.nodes.get(&node_key(domain_name).ok()?)?;
+        node.get(&rrtype).map(Vec::as_slice)
+    }
+
+    /// All records of the zone, in no particular order.
+    pub fn resource_records(&self) -> impl Iterator<Item = &DnsResourceRecord> {
+        self.nodes.values().flat_map(|node| node.values().flatten())
+    }
+
+    /// Look up qname and qtype in the zone, following CNAME records within it, stopping at delegation
+    /// points and matching wildcards as in RFC 4592. Returns an error if qname is not in the zone.
+    pub fn lookup(&self, qname: &str, qtype: u16) -> Result<LookupResult, String> {
+        let origin_key = node_key(&self.origin)?;
+        if !node_key(qname)?.ends_with(&origin_key) {
+            return Err(format!("{} is outside of zone {}", qname, self.origin));
+        }
+
+        let mut result = LookupResult {
+            outcome: LookupOutcome::Answer,
+            answer: Vec::new(),
+            authority: Vec::new(),
+            additional: Vec::new(),
+        };
+        let mut visited: Vec<Vec<Vec<u8>>> = Vec::new();
+        let mut name = String::from(qname);
+
+        for _ in 0..MAX_ALIAS_CHAIN_LENGTH {
+            let key = node_key(&name)?;
+            if !key.ends_with(&origin_key) || visited.contains(&key) {
+                // The CNAME chain leaves the zone or loops, so it is up to the resolver to continue.
+                return Ok(result);
+            }
+            visited.push(key.clone());
+
+            // Look for a delegation point from just below the origin down to the name itself.
+            for depth in (0..key.len() - origin_key.len()).rev() {
+                let node = self.nodes.get(&key[depth..]);
+                if let Some(ns_records) = node.and_then(|node| node.get(&DNS_TYPE_NS)) {
+                    result.outcome = LookupOutcome::Referral;
+                    result.authority.extend(ns_records.iter().cloned());
+                    result.additional.extend(self.glue(ns_records));
+                    return Ok(result);
+                }
+            }
+
+            let node = match self.nodes.get(&key) {
+                Some(node) => node,
+                None => match self.wildcard_node(&key, origin_key.len()) {
+                    Some(node) => node,
+                    None => {
+                        result.outcome = LookupOutcome::NameError;
+                        result.authority.push(self.negative_soa());
+                        return Ok(result);
+                    }
+                },
+            };
+
+            // Records synthesized from a wildcard take the name being looked up as their owner.
+            let with_owner = |records: &[DnsResourceRecord]| -> Vec<DnsResourceRecord> {
+                records
+                    .iter()
+                    .map(|record| DnsResourceRecord {
+                        name: name.clone(),
+                        ..record.clone()
+                    })
+                    .collect()
+            };
+
+            if qtype == DNS_QTYPE_ANY && !node.is_empty() {
+                for records in node.values() {
+                    result.answer.extend(with_owner(records));
+                }
+                return Ok(result);
+            } else if let Some(records) = node.get(&qtype) {
+                result.answer.extend(with_owner(records));
+                if qtype == DNS_TYPE_NS {
+                    result.additional.extend(self.glue(records));
+                }
+                return Ok(result);
+            }
+
+            match node.get(&DNS_TYPE_CNAME).and_then(|cnames| cnames.first()) {
+                Some(cname) => {
+                    result.answer.push(DnsResourceRecord {
+                        name: name.clone(),
+                        ..cname.clone()
+                    });
+                    name = match &cname.rdata {
+                        DnsResourceRecordData::CNAME(target) => target.clone(),
+                        _ => return Err(format!("{} has invalid CNAME data", cname.name)),
+                    };
+                }
+                None => {
+                    result.outcome = LookupOutcome::NoData;
+                    result.authority.push(self.negative_soa());
+                    return Ok(result);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// The wildcard node matching the non-existent name key, the "*" child of its closest encloser.
+    fn wildcard_node(&self, key: &[Vec<u8>], origin_length: usize) -> Option<&Node> {
+        let closest_encloser = (1..=key.len() - origin_length)
+            .map(|depth| &key[depth..])
+            .find(|ancestor| self.nodes.contains_key(*ancestor))?;
+
+        let mut wildcard = vec![b"*".to_vec()];
+        wildcard.extend_from_slice(closest_encloser);
+        self.nodes.get(&wildcard)
+    }
+
+    /// The address records in the zone for the name servers named by ns_records.
+    fn glue(&self, ns_records: &[DnsResourceRecord]) -> Vec<DnsResourceRecord> {
+        let mut glue = Vec::new();
+        for ns_record in ns_records {
+            if let DnsResourceRecordData::NS(nsdname) = &ns_record.rdata {
+                for rrtype in [DNS_TYPE_A, DNS_TYPE_AAAA].iter() {
+                    if let Some(records) = self.rrset(nsdname, *rrtype) {
+                        glue.extend(records.iter().cloned());
+                    }
+                }
+            }
+        }
+
+        glue
+    }
+
+    /// The SOA record for a negative answer, with the TTL negative answers may be cached for (RFC 2308).
+    fn negative_soa(&self) -> DnsResourceRecord {
+        let mut soa = self.soa.clone();
+        if let DnsResourceRecordData::SOA((_, _, _, _, _, _, minimum)) = soa.rdata {
+            soa.ttl = soa.ttl.min(minimum);
+        }
+
+        soa
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_ZONE: &str = r#"$ORIGIN example.com.
+$TTL 3600
+@           SOA     ns1 hostmaster 1 7200 900 1209600 300
+            NS      ns1
+ns1         A       192.0.2.53
+www         A       192.0.2.80
+            AAAA    2001:db8::80
+alias       CNAME   www
+chain       CNAME   alias
+external    CNAME   www.example.net.
+loop1       CNAME   loop2
+loop2       CNAME   loop1
+a.b.c       TXT     "empty non-terminals b.c and c"
+*           TXT     "wildcard"
+*.wild      CNAME   www
+sub         TXT     "sub has no wildcard"
+x.sub       TXT     "x"
+child       NS      ns.child
+            NS      ns.example.net.
+ns.child    A       192.0.2.54
+"#;
+
+    fn lookup(
+        zone: &Zone,
+        qname: &str,
+        qtype: u16,
+    ) -> Result<(LookupOutcome, Vec<String>), String> {
+        let result = zone.lookup(qname, qtype)?;
+        let records = result
+            .answer
+            .iter()
+            .chain(result.authority.iter())
+            .chain(result.additional.iter())
+            .map(|record| record.to_string())
+            .collect();
+
+        Ok((result.outcome, records))
+    }
+
+    #[test]
+    fn test_lookup() -> Result<(), String> {
+        let zone = Zone::new(
+            "example.com.",
+            parser::parse_master_file_str(EXAMPLE_ZONE, "example.com.")?,
+        )?;
+        let soa = "example.com. 300 IN SOA ns1.example.com. hostmaster.example.com. 1 7200 900 1209600 300";
+
+        assert_eq!(
+            lookup(&zone, "WWW.example.com.", DNS_TYPE_A)?,
+            (
+                LookupOutcome::Answer,
+                vec!["WWW.example.com. 3600 IN A 192.0.2.80".into()]
+            )
+        );
+        assert_eq!(
+            lookup(&zone, "chain.example.com.", DNS_TYPE_AAAA)?,
+            (
+                LookupOutcome::Answer,
+                vec![
+                    "chain.example.com. 3600 IN CNAME alias.example.com.".into(),
+                    "alias.example.com. 3600 IN CNAME www.example.com.".into(),
+                    "www.example.com. 3600 IN AAAA 2001:db8::80".into(),
+                ]
+            )
+        );
+        assert_eq!(
+            lookup(&zone, "alias.example.com.", DNS_TYPE_CNAME)?,
+            (
+                LookupOutcome::Answer,
+                vec!["alias.example.com. 3600 IN CNAME www.example.com.".into()]
+            )
+        );
+        assert_eq!(
+            lookup(&zone, "external.example.com.", DNS_TYPE_A)?,
+            (
+                LookupOutcome::Answer,
+                vec!["external.example.com. 3600 IN CNAME www.example.net.".into()]
+            )
+        );
+        assert_eq!(lookup(&zone, "loop1.example.com.", DNS_TYPE_A)?.1.len(), 2);
+        assert_eq!(
+            lookup(&zone, "www.example.com.", DNS_TYPE_MX)?,
+            (LookupOutcome::NoData, vec![soa.into()])
+        );
+        assert_eq!(
+            lookup(&zone, "b.c.example.com.", DNS_TYPE_A)?,
+            (LookupOutcome::NoData, vec![soa.into()])
+        );
+        assert_eq!(
+            lookup(&zone, "b.c.example.com.", DNS_QTYPE_ANY)?,
+            (LookupOutcome::NoData, vec![soa.into()])
+        );
+        assert_eq!(lookup(&zone, "www.example.com.", DNS_QTYPE_ANY)?.1.len(), 2);
+
+        // Wildcards match names that do not exist below their closest encloser.
+        assert_eq!(
+            lookup(&zone, "a.missing.example.com.", DNS_TYPE_TXT)?,
+            (
+                LookupOutcome::Answer,
+                vec!["a.missing.example.com. 3600 IN TXT \"wildcard\"".into()]
+            )
+        );
+        assert_eq!(
+            lookup(&zone, "missing.example.com.", DNS_TYPE_A)?,
+            (LookupOutcome::NoData, vec![soa.into()])
+        );
+        assert_eq!(
+            lookup(&zone, "host.wild.example.com.", DNS_TYPE_A)?,
+            (
+                LookupOutcome::Answer,
+                vec![
+                    "host.wild.example.com. 3600 IN CNAME www.example.com.".into(),
+                    "www.example.com. 3600 IN A 192.0.2.80".into(),
+                ]
+            )
+        );
+        assert_eq!(
+            lookup(&zone, "y.sub.example.com.", DNS_TYPE_TXT)?,
+            (LookupOutcome::NameError, vec![soa.into()])
+        );
+        assert_eq!(
+            lookup(&zone, "d.b.c.example.com.", DNS_TYPE_TXT)?,
+            (LookupOutcome::NameError, vec![soa.into()])
+        );
+        assert_eq!(
+            zone.lookup("y.sub.example.com.", DNS_TYPE_TXT)?.rcode(),
+            DNS_RCODE_NAME_ERROR
+        );
+
+        // Names at and below a delegation point are referred to the child zone, with glue.
+        let referral = vec![
+            "child.example.com. 3600 IN NS ns.child.example.com.".into(),
+            "child.example.com. 3600 IN NS ns.example.net.".into(),
+            "ns.child.example.com. 3600 IN A 192.0.2.54".into(),
+        ];
+        assert_eq!(
+            lookup(&zone, "www.child.example.com.", DNS_TYPE_A)?,
+            (LookupOutcome::Referral, referral.clone())
+        );
+        assert_eq!(
+            lookup(&zone, "ns.child.example.com.", DNS_TYPE_A)?,
+            (LookupOutcome::Referral, referral.clone())
+        );
+        assert_eq!(
+            lookup(&zone, "example.com.", DNS_TYPE_NS)?,
+            (
+                LookupOutcome::Answer,
+                vec![
+                    "example.com. 3600 IN NS ns1.example.com.".into(),
+                    "ns1.example.com. 3600 IN A 192.0.2.53".into(),
+                ]
+            )
+        );
+
+        assert!(zone.lookup("www.example.net.", DNS_TYPE_A).is_err());
+        assert_eq!(zone.resource_records().count(), 18);
+        assert_eq!(
+            zone.rrset("NS.child.example.com.", DNS_TYPE_A)
+                .map(|records| records.len()),
+            Some(1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_zone_errors() -> Result<(), String> {
+        let cases = [
+            ("www A 192.0.2.1\n", "zone example.com. has no SOA record"),
+            (
+                "@ SOA ns1 host 1 2 3 4 5\nwww SOA ns1 host 1 2 3 4 5\n",
+                "zone example.com. has several SOA records",
+            ),
+            (
+                "www SOA ns1 host 1 2 3 4 5\n",
+                "SOA record www.example.com. is not at the origin",
+            ),
+            (
+                "@ SOA ns1 host 1 2 3 4 5\nwww.example.net. A 192.0.2.1\n",
+                "www.example.net. is outside of zone example.com.",
+            ),
+            (
+                "@ SOA ns1 host 1 2 3 4 5\nwww CH A 192.0.2.1\n",
+                "www.example.com. has a different class than zone example.com.",
+            ),
+            (
+                "@ SOA ns1 host 1 2 3 4 5\nwww CNAME @\nwww A 192.0.2.1\n",
+                "www.example.com. has other data alongside its CNAME record",
+            ),
+        ];
+
+        for (text, error) in cases.iter() {
+            let text = format!("$TTL 60\n{}", text);
+            let resource_records = parser::parse_master_file_str(&text, "example.com.")?;
+            match Zone::new("example.com.", resource_records) {
+                Err(result) => assert_eq!(&result, error),
+                Ok(_) => panic!("{:?} created a zone without error", text),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,733 @@
+use crate::{classes::*, dns_packet::dns_resource_record::*, domain_name::*, mnemonics::*};
+use std::path::{Path, PathBuf};
+
+/// The maximum depth of nested $INCLUDE directives, which also stops include loops.
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A whitespace separated field of a master file, with any enclosing quotes removed.
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    quoted: bool,
+    line: usize,
+    column: usize,
+}
+
+/// The tokens of one entry of a master file, which may span several lines within parentheses.
+#[derive(Debug, Clone)]
+struct Entry {
+    tokens: Vec<Token>,
+    /// True if the entry started with whitespace, so the owner name of the previous entry applies.
+    owner_omitted: bool,
+}
+
+/// Split master file text into entries, handling comments, parentheses, quotes and escapes.
+/// Errors are returned along with their line and column.
+fn tokenize(text: &str) -> Result<Vec<Entry>, (usize, usize, String)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut entries = Vec::new();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut owner_omitted = false;
+    let mut open_parenthesis: Option<(usize, usize)> = None;
+    let (mut line, mut column) = (1, 1);
+    let mut index = 0;
+
+    while index < chars.len() {
+        let ch = chars[index];
+
+        if column == 1 && open_parenthesis.is_none() && tokens.is_empty() {
+            owner_omitted = ch == ' ' || ch == '\t';
+        }
+
+        match ch {
+            '\n' => {
+                if open_parenthesis.is_none() && !tokens.is_empty() {
+                    entries.push(Entry {
+                        tokens: std::mem::take(&mut tokens),
+                        owner_omitted,
+                    });
+                }
+                line += 1;
+                column = 1;
+                index += 1;
+                continue;
+            }
+            ' ' | '\t' | '\r' => {}
+            ';' => {
+                while index + 1 < chars.len() && chars[index + 1] != '\n' {
+                    index += 1;
+                }
+            }
+            '(' => {
+                if open_parenthesis.is_some() {
+                    return Err((line, column, "nested '(' is not allowed".into()));
+                }
+                open_parenthesis = Some((line, column));
+            }
+            ')' => {
+                if open_parenthesis.take().is_none() {
+                    return Err((line, column, "')' without a matching '('".into()));
+                }
+            }
+            '"' => {
+                let start_column = column;
+                let mut text = String::new();
+                loop {
+                    index += 1;
+                    column += 1;
+                    match chars.get(index) {
+                        Some('"') => break,
+                        Some('\\') if chars.get(index + 1).is_some_and(|&ch| ch != '\n') => {
+                            text.push('\\');
+                            text.push(chars[index + 1]);
+                            index += 1;
+                            column += 1;
+                        }
+                        Some('\n') | None => {
+                            return Err((line, start_column, "unterminated quoted string".into()));
+                        }
+                        Some(&ch) => text.push(ch),
+                    }
+                }
+
+                tokens.push(Token {
+                    text,
+                    quoted: true,
+                    line,
+                    column: start_column,
+                });
+            }
+            _ => {
+                let start_column = column;
+                let mut text = String::new();
+                while let Some(&ch) = chars.get(index) {
+                    if ch.is_whitespace() || matches!(ch, ';' | '(' | ')' | '"') {
+                        break;
+                    } else if ch == '\\' && chars.get(index + 1).is_some_and(|&ch| ch != '\n') {
+                        text.push('\\');
+                        text.push(chars[index + 1]);
+                        index += 2;
+                        column += 2;
+                    } else {
+                        text.push(ch);
+                        index += 1;
+                        column += 1;
+                    }
+                }
+
+                tokens.push(Token {
+                    text,
+                    quoted: false,
+                    line,
+                    column: start_column,
+                });
+                continue;
+            }
+        }
+
+        index += 1;
+        column += 1;
+    }
+
+    if let Some((line, column)) = open_parenthesis {
+        return Err((line, column, "'(' without a matching ')'".into()));
+    } else if !tokens.is_empty() {
+        entries.push(Entry {
+            tokens,
+            owner_omitted,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// A parser for RFC 1035 section 5 master files, producing the resource records they describe.
+///
+/// Supports the $ORIGIN, $TTL (RFC 2308), $INCLUDE and BIND $GENERATE directives, `@` for the
+/// origin, relative domain names, owner names, TTLs and classes carried over from previous
+/// entries, parentheses spanning lines, comments, quoted strings with escapes, and the RFC 3597
+/// generic TYPE, CLASS and `\#` RDATA syntax. Errors name the file, line and column they were found at.
+#[derive(Debug, Clone)]
+pub struct MasterFileParser {
+    origin: String,
+    default_ttl: Option<u32>,
+    last_ttl: Option<u32>,
+    last_class: u16,
+    last_owner: Option<String>,
+    include_depth: usize,
+}
+
+impl MasterFileParser {
+    /// Create a parser for a zone with the given origin, which must be an absolute domain name.
+    pub fn new(origin: &str) -> Result<MasterFileParser, String> {
+        domain_name_to_labels(origin)?;
+
+        Ok(MasterFileParser {
+            origin: String::from(origin),
+            default_ttl: None,
+            last_ttl: None,
+            last_class: DNS_CLASS_IN,
+            last_owner: None,
+            include_depth: 0,
+        })
+    }
+
+    /// The current origin, which $ORIGIN directives change as the master file is parsed.
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Parse the master file at path. $INCLUDE paths are relative to the directory of the file.
+    pub fn parse_file(&mut self, path: &Path) -> Result<Vec<DnsResourceRecord>, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        let directory = path.parent().map_or(PathBuf::from("."), Path::to_path_buf);
+
+        self.parse_str(&text, &path.display().to_string(), &directory)
+    }
+
+    /// Parse master file text, naming it source in errors. $INCLUDE paths are relative to directory.
+    pub fn parse_str(
+        &mut self,
+        text: &str,
+        source: &str,
+        directory: &Path,
+    ) -> Result<Vec<DnsResourceRecord>, String> {
+        let entries = tokenize(text).map_err(|(line, column, error)| {
+            format!("{}:{}:{}: {}", source, line, column, error)
+        })?;
+
+        let mut resource_records = Vec::new();
+        for entry in entries {
+            self.parse_entry(&entry, source, directory, &mut resource_records)?;
+        }
+
+        Ok(resource_records)
+    }
+
+    fn parse_entry(
+        &mut self,
+        entry: &Entry,
+        source: &str,
+        directory: &Path,
+        resource_records: &mut Vec<DnsResourceRecord>,
+    ) -> Result<(), String> {
+        let tokens = &entry.tokens;
+        let error_at = |token: &Token, error: String| {
+            format!("{}:{}:{}: {}", source, token.line, token.column, error)
+        };
+
+        let first = &tokens[0];
+        if !entry.owner_omitted && !first.quoted && first.text.starts_with('$') {
+            return self.parse_directive(tokens, source, directory, resource_records);
+        }
+
+        let mut index = 0;
+        let owner = if entry.owner_omitted {
+            self.last_owner
+                .clone()
+                .ok_or_else(|| error_at(first, "no previous owner name to use".into()))?
+        } else {
+            index += 1;
+            absolute_domain_name(&first.text, &self.origin)
+                .map_err(|error| error_at(first, error))?
+        };
+
+        let mut ttl = None;
+        let mut class = None;
+        let rrtype = loop {
+            let token = tokens.get(index).ok_or_else(|| {
+                error_at(
+                    &tokens[tokens.len() - 1],
+                    "missing resource record type".into(),
+                )
+            })?;
+            index += 1;
+
+            if ttl.is_none() && token.text.starts_with(|ch: char| ch.is_ascii_digit()) {
+                ttl = Some(parse_ttl(&token.text).map_err(|error| error_at(token, error))?);
+            } else if let (None, Ok(value)) = (class, class_from_string(&token.text)) {
+                class = Some(value);
+            } else {
+                break type_from_string(&token.text).map_err(|error| error_at(token, error))?;
+            }
+        };
+
+        let ttl = ttl.or(self.default_ttl).or(self.last_ttl).ok_or_else(|| {
+            error_at(
+                first,
+                "no TTL given and no $TTL or previous TTL to use".into(),
+            )
+        })?;
+        let class = class.unwrap_or(self.last_class);
+
+        let rdata_tokens = &tokens[index..];
+        let fields: Vec<&str> = rdata_tokens
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect();
+        let rdata = DnsResourceRecordData::from_presentation(rrtype, &fields, &self.origin)
+            .map_err(|error| error_at(rdata_tokens.first().unwrap_or(&tokens[index - 1]), error))?;
+        let rdlength = rdata.to_bytes()?.len() as u16;
+
+        resource_records.push(DnsResourceRecord::new(
+            owner.clone(),
+            rrtype,
+            class,
+            ttl,
+            rdlength,
+            rdata,
+        )?);
+
+        self.last_owner = Some(owner);
+        self.last_ttl = Some(ttl);
+        self.last_class = class;
+
+        Ok(())
+    }
+
+    fn parse_directive(
+        &mut self,
+        tokens: &[Token],
+        source: &str,
+        directory: &Path,
+        resource_records: &mut Vec<DnsResourceRecord>,
+    ) -> Result<(), String> {
+        let directive = &tokens[0];
+        let error_at = |token: &Token, error: String| {
+            format!("{}:{}:{}: {}", source, token.line, token.column, error)
+        };
+        let argument = |index: usize| {
+            tokens.get(index).ok_or_else(|| {
+                error_at(
+                    directive,
+                    format!("{} is missing an argument", directive.text),
+                )
+            })
+        };
+
+        let max_tokens = match directive.text.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let origin = argument(1)?;
+                self.origin = absolute_domain_name(&origin.text, &self.origin)
+                    .map_err(|error| error_at(origin, error))?;
+                2
+            }
+            "$TTL" => {
+                let ttl = argument(1)?;
+                self.default_ttl =
+                    Some(parse_ttl(&ttl.text).map_err(|error| error_at(ttl, error))?);
+                2
+            }
+            "$INCLUDE" => {
+                let path = argument(1)?;
+                if self.include_depth >= MAX_INCLUDE_DEPTH {
+                    return Err(error_at(
+                        path,
+                        format!("more than {} nested $INCLUDE directives", MAX_INCLUDE_DEPTH),
+                    ));
+                }
+
+                // The included file starts with the given or current origin, and does not
+                // change the origin or defaults of this file.
+                let mut include_parser = self.clone();
+                include_parser.include_depth += 1;
+                if let Some(origin) = tokens.get(2) {
+                    include_parser.origin = absolute_domain_name(&origin.text, &self.origin)
+                        .map_err(|error| error_at(origin, error))?;
+                }
+
+                let included = include_parser
+                    .parse_file(&directory.join(&path.text))
+                    .map_err(|error| error_at(path, format!("in $INCLUDE: {}", error)))?;
+                resource_records.extend(included);
+                3
+            }
+            "$GENERATE" => {
+                let range = argument(1)?;
+                argument(4)?;
+                let (start, stop, step) =
+                    parse_generate_range(&range.text).map_err(|error| error_at(range, error))?;
+
+                // Each value expands the remaining fields into an entry parsed like any other.
+                let mut value = start;
+                loop {
+                    let mut entry_tokens = Vec::with_capacity(tokens.len() - 2);
+                    for token in &tokens[2..] {
+                        entry_tokens.push(Token {
+                            text: expand_generate_template(&token.text, value)
+                                .map_err(|error| error_at(token, error))?,
+                            ..token.clone()
+                        });
+                    }
+                    let entry = Entry {
+                        tokens: entry_tokens,
+                        owner_omitted: false,
+                    };
+                    self.parse_entry(&entry, source, directory, resource_records)?;
+
+                    match value.checked_add(step) {
+                        Some(next) if next <= stop => value = next,
+                        _ => break,
+                    }
+                }
+                return Ok(());
+            }
+            _ => {
+                return Err(error_at(
+                    directive,
+                    format!("unknown directive {}", directive.text),
+                ))
+            }
+        };
+
+        match tokens.get(max_tokens) {
+            Some(token) => Err(error_at(token, format!("unexpected field {}", token.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parse a $GENERATE range of the form start-stop or start-stop/step.
+fn parse_generate_range(text: &str) -> Result<(u32, u32, u32), String> {
+    let invalid = || format!("invalid $GENERATE range {}", text);
+
+    let (range, step) = match text.split_once('/') {
+        Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+        None => (text, 1),
+    };
+    let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+    let start: u32 = start.parse().map_err(|_| invalid())?;
+    let stop: u32 = stop.parse().map_err(|_| invalid())?;
+
+    if start > stop || step == 0 {
+        return Err(invalid());
+    }
+
+    Ok((start, stop, step))
+}
+
+/// Substitute value into a $GENERATE template. `$` is replaced by the value and
+/// `${offset,width,base}` by the value plus offset, zero padded to width, in base d, o, x, X, or
+/// n and N for reversed nibbles separated by dots. `\$` is left as an escaped literal `$`.
+fn expand_generate_template(template: &str, value: u32) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut chars = template.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                expanded.push(ch);
+                if let Some(escaped) = chars.next() {
+                    expanded.push(escaped);
+                }
+            }
+            '$' if chars.as_str().starts_with('{') => {
+                let modifiers = &chars.as_str()[1..];
+                let end = modifiers
+                    .find('}')
+                    .ok_or_else(|| format!("unterminated modifier in {}", template))?;
+                expanded.push_str(&format_generate_value(&modifiers[..end], value)?);
+                chars = modifiers[end + 1..].chars();
+            }
+            '$' => expanded.push_str(&value.to_string()),
+            _ => expanded.push(ch),
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn format_generate_value(modifiers: &str, value: u32) -> Result<String, String> {
+    let invalid = || format!("invalid modifier ${{{}}}", modifiers);
+
+    let mut parts = modifiers.split(',');
+    let offset: i64 = parts.next().unwrap_or("").parse().map_err(|_| invalid())?;
+    let width: usize = match parts.next() {
+        Some(width) => width.parse().map_err(|_| invalid())?,
+        None => 0,
+    };
+    let base = parts.next().unwrap_or("d");
+    if parts.next().is_some() || width > 255 {
+        return Err(invalid());
+    }
+
+    let value = value as i64 + offset;
+    if !(0..=u32::MAX as i64).contains(&value) {
+        return Err(format!("${{{}}} is out of range", modifiers));
+    }
+
+    match base {
+        "d" => Ok(format!("{:01$}", value, width)),
+        "o" => Ok(format!("{:01$o}", value, width)),
+        "x" => Ok(format!("{:01$x}", value, width)),
+        "X" => Ok(format!("{:01$X}", value, width)),
+        "n" | "N" => {
+            // Least significant nibble first, with dots counting towards the width.
+            let mut value = value;
+            let mut width = width;
+            let mut nibbles = String::new();
+            loop {
+                let nibble = std::char::from_digit((value & 0xF) as u32, 16).unwrap_or('0');
+                nibbles.push(if base == "N" {
+                    nibble.to_ascii_uppercase()
+                } else {
+                    nibble
+                });
+                value >>= 4;
+                width = width.saturating_sub(1);
+
+                if width == 0 && value == 0 {
+                    break;
+                }
+                nibbles.push('.');
+                width = width.saturating_sub(1);
+                if width == 0 && value == 0 {
+                    break;
+                }
+            }
+            Ok(nibbles)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Parse the master file at path for the zone with the given origin.
+pub fn parse_master_file(path: &Path, origin: &str) -> Result<Vec<DnsResourceRecord>, String> {
+    MasterFileParser::new(origin)?.parse_file(path)
+}
+
+/// Parse master file text for the zone with the given origin.
+/// $INCLUDE paths are relative to the current directory.
+pub fn parse_master_file_str(text: &str, origin: &str) -> Result<Vec<DnsResourceRecord>, String> {
+    MasterFileParser::new(origin)?.parse_str(text, "<input>", Path::new("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_ZONE: &str = r#"$ORIGIN example.com.
+$TTL 1h ; default TTL for records without one
+@       IN  SOA ns1 hostmaster (
+                2024010101 ; serial
+                2h         ; refresh
+                15m        ; retry
+                2w         ; expire
+                300 )      ; minimum
+        IN  NS  ns1
+        IN  NS  ns2.example.net.
+        IN  MX  10 mail
+ns1     600 A   192.0.2.53
+mail    IN 300  A   192.0.2.25
+        AAAA    2001:db8::25
+www     CNAME   @
+txt     TXT     "quoted; not a comment" unquoted "escaped \"quote\" \\ \059"
+_sip._tcp SRV   0 5 5060 sip
+*       TXT     "wildcard"
+svc     TYPE65  \# 3 000100
+$ORIGIN sub
+host    CH  A   \# 4 C0000201
+"#;
+
+    #[test]
+    fn test_parse_master_file() -> Result<(), String> {
+        let resource_records = parse_master_file_str(EXAMPLE_ZONE, "example.com.")?;
+        let lines: Vec<String> = resource_records
+            .iter()
+            .map(|record| record.to_string())
+            .collect();
+
+        let correct = [
+            "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 2024010101 7200 900 1209600 300",
+            "example.com. 3600 IN NS ns1.example.com.",
+            "example.com. 3600 IN NS ns2.example.net.",
+            "example.com. 3600 IN MX 10 mail.example.com.",
+            "ns1.example.com. 600 IN A 192.0.2.53",
+            "mail.example.com. 300 IN A 192.0.2.25",
+            "mail.example.com. 3600 IN AAAA 2001:db8::25",
+            "www.example.com. 3600 IN CNAME example.com.",
+            "txt.example.com. 3600 IN TXT \"quoted; not a comment\" \"unquoted\" \"escaped \\\"quote\\\" \\\\ ;\"",
+            "_sip._tcp.example.com. 3600 IN SRV 0 5 5060 sip.example.com.",
+            "*.example.com. 3600 IN TXT \"wildcard\"",
+            "svc.example.com. 3600 IN TYPE65 \\# 3 000100",
+            "host.sub.example.com. 3600 CH A 192.0.2.1",
+        ];
+        assert_eq!(lines, correct);
+
+        // Without $TTL the TTL of the previous record is used.
+        let resource_records =
+            parse_master_file_str("a 60 IN A 192.0.2.1\nb A 192.0.2.2\n", "example.com.")?;
+        assert_eq!(resource_records[1].ttl, 60);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_master_file_errors() -> Result<(), String> {
+        let cases = [
+            ("www A 192.0.2.1\n", "<input>:1:1: no TTL"),
+            (
+                "$TTL 60\nwww A 192.0.2.300\n",
+                "<input>:2:7: invalid address",
+            ),
+            (
+                "$TTL 60\n\nwww IN BOGUS x\n",
+                "<input>:3:8: unknown type BOGUS",
+            ),
+            (
+                "$TTL 60\nwww IN\n",
+                "<input>:2:5: missing resource record type",
+            ),
+            ("$TTL 60\n A 192.0.2.1\n", "<input>:2:2: no previous owner"),
+            (
+                "$TTL 60\n@ SOA ns1 host (\n 1 2 3 4 5\n",
+                "<input>:2:16: '(' without",
+            ),
+            ("$TTL 60\n@ A 192.0.2.1 )\n", "<input>:2:15: ')' without"),
+            (
+                "$TTL 60\n@ TXT \"open\n",
+                "<input>:2:7: unterminated quoted string",
+            ),
+            ("$TTL 60\n@ MX 10\n", "<input>:2:6: missing field"),
+            (
+                "$TTL 60\n@ A 192.0.2.1 192.0.2.2\n",
+                "<input>:2:5: unexpected extra field",
+            ),
+            ("$TTL 1x\n", "<input>:1:6: invalid TTL"),
+            ("$ORIGIN a..b\n", "<input>:1:9:"),
+            ("$ORIGIN\n", "<input>:1:1: $ORIGIN is missing an argument"),
+            ("$TTL 60 60\n", "<input>:1:9: unexpected field"),
+            ("$BOGUS x\n", "<input>:1:1: unknown directive"),
+        ];
+
+        for (text, error) in cases.iter() {
+            let result = parse_master_file_str(text, "example.com.");
+            match result {
+                Err(result) => assert!(
+                    result.starts_with(error),
+                    "{} does not start with {}",
+                    result,
+                    error
+                ),
+                Ok(_) => panic!("{:?} parsed without error", text),
+            }
+        }
+
+        assert!(parse_master_file_str("; only a comment\n\n", "example.com.")?.is_empty());
+        assert!(MasterFileParser::new("example.com").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate() -> Result<(), String> {
+        let text = "$TTL 60\n$GENERATE 1-5/2 $ PTR host-$.example.com.\n";
+        let resource_records = parse_master_file_str(text, "2.0.192.in-addr.arpa.")?;
+        let lines: Vec<String> = resource_records
+            .iter()
+            .map(|record| record.to_string())
+            .collect();
+        let correct = [
+            "1.2.0.192.in-addr.arpa. 60 IN PTR host-1.example.com.",
+            "3.2.0.192.in-addr.arpa. 60 IN PTR host-3.example.com.",
+            "5.2.0.192.in-addr.arpa. 60 IN PTR host-5.example.com.",
+        ];
+        assert_eq!(lines, correct);
+
+        let text = "$GENERATE 10-11 ${-10,3} 300 IN A 192.0.2.${0,0,d}\n\
+                    $GENERATE 255-255 h${0,4,x}-${0,0,X}-${0,0,o} CNAME \\$$\n\
+                    $GENERATE 0-0 ${291,0,n} CNAME ${291,6,N}\n";
+        let resource_records = parse_master_file_str(text, "example.com.")?;
+        let lines: Vec<String> = resource_records
+            .iter()
+            .map(|record| record.to_string())
+            .collect();
+        let correct = [
+            "000.example.com. 300 IN A 192.0.2.10",
+            "001.example.com. 300 IN A 192.0.2.11",
+            "h00ff-FF-377.example.com. 300 IN CNAME \\$255.example.com.",
+            "3.2.1.example.com. 300 IN CNAME 3.2.1.",
+        ];
+        assert_eq!(lines, correct);
+
+        let cases = [
+            ("$GENERATE 1-2 $ PTR\n", "<input>:1:1: $GENERATE is missing"),
+            (
+                "$GENERATE 2-1 $ 60 A 192.0.2.$\n",
+                "<input>:1:11: invalid $GENERATE range",
+            ),
+            (
+                "$GENERATE 1-2/0 $ 60 A 192.0.2.$\n",
+                "<input>:1:11: invalid $GENERATE range",
+            ),
+            (
+                "$GENERATE 1-2 ${-2} 60 A 192.0.2.1\n",
+                "<input>:1:15: ${-2} is out of range",
+            ),
+            (
+                "$GENERATE 1-2 ${0,0,q} 60 A 192.0.2.1\n",
+                "<input>:1:15: invalid modifier",
+            ),
+            (
+                "$GENERATE 1-2 ${0 60 A 192.0.2.1\n",
+                "<input>:1:15: unterminated modifier",
+            ),
+            (
+                "$GENERATE 255-256 $ 60 A 192.0.2.$\n",
+                "<input>:1:26: invalid address",
+            ),
+        ];
+        for (text, error) in cases.iter() {
+            match parse_master_file_str(text, "example.com.") {
+                Err(result) => assert!(
+                    result.starts_with(error),
+                    "{} does not start with {}",
+                    result,
+                    error
+                ),
+                Ok(_) => panic!("{:?} parsed without error", text),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include() -> Result<(), String> {
+        let directory =
+            std::env::temp_dir().join(format!("rusty_dns_include_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).map_err(|error| error.to_string())?;
+
+        let write = |name: &str, text: &str| {
+            std::fs::write(directory.join(name), text).map_err(|error| error.to_string())
+        };
+        write(
+            "zone.db",
+            "$TTL 60\n$INCLUDE hosts.db hosts\nwww A 192.0.2.80\n$INCLUDE loop.db\n",
+        )?;
+        write("hosts.db", "a A 192.0.2.1\n$ORIGIN other.\nb A 192.0.2.2\n")?;
+        write("loop.db", "$INCLUDE loop.db\n")?;
+
+        let error = parse_master_file(&directory.join("zone.db"), "example.com.").unwrap_err();
+        assert!(error.contains("nested $INCLUDE"), "{}", error);
+
+        write(
+            "zone.db",
+            "$TTL 60\n$INCLUDE hosts.db hosts\nwww A 192.0.2.80\n",
+        )?;
+        let resource_records = parse_master_file(&directory.join("zone.db"), "example.com.")?;
+        let names: Vec<&str> = resource_records
+            .iter()
+            .map(|record| record.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["a.hosts.example.com.", "b.other.", "www.example.com."]
+        );
+
+        std::fs::remove_dir_all(&directory).map_err(|error| error.to_string())?;
+
+        Ok(())
+    }
+}
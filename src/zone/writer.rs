@@ -0,0 +1,345 @@
+use crate::{
+    dns_packet::dns_resource_record::*, domain_name::*, encoding::*, mnemonics::*, types::*,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// RDATA longer than this is split over several lines within parentheses.
+pub const WRAP_WIDTH: usize = 56;
+
+/// The RDATA of a record as presentation text for the first line, followed by parts which are each
+/// written on a line of their own within parentheses, with an optional comment.
+struct RdataLines {
+    head: String,
+    parts: Vec<(String, Option<&'static str>)>,
+}
+
+impl RdataLines {
+    fn new(rdata: &DnsResourceRecordData) -> RdataLines {
+        let text = rdata.to_string();
+        let chunks = |data: &str| -> Vec<(String, Option<&'static str>)> {
+            data.as_bytes()
+                .chunks(WRAP_WIDTH)
+                .map(|chunk| (String::from_utf8_lossy(chunk).into_owned(), None))
+                .collect()
+        };
+
+        let (head, parts) = match rdata {
+            DnsResourceRecordData::SOA((mname, rname, serial, refresh, retry, expire, minimum)) => {
+                let parts = [
+                    (serial, "serial"),
+                    (refresh, "refresh"),
+                    (retry, "retry"),
+                    (expire, "expire"),
+                    (minimum, "minimum"),
+                ];
+                (
+                    format!("{} {}", mname, rname),
+                    parts
+                        .iter()
+                        .map(|(value, comment)| (value.to_string(), Some(*comment)))
+                        .collect(),
+                )
+            }
+            _ if text.len() <= WRAP_WIDTH => (text, Vec::new()),
+            DnsResourceRecordData::TXT(txt_data) if txt_data.len() > 1 => (
+                String::new(),
+                txt_data
+                    .iter()
+                    .map(|character_string| {
+                        (character_string_to_presentation(character_string), None)
+                    })
+                    .collect(),
+            ),
+            DnsResourceRecordData::CERT((_, _, _, data))
+            | DnsResourceRecordData::IPSECKEY((_, _, _, data))
+                if !data.is_empty() =>
+            {
+                let encoded = base64_encode(data);
+                let head = text[..text.len() - encoded.len()].trim_end();
+                (String::from(head), chunks(&encoded))
+            }
+            DnsResourceRecordData::NULL(data) => {
+                (format!("\\# {}", data.len()), chunks(&hex_encode(data)))
+            }
+            _ => (text, Vec::new()),
+        };
+
+        RdataLines { head, parts }
+    }
+}
+
+/// Rewrite the domain names in rdata relative to origin.
+fn relative_rdata(
+    rdata: &DnsResourceRecordData,
+    origin: &str,
+) -> Result<DnsResourceRecordData, String> {
+    use DnsResourceRecordData::*;
+    let relative = |name: &String| relative_domain_name(name, origin);
+
+    let rdata = match rdata {
+        NS(name) => NS(relative(name)?),
+        MD(name) => MD(relative(name)?),
+        MF(name) => MF(relative(name)?),
+        CNAME(name) => CNAME(relative(name)?),
+        MB(name) => MB(relative(name)?),
+        MG(name) => MG(relative(name)?),
+        MR(name) => MR(relative(name)?),
+        PTR(name) => PTR(relative(name)?),
+        DNAME(name) => DNAME(relative(name)?),
+        SOA((mname, rname, serial, refresh, retry, expire, minimum)) => SOA((
+            relative(mname)?,
+            relative(rname)?,
+            *serial,
+            *refresh,
+            *retry,
+            *expire,
+            *minimum,
+        )),
+        MINFO((rmailbx, emailbx)) => MINFO((relative(rmailbx)?, relative(emailbx)?)),
+        MX((preference, exchange)) => MX((*preference, relative(exchange)?)),
+        SRV((priority, weight, port, target)) => {
+            SRV((*priority, *weight, *port, relative(target)?))
+        }
+        RP((mbox_dname, txt_dname)) => RP((relative(mbox_dname)?, relative(txt_dname)?)),
+        AFSDB((subtype, hostname)) => AFSDB((*subtype, relative(hostname)?)),
+        RT((preference, intermediate_host)) => RT((*preference, relative(intermediate_host)?)),
+        OPT(_) => {
+            return Err(
+                "OPT pseudo-RRs only exist in DNS messages, not in presentation format".into(),
+            )
+        }
+        _ => rdata.clone(),
+    };
+
+    Ok(rdata)
+}
+
+/// Write resource_records as an RFC 1035 master file for the zone with the given origin.
+///
+/// The file starts with $ORIGIN and a $TTL of the most common TTL, which records then omit. The SOA
+/// record comes first, followed by the remaining records grouped by owner name and RRset in the
+/// order they first appear. Domain names are written relative to the origin, columns are aligned,
+/// and long RDATA is wrapped in parentheses. [super::parser::parse_master_file_str] reads the
+/// output back into the same records.
+pub fn write_master_file(
+    resource_records: &[DnsResourceRecord],
+    origin: &str,
+) -> Result<String, String> {
+    domain_name_to_labels(origin)?;
+
+    let mut ttls: Vec<(u32, usize)> = Vec::new();
+    for resource_record in resource_records {
+        match ttls.iter_mut().find(|(ttl, _)| *ttl == resource_record.ttl) {
+            Some((_, count)) => *count += 1,
+            None => ttls.push((resource_record.ttl, 1)),
+        }
+    }
+    let default_ttl = ttls
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(ttl, _)| *ttl);
+
+    // Rank owner names and RRsets by first appearance, counting SOA records first, then sort by
+    // those ranks to group them.
+    let mut owner_ranks: HashMap<Vec<Vec<u8>>, usize> = HashMap::new();
+    let mut rrset_ranks: HashMap<(usize, u16, u16), usize> = HashMap::new();
+    let mut rank = |resource_record: &DnsResourceRecord| -> Result<(usize, usize), String> {
+        let next_rank = owner_ranks.len();
+        let owner_rank = *owner_ranks
+            .entry(domain_name_to_labels(&resource_record.name)?)
+            .or_insert(next_rank);
+        let next_rank = rrset_ranks.len();
+        let rrset_rank = *rrset_ranks
+            .entry((owner_rank, resource_record.class, resource_record.rrtype))
+            .or_insert(next_rank);
+
+        Ok((owner_rank, rrset_rank))
+    };
+    for resource_record in resource_records {
+        if resource_record.rrtype == DNS_TYPE_SOA {
+            rank(resource_record)?;
+        }
+    }
+    let mut ranked = Vec::with_capacity(resource_records.len());
+    for resource_record in resource_records {
+        let (owner_rank, rrset_rank) = rank(resource_record)?;
+        ranked.push((owner_rank, rrset_rank, resource_record));
+    }
+    ranked.sort_by_key(|(owner_rank, rrset_rank, _)| (*owner_rank, *rrset_rank));
+
+    // Owner, TTL, class and type columns, followed by the RDATA.
+    let mut rows = Vec::with_capacity(ranked.len());
+    let mut last_owner_rank = None;
+    for (owner_rank, _, resource_record) in ranked {
+        let owner = match last_owner_rank.replace(owner_rank) == Some(owner_rank) {
+            true => String::new(),
+            false => relative_domain_name(&resource_record.name, origin)?,
+        };
+        let ttl = match Some(resource_record.ttl) == default_ttl {
+            true => String::new(),
+            false => resource_record.ttl.to_string(),
+        };
+        let columns = [
+            owner,
+            ttl,
+            class_to_string(resource_record.class),
+            type_to_string(resource_record.rrtype),
+        ];
+        let rdata = RdataLines::new(&relative_rdata(&resource_record.rdata, origin)?);
+
+        rows.push((columns, rdata));
+    }
+
+    let mut widths = [0; 4];
+    for (columns, _) in &rows {
+        for (width, column) in widths.iter_mut().zip(columns.iter()) {
+            *width = (*width).max(column.len());
+        }
+    }
+    // Records without an owner name must start with whitespace.
+    widths[0] = widths[0].max(1);
+
+    let mut master_file = format!("$ORIGIN {}\n", origin);
+    if let Some(default_ttl) = default_ttl {
+        master_file.push_str(&format!("$TTL {}\n", default_ttl));
+    }
+
+    for (columns, rdata) in rows {
+        let mut line = String::new();
+        for (width, column) in widths.iter().zip(columns.iter()) {
+            if *width > 0 {
+                line.push_str(&format!("{:1$} ", column, width));
+            }
+        }
+        let indent = line.len() + 4;
+
+        line.push_str(&rdata.head);
+        if !rdata.parts.is_empty() {
+            if !rdata.head.is_empty() {
+                line.push(' ');
+            }
+            line.push('(');
+        }
+        master_file.push_str(line.trim_end());
+        master_file.push('\n');
+
+        let last = rdata.parts.len().saturating_sub(1);
+        for (index, (part, comment)) in rdata.parts.iter().enumerate() {
+            let mut line = format!("{:1$}{2}", "", indent, part);
+            if index == last {
+                line.push_str(" )");
+            }
+            if let Some(comment) = comment {
+                line.push_str(&format!(" ; {}", comment));
+            }
+            master_file.push_str(&line);
+            master_file.push('\n');
+        }
+    }
+
+    Ok(master_file)
+}
+
+/// Write resource_records as a master file at path, see [write_master_file].
+pub fn save_master_file(
+    path: &Path,
+    resource_records: &[DnsResourceRecord],
+    origin: &str,
+) -> Result<(), String> {
+    let master_file = write_master_file(resource_records, origin)?;
+    std::fs::write(path, master_file).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zone::parser::*;
+
+    const EXAMPLE_ZONE: &str = r#"$ORIGIN example.com.
+$TTL 3600
+www     A       192.0.2.80
+@       SOA     ns1 hostmaster.example.com. 2024010101 7200 900 1209600 300
+@       NS      ns1
+        NS      ns2.example.net.
+www     300 AAAA 2001:db8::80
+ns1     A       192.0.2.53
+www     A       192.0.2.81
+@       MX      10 mail.Example.com.
+txt     TXT     "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 include:_spf.example.net" "~all"
+cert    CERT    PKIX 1 8 MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAq3yYFBvbF5NQW3xvXyz2mY3Q8UDs2wE=
+null    TYPE10  \# 40 000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F2021222324252627
+\@\$x   CH TXT  "chaos"
+"#;
+
+    const CORRECT_MASTER_FILE: &str = r#"$ORIGIN example.com.
+$TTL 3600
+@         IN SOA  ns1 hostmaster (
+                      2024010101 ; serial
+                      7200 ; refresh
+                      900 ; retry
+                      1209600 ; expire
+                      300 ) ; minimum
+          IN NS   ns1
+          IN NS   ns2.example.net.
+          IN MX   10 mail.Example.com.
+www       IN A    192.0.2.80
+          IN A    192.0.2.81
+      300 IN AAAA 2001:db8::80
+ns1       IN A    192.0.2.53
+txt       IN TXT  (
+                      "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 include:_spf.example.net"
+                      "~all" )
+cert      IN CERT 1 1 8 (
+                      MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAq3yYFBvbF5NQ
+                      W3xvXyz2mY3Q8UDs2wE= )
+null      IN NULL \# 40 (
+                      000102030405060708090A0B0C0D0E0F101112131415161718191A1B
+                      1C1D1E1F2021222324252627 )
+\@\$x     CH TXT  "chaos"
+"#;
+
+    #[test]
+    fn test_write_master_file() -> Result<(), String> {
+        let resource_records = parse_master_file_str(EXAMPLE_ZONE, "example.com.")?;
+        let master_file = write_master_file(&resource_records, "example.com.")?;
+        assert_eq!(master_file, CORRECT_MASTER_FILE);
+
+        // Reading the output back gives the same records, and writing them again the same file.
+        let reread = parse_master_file_str(&master_file, "example.com.")?;
+        let mut lines: Vec<String> = resource_records
+            .iter()
+            .map(|record| record.to_string())
+            .collect();
+        let mut reread_lines: Vec<String> =
+            reread.iter().map(|record| record.to_string()).collect();
+        lines.sort();
+        reread_lines.sort();
+        assert_eq!(lines, reread_lines);
+        assert_eq!(write_master_file(&reread, "example.com.")?, master_file);
+
+        // URI targets are not limited to the length of a character string.
+        let target = format!("https://www.example.com/{}", "a".repeat(300));
+        let resource_records =
+            parse_master_file_str(&format!("_http._tcp 60 URI 10 1 \"{}\"\n", target), ".")?;
+        let master_file = write_master_file(&resource_records, ".")?;
+        let reread = parse_master_file_str(&master_file, ".")?;
+        assert_eq!(reread[0].rdata.to_string(), format!("10 1 \"{}\"", target));
+        assert_eq!(write_master_file(&reread, ".")?, master_file);
+
+        // Relative to the root, and without any records.
+        let resource_records = parse_master_file_str("www. 60 A 192.0.2.1\n", ".")?;
+        assert_eq!(
+            write_master_file(&resource_records, ".")?,
+            "$ORIGIN .\n$TTL 60\nwww IN A 192.0.2.1\n"
+        );
+        assert_eq!(
+            write_master_file(&[], "example.com.")?,
+            "$ORIGIN example.com.\n"
+        );
+        assert!(write_master_file(&[], "example.com").is_err());
+
+        Ok(())
+    }
+}
@@ -1,4 +1,21 @@
-use rusty_dns::{dns_packet::*, rcodes::*, send_dns_query_to, types::*};
+use rusty_dns::{
+    cache::*,
+    dns_packet::{dns_resource_record::*, *},
+    forwarder::*,
+    hosts::*,
+    iterative::*,
+    rcodes::*,
+    resolve_domain_name_with_cache,
+    resolver::*,
+    root_hints::*,
+    send_dns_query_reporting_transport, send_dns_query_to, send_dns_query_with_options,
+    server::*,
+    tcp::*,
+    types::*,
+    zone::*,
+    QueryOptions, Transport,
+};
+use std::io::{Read, Write};
 
 #[test]
 fn test_basic_dns_resolution() -> Result<(), String> {
@@ -19,3 +36,814 @@ fn test_basic_dns_resolution() -> Result<(), String> {
 
     Ok(())
 }
+
+#[test]
+fn test_resolve_from_cache() -> Result<(), String> {
+    // Cached answers are used without asking the recursive resolver.
+    let cache = Cache::default();
+    let records = parser::parse_master_file_str(
+        "alias 300 CNAME www\nwww 300 A 192.0.2.1\n",
+        "example.test.",
+    )?;
+    cache.insert_rrset(&records[..1])?;
+    cache.insert_rrset(&records[1..])?;
+
+    let address = resolve_domain_name_with_cache(&String::from("Alias.Example.Test"), &cache)?;
+    assert_eq!(address, std::net::Ipv4Addr::new(192, 0, 2, 1));
+    let statistics = cache.statistics();
+    assert_eq!((statistics.hits, statistics.misses), (2, 1));
+
+    Ok(())
+}
+
+/// Answer queries on a local socket, passing each query's question name through rewrite_qname.
+/// Returns the server address and a handle yielding the queries received.
+fn spawn_udp_responder(
+    queries: usize,
+    rewrite_qname: fn(&str) -> String,
+) -> (String, std::thread::JoinHandle<Vec<DnsPacket>>) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap().to_string();
+
+    let handle = std::thread::spawn(move || {
+        let mut received = Vec::new();
+        let mut buf = [0; 512];
+
+        for _ in 0..queries {
+            let (amt, source) = socket.recv_from(&mut buf).unwrap();
+            let mut dns_packet = DnsPacket::parse_dns_packet(&buf[..amt]).unwrap();
+            received.push(dns_packet.clone());
+
+            dns_packet.header.qr = true;
+            dns_packet.question[0].qname = rewrite_qname(&dns_packet.question[0].qname);
+            socket
+                .send_to(&dns_packet.serialize().unwrap(), source)
+                .unwrap();
+        }
+
+        received
+    });
+
+    (address, handle)
+}
+
+#[test]
+fn test_query_case_randomization() -> Result<(), String> {
+    let domain_name = format!("{}.example.com.", "www".repeat(10));
+    let options = QueryOptions {
+        randomize_case: true,
+        ..QueryOptions::default()
+    };
+
+    // A server that preserves case answers once, echoing the randomized name.
+    let (address, handle) = spawn_udp_responder(1, str::to_string);
+    let dns_packet = DnsPacket::new(&domain_name, DNS_TYPE_A)?;
+    let dns_response = send_dns_query_with_options(&dns_packet, &address, &options)?;
+    let received = handle.join().map_err(|_| "responder panicked")?;
+    assert_ne!(received[0].question[0].qname, domain_name);
+    assert_eq!(
+        dns_response.question[0].qname,
+        received[0].question[0].qname
+    );
+
+    // A server that lowercases names is asked again with the name as given.
+    let (address, handle) = spawn_udp_responder(2, str::to_ascii_lowercase);
+    let dns_response = send_dns_query_with_options(&dns_packet, &address, &options)?;
+    let received = handle.join().map_err(|_| "responder panicked")?;
+    assert_ne!(received[0].question[0].qname, domain_name);
+    assert_eq!(received[1].question[0].qname, domain_name);
+    assert_eq!(dns_response.question[0].qname, domain_name);
+
+    // The query sent again uses a new ID, but the response has the ID of the query as given.
+    assert_eq!(received[0].header.id, dns_packet.header.id);
+    assert_ne!(received[1].header.id, dns_packet.header.id);
+    assert_eq!(dns_response.header.id, dns_packet.header.id);
+
+    // A response for a different name is rejected.
+    let (address, handle) = spawn_udp_responder(1, |_| String::from("spoofed.example.com."));
+    assert!(send_dns_query_with_options(&dns_packet, &address, &options).is_err());
+    handle.join().map_err(|_| "responder panicked")?;
+
+    Ok(())
+}
+
+/// Read one length prefixed DNS message from stream, returning None once the client closes it.
+fn read_framed(stream: &mut std::net::TcpStream) -> Option<DnsPacket> {
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).ok()?;
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message).ok()?;
+    DnsPacket::parse_dns_packet(&message).ok()
+}
+
+/// Serialize query as a response, preceded by its length.
+fn framed_response(mut query: DnsPacket) -> Vec<u8> {
+    query.header.qr = true;
+    let message = query.serialize().unwrap();
+    let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(&message);
+    framed
+}
+
+#[test]
+fn test_tcp_connection() -> Result<(), String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let handle = std::thread::spawn(move || {
+        // Two pipelined queries are answered in reverse order, the second response in two parts.
+        let (mut stream, _) = listener.accept().unwrap();
+        let first = read_framed(&mut stream).unwrap();
+        let second = read_framed(&mut stream).unwrap();
+        stream.write_all(&framed_response(second)).unwrap();
+        let framed = framed_response(first);
+        stream.write_all(&framed[..5]).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        stream.write_all(&framed[5..]).unwrap();
+
+        // A third query on the same connection, after which the server closes it.
+        let third = read_framed(&mut stream).unwrap();
+        stream.write_all(&framed_response(third)).unwrap();
+        drop(stream);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let fourth = read_framed(&mut stream).unwrap();
+        stream.write_all(&framed_response(fourth)).unwrap();
+        while read_framed(&mut stream).is_some() {}
+    });
+
+    let mut connection = TcpConnection::new(&address, std::time::Duration::from_secs(2))?;
+    assert!(!connection.is_connected());
+
+    let mut first = DnsPacket::new(&String::from("one.example.test."), DNS_TYPE_A)?;
+    first.header.id = 1;
+    let mut second = DnsPacket::new(&String::from("two.example.test."), DNS_TYPE_A)?;
+    second.header.id = 2;
+    connection.send(&first)?;
+    connection.send(&second)?;
+    assert!(connection.send(&first).is_err());
+
+    let response = connection.receive(1)?;
+    assert_eq!(response.question[0].qname, "one.example.test.");
+    let response = connection.receive(2)?;
+    assert_eq!(response.question[0].qname, "two.example.test.");
+    assert!(connection.receive(2).is_err());
+
+    let mut third = DnsPacket::new(&String::from("three.example.test."), DNS_TYPE_A)?;
+    third.header.id = 3;
+    let response = connection.query(&third)?;
+    assert_eq!(response.header.id, 3);
+
+    // The server has closed the reused connection, so the query is sent again on a new one.
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let mut fourth = DnsPacket::new(&String::from("four.example.test."), DNS_TYPE_A)?;
+    fourth.header.id = 4;
+    let response = connection.query(&fourth)?;
+    assert_eq!(response.question[0].qname, "four.example.test.");
+
+    connection.close();
+    handle.join().map_err(|_| "server panicked")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_tcp_connection_errors() -> Result<(), String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let handle = std::thread::spawn(move || {
+        // The first query is not answered, and the response to its retry cannot be parsed.
+        let (mut stream, _) = listener.accept().unwrap();
+        read_framed(&mut stream).unwrap();
+        let retry = read_framed(&mut stream).unwrap();
+        let mut malformed = vec![0, 12];
+        malformed.extend_from_slice(&retry.header.id.to_be_bytes());
+        malformed.extend_from_slice(&[0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0]);
+        stream.write_all(&malformed).unwrap();
+
+        let last = read_framed(&mut stream).unwrap();
+        stream.write_all(&framed_response(last)).unwrap();
+        while read_framed(&mut stream).is_some() {}
+    });
+
+    let mut connection = TcpConnection::new(&address, std::time::Duration::from_millis(300))?;
+    let mut query = DnsPacket::new(&String::from("one.example.test."), DNS_TYPE_A)?;
+    query.header.id = 1;
+    let error = connection.query(&query).unwrap_err();
+    assert!(error.contains("timed out"), "{}", error);
+
+    // The timed out query is no longer outstanding, so it can be retried under the same ID.
+    let error = connection.query(&query).unwrap_err();
+    assert!(error.contains("Could not parse response"), "{}", error);
+
+    query.header.id = 2;
+    connection.query(&query)?;
+    assert!(connection.is_connected());
+
+    connection.close();
+    handle.join().map_err(|_| "server panicked")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_authoritative_server() -> Result<(), String> {
+    let master_file =
+        "$TTL 60\n@ SOA ns1 host 1 2 3 4 5\n@ NS ns1\nns1 A 192.0.2.1\nwww A 192.0.2.2\n";
+    let zone = Zone::new(
+        "example.com.",
+        parser::parse_master_file_str(master_file, "example.com.")?,
+    )?;
+    let handle = AuthoritativeServer::new(vec![zone])?.spawn("127.0.0.1:0")?;
+    let address = handle.local_address().to_string();
+
+    let dns_packet = DnsPacket::new(&String::from("www.example.com."), DNS_TYPE_A)?;
+    let dns_response = send_dns_query_to(&dns_packet, &address)?;
+    assert!(dns_response.header.aa);
+    assert_eq!(
+        dns_response.answer[0].to_string(),
+        "www.example.com. 60 IN A 192.0.2.2"
+    );
+
+    // Two pipelined queries over TCP, the first written in two parts.
+    let mut stream = std::net::TcpStream::connect(&address).map_err(|error| error.to_string())?;
+    let mut framed = Vec::new();
+    for (qname, qtype) in [
+        ("ns1.example.com.", DNS_TYPE_A),
+        ("www.example.net.", DNS_TYPE_A),
+    ]
+    .iter()
+    {
+        let message = DnsPacket::new(&String::from(*qname), *qtype)?.serialize()?;
+        framed.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        framed.extend_from_slice(&message);
+    }
+    stream
+        .write_all(&framed[..5])
+        .map_err(|error| error.to_string())?;
+    stream.flush().map_err(|error| error.to_string())?;
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    stream
+        .write_all(&framed[5..])
+        .map_err(|error| error.to_string())?;
+
+    let mut rcodes = Vec::new();
+    for _ in 0..2 {
+        let mut length = [0; 2];
+        stream
+            .read_exact(&mut length)
+            .map_err(|error| error.to_string())?;
+        let mut message = vec![0; u16::from_be_bytes(length) as usize];
+        stream
+            .read_exact(&mut message)
+            .map_err(|error| error.to_string())?;
+        rcodes.push(DnsPacket::parse_dns_packet(&message)?.header.rcode);
+    }
+    assert_eq!(rcodes, [DNS_RCODE_NO_ERROR, DNS_RCODE_REFUSED]);
+
+    handle.shutdown();
+    let mut buf = [0; 1];
+    assert_eq!(stream.read(&mut buf).map_err(|error| error.to_string())?, 0);
+
+    Ok(())
+}
+
+/// Serve the zone at origin described by master_file on address.
+fn spawn_zone_server(
+    address: &str,
+    origin: &str,
+    master_file: &str,
+) -> Result<ServerHandle, String> {
+    let master_file = format!("$TTL 60\n@ SOA ns host 1 2 3 4 5\n{}", master_file);
+    let zone = Zone::new(origin, parser::parse_master_file_str(&master_file, origin)?)?;
+    AuthoritativeServer::new(vec![zone])?.spawn(address)
+}
+
+#[test]
+fn test_tcp_server_limits() -> Result<(), String> {
+    let records: String = (0..40)
+        .map(|index| format!("big TXT \"{:02}{}\"\n", index, "x".repeat(38)))
+        .collect();
+    let server = spawn_zone_server("127.0.0.1:0", "example.test.", &records)?;
+    let address = server.local_address();
+    let query = DnsPacket::new(&String::from("example.test."), DNS_TYPE_SOA)?;
+
+    // Connections past the limit wait to be accepted until another one is closed.
+    let mut connections = Vec::new();
+    for _ in 0..MAX_CONCURRENT_TCP_CONNECTIONS {
+        connections.push(std::net::TcpStream::connect(address).unwrap());
+    }
+    let mut waiting =
+        TcpConnection::new(&address.to_string(), std::time::Duration::from_millis(500))?;
+    assert!(waiting.query(&query).is_err());
+    drop(waiting);
+    connections.pop();
+    let mut waiting = TcpConnection::new(&address.to_string(), std::time::Duration::from_secs(2))?;
+    waiting.query(&query)?;
+    drop(connections);
+    drop(waiting);
+
+    // A client that sends queries without reading the responses cannot keep the server from shutting
+    // down, once its connection has been accepted.
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    let message = query.serialize()?;
+    let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(&message);
+    stream.write_all(&framed).unwrap();
+    stream.read_exact(&mut [0; 2]).unwrap();
+
+    stream
+        .set_write_timeout(Some(std::time::Duration::from_millis(500)))
+        .unwrap();
+    let message = DnsPacket::new(&String::from("big.example.test."), DNS_TYPE_TXT)?.serialize()?;
+    let mut framed = Vec::new();
+    for _ in 0..1000 {
+        framed.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        framed.extend_from_slice(&message);
+    }
+    while stream.write_all(&framed).is_ok() {}
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        server.shutdown();
+        let _ = sender.send(());
+    });
+    receiver
+        .recv_timeout(std::time::Duration::from_secs(10))
+        .map_err(|_| "server did not shut down")?;
+
+    Ok(())
+}
+
+/// An authoritative server that adds records to the answer section of all its responses.
+struct ForgingServer {
+    server: AuthoritativeServer,
+    forged: Vec<DnsResourceRecord>,
+}
+
+impl QueryHandler for ForgingServer {
+    fn respond(&self, query: &DnsPacket) -> DnsPacket {
+        let mut response = self.server.respond(query);
+        response.answer.extend(self.forged.iter().cloned());
+        response.update_counts();
+        response
+    }
+}
+
+/// An authoritative server that answers SERVFAIL to all queries for one type.
+struct FailingServer {
+    server: AuthoritativeServer,
+    rrtype: u16,
+}
+
+impl QueryHandler for FailingServer {
+    fn respond(&self, query: &DnsPacket) -> DnsPacket {
+        let mut response = self.server.respond(query);
+        if query.question[0].qtype == self.rrtype {
+            response.header.rcode = DNS_RCODE_SERVER_ERROR;
+            response.answer.clear();
+            response.authority.clear();
+            response.update_counts();
+        }
+        response
+    }
+}
+
+#[test]
+fn test_serve_until_quit() -> Result<(), String> {
+    let server = spawn_zone_server("127.0.0.1:0", "example.test.", "")?;
+    let address = server.local_address();
+    server.serve_until_quit(std::io::Cursor::new("status\n quit \n"));
+    assert!(std::net::TcpStream::connect(address).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_iterative_resolution() -> Result<(), String> {
+    // Stand-in servers on loopback addresses sharing one port. Of the servers for com. the first is
+    // lame and the second does not answer. The servers for example.com. have no glue.
+    let root = spawn_zone_server(
+        "127.0.0.1:0",
+        ".",
+        "@ NS a.root.test.\na.root.test. A 127.0.0.1\n\
+         com. NS lame.test.\ncom. NS dead.test.\ncom. NS ns.com.\n\
+         lame.test. A 127.0.0.4\ndead.test. A 127.0.0.5\nns.com. A 127.0.0.2\n\
+         net. NS ns.net.\nns.net. A 127.0.0.3\n",
+    )?;
+    let port = root.local_address().port();
+    let servers = vec![
+        root,
+        spawn_zone_server(
+            &format!("127.0.0.2:{}", port),
+            "com.",
+            "@ NS ns\nns A 127.0.0.2\nexample NS ns.example.net.\n",
+        )?,
+        spawn_zone_server(
+            &format!("127.0.0.3:{}", port),
+            "net.",
+            "@ NS ns\nns A 127.0.0.3\nns.example A 127.0.0.6\nwww.example A 192.0.2.1\n",
+        )?,
+        spawn_zone_server(&format!("127.0.0.4:{}", port), "org.", "")?,
+        // The server for example.com. also answers for the target of its CNAME, which is not trusted.
+        spawn_server(
+            ForgingServer {
+                server: AuthoritativeServer::new(vec![Zone::new(
+                    "example.com.",
+                    parser::parse_master_file_str(
+                        "$TTL 60\n@ SOA ns host 1 2 3 4 5\n@ NS ns.example.net.\n\
+                         www CNAME www.example.net.\n",
+                        "example.com.",
+                    )?,
+                )?])?,
+                forged: parser::parse_master_file_str("www.example.net. 60 A 192.0.2.99\n", ".")?,
+            },
+            &format!("127.0.0.6:{}", port),
+        )?,
+    ];
+
+    let options = QueryOptions {
+        timeout: std::time::Duration::from_millis(300),
+        ..QueryOptions::default()
+    };
+    let root_hints = RootHints::parse(". 60 NS a.root.test.\na.root.test. 60 A 127.0.0.1\n")?;
+    let resolver = IterativeResolver::with_root_hints(root_hints, port, options);
+
+    let resolution = resolver.resolve("www.example.com", DNS_TYPE_A)?;
+    assert_eq!(resolution.rcode, DNS_RCODE_NO_ERROR);
+    let answer: Vec<String> = resolution
+        .answer
+        .iter()
+        .map(|record| record.to_string())
+        .collect();
+    assert_eq!(
+        answer,
+        [
+            "www.example.com. 60 IN CNAME www.example.net.",
+            "www.example.net. 60 IN A 192.0.2.1",
+        ]
+    );
+
+    let consulted: Vec<(String, String, bool)> = resolution
+        .servers
+        .iter()
+        .map(|query| {
+            (
+                query.address.ip().to_string(),
+                query.qname.clone(),
+                query.result.is_ok(),
+            )
+        })
+        .collect();
+    let expected = [
+        ("127.0.0.1", "www.example.com.", true),
+        ("127.0.0.4", "www.example.com.", true),
+        ("127.0.0.5", "www.example.com.", false),
+        ("127.0.0.2", "www.example.com.", true),
+        ("127.0.0.1", "ns.example.net.", true),
+        ("127.0.0.3", "ns.example.net.", true),
+        ("127.0.0.6", "www.example.com.", true),
+        ("127.0.0.1", "www.example.net.", true),
+        ("127.0.0.3", "www.example.net.", true),
+    ];
+    let expected: Vec<(String, String, bool)> = expected
+        .iter()
+        .map(|(address, qname, ok)| (address.to_string(), qname.to_string(), *ok))
+        .collect();
+    assert_eq!(consulted, expected);
+    assert_eq!(resolution.servers[1].result, Ok(DNS_RCODE_REFUSED));
+    assert_eq!(resolution.servers[6].zone, "example.com.");
+
+    let resolution = resolver.resolve("missing.example.com.", DNS_TYPE_A)?;
+    assert_eq!(resolution.rcode, DNS_RCODE_NAME_ERROR);
+    assert_eq!(resolution.authority[0].rrtype, DNS_TYPE_SOA);
+    assert!(!resolver.root_hints().needs_priming());
+
+    for server in servers {
+        server.shutdown();
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_root_hints_priming() -> Result<(), String> {
+    // The hinted server publishes a new set of root servers, one of them without glue.
+    let root = spawn_zone_server(
+        "127.0.0.7:0",
+        ".",
+        "@ NS new.root.test.\n@ NS b.root.test.\nnew.root.test. A 127.0.0.8\n\
+         new.root.test. AAAA ::1\n",
+    )?;
+    let port = root.local_address().port();
+
+    let mut root_hints = RootHints::parse(
+        ". 3600 NS old.root.test.\n. 3600 NS b.root.test.\n\
+         old.root.test. 3600 A 127.0.0.7\nb.root.test. 3600 A 127.0.0.9\n",
+    )?;
+    assert!(root_hints.needs_priming());
+    root_hints.prime(port, &QueryOptions::default())?;
+    root.shutdown();
+
+    assert!(!root_hints.needs_priming());
+    assert_eq!(root_hints.ttl(), 60);
+    let servers: Vec<(String, Vec<String>)> = root_hints
+        .servers()
+        .iter()
+        .map(|server| {
+            let addresses = server.addresses.iter().map(|a| a.to_string()).collect();
+            (server.name.clone(), addresses)
+        })
+        .collect();
+    assert_eq!(
+        servers,
+        [
+            (
+                "new.root.test.".to_string(),
+                vec!["127.0.0.8".to_string(), "::1".to_string()]
+            ),
+            ("b.root.test.".to_string(), vec!["127.0.0.9".to_string()]),
+        ]
+    );
+
+    let options = QueryOptions {
+        timeout: std::time::Duration::from_millis(300),
+        ..QueryOptions::default()
+    };
+    assert!(root_hints.prime(port, &options).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_root_hints_priming_failure() -> Result<(), String> {
+    // One hinted server is down and the other does not serve the root zone, so priming fails.
+    let server = spawn_zone_server("127.0.0.10:0", "com.", "www A 192.0.2.1\n")?;
+    let port = server.local_address().port();
+    let root_hints = RootHints::parse(
+        ". 3600 NS a.root.test.\n. 3600 NS b.root.test.\n\
+         a.root.test. 3600 A 127.0.0.11\nb.root.test. 3600 A 127.0.0.10\n",
+    )?;
+    let options = QueryOptions {
+        timeout: std::time::Duration::from_millis(300),
+        ..QueryOptions::default()
+    };
+    let resolver = IterativeResolver::with_root_hints(root_hints, port, options);
+
+    // The hints are still used, and priming is not tried again for every name.
+    for _ in 0..2 {
+        let resolution = resolver.resolve("www.com.", DNS_TYPE_A)?;
+        assert_eq!(
+            resolution.answer[0].to_string(),
+            "www.com. 60 IN A 192.0.2.1"
+        );
+        assert!(!resolver.root_hints().needs_priming());
+    }
+    assert_eq!(resolver.root_hints().servers()[0].name, "a.root.test.");
+    server.shutdown();
+
+    Ok(())
+}
+
+#[test]
+fn test_truncated_response_retry() -> Result<(), String> {
+    // Twenty TXT records of 40 octets do not fit in 512 octets, so the server truncates them over UDP.
+    let records: String = (0..20)
+        .map(|index| format!("big TXT \"{:02}{}\"\n", index, "x".repeat(38)))
+        .collect();
+    let server = spawn_zone_server("127.0.0.1:0", "example.test.", &records)?;
+    let address = server.local_address().to_string();
+
+    let dns_packet = DnsPacket::new(&String::from("big.example.test."), DNS_TYPE_TXT)?;
+    let (dns_response, transport) =
+        send_dns_query_reporting_transport(&dns_packet, &address, &QueryOptions::default())?;
+    assert_eq!(transport, Transport::Tcp);
+    assert!(!dns_response.header.tc);
+    assert_eq!(dns_response.answer.len(), 20);
+
+    let dns_packet = DnsPacket::new(&String::from("example.test."), DNS_TYPE_SOA)?;
+    let (dns_response, transport) =
+        send_dns_query_reporting_transport(&dns_packet, &address, &QueryOptions::default())?;
+    assert_eq!(transport, Transport::Udp);
+    assert_eq!(dns_response.answer.len(), 1);
+
+    server.shutdown();
+
+    Ok(())
+}
+
+#[test]
+fn test_stub_resolver() -> Result<(), String> {
+    let zones = vec![
+        Zone::new(
+            ".",
+            parser::parse_master_file_str("$TTL 60\n@ SOA ns host 1 2 3 4 5\n", ".")?,
+        )?,
+        Zone::new(
+            "example.test.",
+            parser::parse_master_file_str(
+                "$TTL 60\n@ SOA ns host 1 2 3 4 5\nwww A 192.0.2.1\nhost A 192.0.2.2\n",
+                "example.test.",
+            )?,
+        )?,
+    ];
+    let server = AuthoritativeServer::new(zones)?.spawn("127.0.0.1:0")?;
+
+    // Nothing listens on the first name server, so every query falls back to the second.
+    let unused = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unused_address = unused.local_addr().unwrap();
+    drop(unused);
+    let config = ResolverConfig::parse("search example.test\noptions attempts:1 rotate edns0\n");
+    let config = ResolverConfig {
+        nameservers: vec![unused_address, server.local_address()],
+        timeout: std::time::Duration::from_millis(300),
+        ..config
+    };
+    let resolver = Resolver::new(config.clone());
+
+    let response = resolver.lookup("www", DNS_TYPE_A)?;
+    assert_eq!(
+        response.answer[0].to_string(),
+        "www.example.test. 60 IN A 192.0.2.1"
+    );
+    assert!(response.opt().is_some());
+
+    // A name that exists without the type is preferred over a later name error.
+    let response = resolver.lookup("host", DNS_TYPE_AAAA)?;
+    assert_eq!(response.header.rcode, DNS_RCODE_NO_ERROR);
+    assert_eq!(response.question[0].qname, "host.example.test.");
+    let response = resolver.lookup("missing", DNS_TYPE_A)?;
+    assert_eq!(response.header.rcode, DNS_RCODE_NAME_ERROR);
+    assert_eq!(response.question[0].qname, "missing.");
+
+    let resolver = Resolver::new(ResolverConfig {
+        use_vc: true,
+        ..config
+    });
+    let response = resolver.lookup("www.example.test.", DNS_TYPE_A)?;
+    assert_eq!(response.answer.len(), 1);
+
+    server.shutdown();
+
+    Ok(())
+}
+
+#[test]
+fn test_host_resolver() -> Result<(), String> {
+    let zones = vec![
+        Zone::new(
+            "example.test.",
+            parser::parse_master_file_str(
+                "$TTL 60\n@ SOA ns host 1 2 3 4 5\nwww A 192.0.2.1\nwww AAAA 2001:db8::1\n\
+                 alias CNAME www\n",
+                "example.test.",
+            )?,
+        )?,
+        Zone::new(
+            "2.0.192.in-addr.arpa.",
+            parser::parse_master_file_str(
+                "$TTL 60\n@ SOA ns host 1 2 3 4 5\n1 PTR www.example.test.\n",
+                "2.0.192.in-addr.arpa.",
+            )?,
+        )?,
+    ];
+    let server = AuthoritativeServer::new(zones)?.spawn("127.0.0.1:0")?;
+    let config = ResolverConfig {
+        nameservers: vec![server.local_address()],
+        attempts: 1,
+        timeout: std::time::Duration::from_millis(300),
+        ..ResolverConfig::default()
+    };
+    let hosts = Hosts::parse("192.0.2.99 www.example.test www\n");
+
+    let to_strings = |addresses: Vec<std::net::IpAddr>| -> Vec<String> {
+        addresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect()
+    };
+    let resolver = HostResolver::new(
+        hosts.clone(),
+        Resolver::new(config.clone()),
+        DEFAULT_HOSTS_ORDER.to_vec(),
+    );
+    assert_eq!(
+        to_strings(resolver.lookup_host("WWW.example.test")?),
+        ["192.0.2.99"]
+    );
+    assert_eq!(
+        to_strings(resolver.lookup_host("alias.example.test.")?),
+        ["192.0.2.1", "2001:db8::1"]
+    );
+    assert!(resolver.lookup_host("missing.example.test.").is_err());
+    assert_eq!(
+        resolver.lookup_address(&"192.0.2.99".parse().unwrap())?,
+        ["www.example.test.", "www."]
+    );
+    assert_eq!(
+        resolver.lookup_address(&"192.0.2.1".parse().unwrap())?,
+        ["www.example.test."]
+    );
+    assert!(resolver
+        .lookup_address(&"192.0.2.2".parse().unwrap())
+        .is_err());
+
+    let resolver = HostResolver::new(
+        hosts,
+        Resolver::new(config.clone()),
+        parse_hosts_order("hosts: dns files\n"),
+    );
+    assert_eq!(
+        to_strings(resolver.lookup_host("www.example.test")?),
+        ["192.0.2.1", "2001:db8::1"]
+    );
+    assert_eq!(to_strings(resolver.lookup_host("www")?), ["192.0.2.99"]);
+    server.shutdown();
+
+    // The IPv4 addresses are returned even when the lookup of the IPv6 addresses fails.
+    let zone = Zone::new(
+        "example.test.",
+        parser::parse_master_file_str(
+            "$TTL 60\n@ SOA ns host 1 2 3 4 5\nwww A 192.0.2.1\nwww AAAA 2001:db8::1\n",
+            "example.test.",
+        )?,
+    )?;
+    let server = spawn_server(
+        FailingServer {
+            server: AuthoritativeServer::new(vec![zone])?,
+            rrtype: DNS_TYPE_AAAA,
+        },
+        "127.0.0.1:0",
+    )?;
+    let config = ResolverConfig {
+        nameservers: vec![server.local_address()],
+        ..config
+    };
+    let resolver = HostResolver::new(
+        Hosts::default(),
+        Resolver::new(config),
+        DEFAULT_HOSTS_ORDER.to_vec(),
+    );
+    assert_eq!(
+        to_strings(resolver.lookup_host("www.example.test.")?),
+        ["192.0.2.1"]
+    );
+    server.shutdown();
+
+    Ok(())
+}
+
+#[test]
+fn test_forwarder() -> Result<(), String> {
+    let upstream = spawn_zone_server(
+        "127.0.0.1:0",
+        "example.test.",
+        "www 1 A 192.0.2.1\nalias 1 CNAME www\nslow 60 A 192.0.2.2\n",
+    )?;
+    let mut config = ForwarderConfig::new(vec![upstream.local_address()]);
+    config.options.timeout = std::time::Duration::from_millis(200);
+    let forwarder = std::sync::Arc::new(Forwarder::new(config)?);
+    let handle = spawn_server(forwarder.clone(), "127.0.0.1:0")?;
+    let address = handle.local_address().to_string();
+
+    // The upstream answer comes back under the client's ID, then later queries are answered from cache.
+    let query = DnsPacket::new(&String::from("Alias.Example.Test."), DNS_TYPE_A)?;
+    let response = send_dns_query_to(&query, &address)?;
+    assert_eq!(response.header.id, query.header.id);
+    assert_eq!(response.question[0].qname, "Alias.Example.Test.");
+    assert_eq!(response.answer.len(), 2);
+    let response = send_dns_query_to(&query, &address)?;
+    assert!(response.header.ra && !response.header.aa);
+    assert_eq!(
+        response.answer[1].to_string(),
+        "www.example.test. 1 IN A 192.0.2.1"
+    );
+    assert_eq!(forwarder.cache().statistics().hits, 2);
+
+    let options = QueryOptions {
+        transport: rusty_dns::Transport::Tcp,
+        ..QueryOptions::default()
+    };
+    let query = DnsPacket::new(&String::from("missing.example.test."), DNS_TYPE_A)?;
+    let response = send_dns_query_with_options(&query, &address, &options)?;
+    assert_eq!(response.header.rcode, DNS_RCODE_NAME_ERROR);
+
+    // Once the upstream is gone, expired answers are served stale and others fail.
+    upstream.shutdown();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let query = DnsPacket::new(&String::from("alias.example.test."), DNS_TYPE_A)?;
+    let response = send_dns_query_to(&query, &address)?;
+    assert_eq!(response.header.rcode, DNS_RCODE_NO_ERROR);
+    assert_eq!(
+        response.answer[1].to_string(),
+        "www.example.test. 30 IN A 192.0.2.1"
+    );
+    let query = DnsPacket::new(&String::from("slow.example.test."), DNS_TYPE_A)?;
+    let response = send_dns_query_to(&query, &address)?;
+    assert_eq!(response.header.rcode, DNS_RCODE_SERVER_ERROR);
+
+    handle.shutdown();
+
+    Ok(())
+}
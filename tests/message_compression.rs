@@ -0,0 +1,38 @@
+//! Turning message compression off affects the whole process, so this test runs in its own binary
+//! where it cannot change the output of the byte-exact serializer tests.
+
+use rusty_dns::{dns_packet::*, wire::*};
+
+#[test]
+fn test_message_compression_toggle() -> Result<(), String> {
+    let compressed = [
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // header
+        0x03, 0x77, 0x77, 0x77, 0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F,
+        0x6D, 0x00, 0x00, 0x01, 0x00, 0x01, // www.example.com. IN A
+        0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04, 0xC0, 0x00, 0x02,
+        0x01, // www.example.com. 3600 IN A 192.0.2.1, the owner name compressed
+    ];
+    let uncompressed = [
+        0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // header
+        0x03, 0x77, 0x77, 0x77, 0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F,
+        0x6D, 0x00, 0x00, 0x01, 0x00, 0x01, // www.example.com. IN A
+        0x03, 0x77, 0x77, 0x77, 0x07, 0x65, 0x78, 0x61, 0x6D, 0x70, 0x6C, 0x65, 0x03, 0x63, 0x6F,
+        0x6D, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04, 0xC0, 0x00, 0x02,
+        0x01, // www.example.com. 3600 IN A 192.0.2.1
+    ];
+    let dns_packet = DnsPacket::parse_dns_packet(&compressed)?;
+
+    assert!(message_compression());
+    assert_eq!(dns_packet.serialize()?, compressed);
+
+    set_message_compression(false);
+    let result = dns_packet.serialize();
+    let disabled = message_compression();
+    set_message_compression(true);
+    assert!(!disabled);
+    assert_eq!(result?, uncompressed);
+
+    assert_eq!(dns_packet.serialize()?, compressed);
+
+    Ok(())
+}